@@ -0,0 +1,268 @@
+use alloc::vec::Vec;
+use core::cmp;
+use core::mem;
+
+use deflate;
+use gzip;
+use io::{self, Read, Write};
+use lz77;
+use non_blocking;
+use zlib;
+
+const BUFFER_SIZE: usize = 8 * 1024;
+
+/// A blocking encoder which writes the encoded data to a `Vec<u8>`.
+pub trait BufferEncode: Write {
+    fn sync_flush(&mut self) -> io::Result<()>;
+    fn output_mut(&mut self) -> &mut Vec<u8>;
+    fn finish_output(self) -> io::Result<Vec<u8>>;
+}
+impl<E> BufferEncode for deflate::Encoder<Vec<u8>, E>
+where
+    E: lz77::Lz77Encode,
+{
+    fn sync_flush(&mut self) -> io::Result<()> {
+        deflate::Encoder::sync_flush(self)
+    }
+    fn output_mut(&mut self) -> &mut Vec<u8> {
+        self.as_inner_mut()
+    }
+    fn finish_output(self) -> io::Result<Vec<u8>> {
+        self.finish().into_result()
+    }
+}
+impl<E> BufferEncode for zlib::Encoder<Vec<u8>, E>
+where
+    E: lz77::Lz77Encode,
+{
+    fn sync_flush(&mut self) -> io::Result<()> {
+        zlib::Encoder::sync_flush(self)
+    }
+    fn output_mut(&mut self) -> &mut Vec<u8> {
+        self.as_inner_mut()
+    }
+    fn finish_output(self) -> io::Result<Vec<u8>> {
+        self.finish().into_result()
+    }
+}
+impl<E> BufferEncode for gzip::Encoder<Vec<u8>, E>
+where
+    E: lz77::Lz77Encode,
+{
+    fn sync_flush(&mut self) -> io::Result<()> {
+        gzip::Encoder::sync_flush(self)
+    }
+    fn output_mut(&mut self) -> &mut Vec<u8> {
+        self.as_inner_mut()
+    }
+    fn finish_output(self) -> io::Result<Vec<u8>> {
+        self.finish().into_result()
+    }
+}
+
+/// An encoder which compresses the data read from the inner reader.
+#[derive(Debug)]
+pub struct ReadEncoder<T, R> {
+    encoder: Option<T>,
+    inner: R,
+    input: Vec<u8>,
+    buf: Vec<u8>,
+    offset: usize,
+}
+impl<T, R> ReadEncoder<T, R>
+where
+    T: BufferEncode,
+    R: Read,
+{
+    pub fn new(encoder: T, inner: R) -> Self {
+        ReadEncoder {
+            encoder: Some(encoder),
+            inner,
+            input: vec![0; BUFFER_SIZE],
+            buf: Vec::new(),
+            offset: 0,
+        }
+    }
+    pub fn as_inner_ref(&self) -> &R {
+        &self.inner
+    }
+    pub fn as_inner_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+impl<T, R> Read for ReadEncoder<T, R>
+where
+    T: BufferEncode,
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.offset == self.buf.len() {
+            self.buf.clear();
+            self.offset = 0;
+            let read_size = match self.encoder {
+                None => return Ok(0),
+                Some(_) => self.inner.read(&mut self.input)?,
+            };
+            if read_size == 0 {
+                let encoder = self.encoder.take().expect("Never fails");
+                self.buf = encoder.finish_output()?;
+            } else {
+                let encoder = self.encoder.as_mut().expect("Never fails");
+                encoder.write_all(&self.input[..read_size])?;
+                mem::swap(&mut self.buf, encoder.output_mut());
+            }
+        }
+        let size = cmp::min(buf.len(), self.buf.len() - self.offset);
+        buf[..size].copy_from_slice(&self.buf[self.offset..][..size]);
+        self.offset += size;
+        Ok(size)
+    }
+}
+
+/// The input buffer of a decoder which receives the encoded data through `io::Write`.
+///
+/// `read` returns `WouldBlock` if the buffer is empty,
+/// and `Ok(0)` after `set_eof` is called.
+#[derive(Debug, Default)]
+pub struct InputBuffer {
+    buf: Vec<u8>,
+    offset: usize,
+    eof: bool,
+}
+impl InputBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn push(&mut self, buf: &[u8]) {
+        if self.offset == self.buf.len() {
+            self.buf.clear();
+            self.offset = 0;
+        }
+        self.buf.extend_from_slice(buf);
+    }
+    pub fn set_eof(&mut self) {
+        self.eof = true;
+    }
+    pub fn discard_remaining(&mut self) -> usize {
+        let size = self.buf.len() - self.offset;
+        self.buf.clear();
+        self.offset = 0;
+        size
+    }
+    pub fn take_remaining(&mut self) -> Vec<u8> {
+        let remaining = self.buf.split_off(self.offset);
+        self.buf.clear();
+        self.offset = 0;
+        remaining
+    }
+}
+impl Read for InputBuffer {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.offset < self.buf.len() {
+            let size = cmp::min(buf.len(), self.buf.len() - self.offset);
+            buf[..size].copy_from_slice(&self.buf[self.offset..][..size]);
+            self.offset += size;
+            Ok(size)
+        } else if self.eof {
+            Ok(0)
+        } else {
+            Err(io::Error::new(io::ErrorKind::WouldBlock, "Would block"))
+        }
+    }
+}
+
+/// A non-blocking decoder which reads the encoded data from an `InputBuffer`.
+pub trait BufferDecode: Read {
+    fn input_mut(&mut self) -> &mut InputBuffer;
+}
+impl BufferDecode for non_blocking::deflate::Decoder<InputBuffer> {
+    fn input_mut(&mut self) -> &mut InputBuffer {
+        self.as_inner_mut()
+    }
+}
+impl BufferDecode for non_blocking::zlib::Decoder<InputBuffer> {
+    fn input_mut(&mut self) -> &mut InputBuffer {
+        self.as_inner_mut()
+    }
+}
+impl BufferDecode for non_blocking::gzip::Decoder<InputBuffer> {
+    fn input_mut(&mut self) -> &mut InputBuffer {
+        self.as_inner_mut()
+    }
+}
+
+/// A decoder which receives the encoded data through `io::Write`,
+/// and writes the decoded data to the inner writer.
+#[derive(Debug)]
+pub struct WriteDecoder<T, W> {
+    decoder: T,
+    inner: W,
+    buf: Vec<u8>,
+    eos: bool,
+}
+impl<T, W> WriteDecoder<T, W>
+where
+    T: BufferDecode,
+    W: Write,
+{
+    pub fn new(decoder: T, inner: W) -> Self {
+        WriteDecoder {
+            decoder,
+            inner,
+            buf: vec![0; BUFFER_SIZE],
+            eos: false,
+        }
+    }
+    pub fn decoder_mut(&mut self) -> &mut T {
+        &mut self.decoder
+    }
+    pub fn is_eos(&self) -> bool {
+        self.eos
+    }
+
+    /// Decodes the remaining data, and returns the inner writer and the trailing bytes.
+    pub fn finish(mut self) -> (W, Vec<u8>, Option<io::Error>) {
+        self.decoder.input_mut().set_eof();
+        let result = self.decode().and_then(|()| self.inner.flush());
+        let trailing = self.decoder.input_mut().take_remaining();
+        (self.inner, trailing, result.err())
+    }
+    pub fn as_inner_ref(&self) -> &W {
+        &self.inner
+    }
+    pub fn as_inner_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn decode(&mut self) -> io::Result<()> {
+        while !self.eos {
+            match self.decoder.read(&mut self.buf) {
+                Ok(0) => self.eos = true,
+                Ok(size) => self.inner.write_all(&self.buf[..size])?,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+impl<T, W> Write for WriteDecoder<T, W>
+where
+    T: BufferDecode,
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.decoder.input_mut().push(buf);
+        self.decode()?;
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
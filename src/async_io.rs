@@ -0,0 +1,233 @@
+//! Adapters which drive the non-blocking encoders and decoders over asynchronous streams.
+//!
+//! `AsyncIo` implements `io::Read` and `io::Write` by polling the inner asynchronous stream
+//! with the waker of the current task, and `Poll::Pending` is converted to `WouldBlock`.
+//! The non-blocking state machines in `non_blocking` handle `WouldBlock` by suspending the operation,
+//! so the async encoders and decoders only have to convert `WouldBlock` back to `Poll::Pending`.
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+use io;
+
+/// The marker of the streams which implement the traits of `futures-io`.
+#[cfg(feature = "futures-io")]
+#[derive(Debug)]
+pub enum FuturesIo {}
+
+/// The marker of the streams which implement the traits of `tokio`.
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub enum TokioIo {}
+
+/// A blocking-style view of an asynchronous stream.
+///
+/// `M` is the marker of the async I/O traits which `T` implements.
+#[derive(Debug)]
+pub struct AsyncIo<T, M> {
+    inner: T,
+    waker: Option<Waker>,
+    _marker: PhantomData<M>,
+}
+impl<T, M> AsyncIo<T, M>
+where
+    T: Unpin,
+{
+    pub fn new(inner: T) -> Self {
+        AsyncIo {
+            inner,
+            waker: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Registers the waker of the current task, which is used to poll the inner stream.
+    pub fn register(&mut self, cx: &Context) {
+        match self.waker {
+            Some(ref waker) if waker.will_wake(cx.waker()) => {}
+            _ => self.waker = Some(cx.waker().clone()),
+        }
+    }
+    pub fn as_inner_ref(&self) -> &T {
+        &self.inner
+    }
+    pub fn as_inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn poll<F, U>(&mut self, f: F) -> io::Result<U>
+    where
+        F: FnOnce(Pin<&mut T>, &mut Context) -> Poll<io::Result<U>>,
+    {
+        let waker = self.waker.as_ref().expect("No waker is registered");
+        let mut cx = Context::from_waker(waker);
+        match f(Pin::new(&mut self.inner), &mut cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => Err(io::Error::new(io::ErrorKind::WouldBlock, "Pending")),
+        }
+    }
+}
+#[cfg(feature = "futures-io")]
+impl<T> io::Read for AsyncIo<T, FuturesIo>
+where
+    T: futures_io::AsyncRead + Unpin,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.poll(|inner, cx| inner.poll_read(cx, buf))
+    }
+}
+#[cfg(feature = "futures-io")]
+impl<T> io::Write for AsyncIo<T, FuturesIo>
+where
+    T: futures_io::AsyncWrite + Unpin,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.poll(|inner, cx| inner.poll_write(cx, buf))
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.poll(|inner, cx| inner.poll_flush(cx))
+    }
+}
+#[cfg(feature = "futures-io")]
+impl<T> AsyncIo<T, FuturesIo>
+where
+    T: futures_io::AsyncWrite + Unpin,
+{
+    pub fn close(&mut self) -> io::Result<()> {
+        self.poll(|inner, cx| inner.poll_close(cx))
+    }
+}
+#[cfg(feature = "tokio")]
+impl<T> io::Read for AsyncIo<T, TokioIo>
+where
+    T: tokio::io::AsyncRead + Unpin,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut buf = tokio::io::ReadBuf::new(buf);
+        self.poll(|inner, cx| inner.poll_read(cx, &mut buf))?;
+        Ok(buf.filled().len())
+    }
+}
+#[cfg(feature = "tokio")]
+impl<T> io::Write for AsyncIo<T, TokioIo>
+where
+    T: tokio::io::AsyncWrite + Unpin,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.poll(|inner, cx| inner.poll_write(cx, buf))
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.poll(|inner, cx| inner.poll_flush(cx))
+    }
+}
+#[cfg(feature = "tokio")]
+impl<T> AsyncIo<T, TokioIo>
+where
+    T: tokio::io::AsyncWrite + Unpin,
+{
+    pub fn shutdown(&mut self) -> io::Result<()> {
+        self.poll(|inner, cx| inner.poll_shutdown(cx))
+    }
+}
+
+/// Converts the result of a non-blocking operation to `Poll`.
+pub fn into_poll<T>(result: io::Result<T>) -> Poll<io::Result<T>> {
+    match result {
+        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Poll::Pending,
+        result => Poll::Ready(result),
+    }
+}
+
+/// A stream which returns `Poll::Pending` at every other call, to test the async encoders and decoders.
+#[cfg(test)]
+#[derive(Debug)]
+pub struct Intermittent<T> {
+    inner: T,
+    pending: bool,
+}
+#[cfg(test)]
+impl<T> Intermittent<T> {
+    pub fn new(inner: T) -> Self {
+        Intermittent {
+            inner,
+            pending: false,
+        }
+    }
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn poll<F, U>(self: Pin<&mut Self>, cx: &mut Context, f: F) -> Poll<U>
+    where
+        T: Unpin,
+        F: FnOnce(Pin<&mut T>, &mut Context) -> Poll<U>,
+    {
+        let this = self.get_mut();
+        this.pending = !this.pending;
+        if this.pending {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        } else {
+            f(Pin::new(&mut this.inner), cx)
+        }
+    }
+}
+#[cfg(all(test, feature = "futures-io"))]
+impl<T> futures_io::AsyncRead for Intermittent<T>
+where
+    T: futures_io::AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll(cx, |inner, cx| inner.poll_read(cx, buf))
+    }
+}
+#[cfg(all(test, feature = "futures-io"))]
+impl<T> futures_io::AsyncWrite for Intermittent<T>
+where
+    T: futures_io::AsyncWrite + Unpin,
+{
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.poll(cx, |inner, cx| inner.poll_write(cx, buf))
+    }
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        self.poll(cx, |inner, cx| inner.poll_flush(cx))
+    }
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        self.poll(cx, |inner, cx| inner.poll_close(cx))
+    }
+}
+#[cfg(all(test, feature = "tokio"))]
+impl<T> tokio::io::AsyncRead for Intermittent<T>
+where
+    T: tokio::io::AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut tokio::io::ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        self.poll(cx, |inner, cx| inner.poll_read(cx, buf))
+    }
+}
+#[cfg(all(test, feature = "tokio"))]
+impl<T> tokio::io::AsyncWrite for Intermittent<T>
+where
+    T: tokio::io::AsyncWrite + Unpin,
+{
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.poll(cx, |inner, cx| inner.poll_write(cx, buf))
+    }
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        self.poll(cx, |inner, cx| inner.poll_flush(cx))
+    }
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        self.poll(cx, |inner, cx| inner.poll_shutdown(cx))
+    }
+}
@@ -1,7 +1,9 @@
-use byteorder::LittleEndian;
-use byteorder::ReadBytesExt;
-use byteorder::WriteBytesExt;
-use std::io;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, LittleEndian};
+use core::cmp;
+
+use io;
+use io::WriteBytesExt;
 
 #[derive(Debug)]
 pub struct BitWriter<W> {
@@ -32,6 +34,9 @@ where
         self.end += bitwidth;
         self.flush_if_needed()
     }
+    pub fn bit_offset(&self) -> u8 {
+        self.end % 8
+    }
     pub fn flush(&mut self) -> io::Result<()> {
         while self.end > 0 {
             self.inner.write_u8(self.buf as u8)?;
@@ -63,28 +68,46 @@ impl<W> BitWriter<W> {
     }
 }
 
+/// A reader of bit streams (the least significant bit of each byte comes first).
+///
+/// Up to 64 bits are buffered, and they are refilled with several bytes at once
+/// from the internal buffer of the inner `BufRead`.
+///
+/// The bytes which have been loaded into the bit buffer are not consumed from the inner stream
+/// until the reader moves to the next internal buffer of it or `as_inner_mut` (or `into_inner`) is called.
+/// So the inner stream is never advanced beyond the bytes of which bits have actually been read,
+/// except the bytes which were crossing the boundary of the internal buffers.
 #[derive(Debug)]
 pub struct BitReader<R> {
     inner: R,
-    last_read: u32,
-    offset: u8,
+    bits: u64,
+    nbits: u8,
+    pending: usize,
+    buffered: usize,
+    consumed: u64,
     last_error: Option<io::Error>,
 }
 impl<R> BitReader<R>
 where
-    R: io::Read,
+    R: io::BufRead,
 {
     pub fn new(inner: R) -> Self {
         BitReader {
             inner,
-            last_read: 0,
-            offset: 32,
+            bits: 0,
+            nbits: 0,
+            pending: 0,
+            buffered: 0,
+            consumed: 0,
             last_error: None,
         }
     }
     #[inline(always)]
     pub fn set_last_error(&mut self, e: io::Error) {
-        self.last_error = Some(e);
+        // The first error is the cause (e.g., `WouldBlock`) of the succeeding ones
+        if self.last_error.is_none() {
+            self.last_error = Some(e);
+        }
     }
     #[inline(always)]
     pub fn check_last_error(&mut self) -> io::Result<()> {
@@ -112,71 +135,216 @@ where
     #[inline(always)]
     pub fn peek_bits_unchecked(&mut self, bitwidth: u8) -> u16 {
         debug_assert!(bitwidth <= 16);
-        while 32 < self.offset + bitwidth {
-            if self.last_error.is_some() {
-                return 0;
-            }
-            if let Err(e) = self.fill_next_u8() {
-                self.last_error = Some(e);
-                return 0;
-            }
+        if self.nbits < bitwidth {
+            self.refill(bitwidth);
         }
-        debug_assert!(self.offset < 32 || bitwidth == 0);
-        let bits = self.last_read.wrapping_shr(u32::from(self.offset)) as u16;
-        bits & ((1 << bitwidth) - 1)
+        (self.bits & ((1 << bitwidth) - 1)) as u16
     }
+    /// Loads the bits from the current internal buffer of the inner stream,
+    /// so that `bitwidth` bits become available if possible.
+    ///
+    /// Unlike `peek_bits_unchecked`, this never moves to the next internal buffer nor fails,
+    /// so fewer bits may be available afterwards (see `buffered_bitwidth`).
     #[inline(always)]
-    pub fn skip_bits(&mut self, bitwidth: u8) {
-        debug_assert!(self.last_error.is_some() || 32 - self.offset >= bitwidth);
-        self.offset += bitwidth;
+    pub fn preload(&mut self, bitwidth: u8) {
+        if self.nbits < bitwidth && self.pending < self.buffered {
+            self.load_buffered();
+        }
     }
+
+    /// Returns the loaded bits (the bits beyond `buffered_bitwidth` are zero).
     #[inline(always)]
-    fn fill_next_u8(&mut self) -> io::Result<()> {
-        self.offset -= 8;
-        self.last_read >>= 8;
+    pub fn buffered_bits(&self) -> u64 {
+        self.bits
+    }
 
-        let next = u32::from(self.inner.read_u8()?);
-        self.last_read |= next << (32 - 8);
-        Ok(())
+    /// Returns the number of the loaded bits.
+    #[inline(always)]
+    pub fn buffered_bitwidth(&self) -> u8 {
+        self.nbits
+    }
+    #[inline(always)]
+    pub fn skip_bits(&mut self, bitwidth: u8) {
+        debug_assert!(self.last_error.is_some() || self.nbits >= bitwidth);
+        self.bits >>= bitwidth;
+        self.nbits = self.nbits.saturating_sub(bitwidth);
+    }
+
+    /// Skips the remaining bits of the current byte.
+    pub fn align_to_byte(&mut self) {
+        let padding = self.nbits % 8;
+        self.skip_bits(padding);
+    }
+
+    /// Consumes the bytes of which bits have been read from the inner stream.
+    ///
+    /// The unread whole bytes in the bit buffer are left in the inner stream if they have not been consumed yet.
+    pub fn sync(&mut self) {
+        let unread = cmp::min(usize::from(self.nbits / 8), self.pending);
+        self.inner.consume(self.pending - unread);
+        self.consumed += (self.pending - unread) as u64;
+        self.pending = 0;
+        self.buffered = 0;
+        self.nbits -= (unread * 8) as u8;
+        self.bits &= (!0u64).checked_shr(64 - u32::from(self.nbits)).unwrap_or(0);
+    }
+    pub fn as_inner_mut(&mut self) -> &mut R {
+        self.sync();
+        &mut self.inner
+    }
+    pub fn into_inner(mut self) -> R {
+        self.sync();
+        self.inner
     }
     #[inline]
     pub(crate) fn state(&self) -> BitReaderState {
         BitReaderState {
-            last_read: self.last_read,
-            offset: self.offset,
+            bits: self.bits,
+            nbits: self.nbits,
+            pending: self.pending,
+            buffered: self.buffered,
+            consumed: self.consumed,
         }
     }
     #[inline]
     pub(crate) fn restore_state(&mut self, state: BitReaderState) {
-        self.last_read = state.last_read;
-        self.offset = state.offset;
+        self.bits = state.bits;
+        self.nbits = state.nbits;
+        self.pending = state.pending;
+        self.buffered = state.buffered;
+        self.consumed = state.consumed;
+    }
+
+    #[inline(never)]
+    fn refill(&mut self, bitwidth: u8) {
+        while self.nbits < bitwidth {
+            if self.last_error.is_some() {
+                return;
+            }
+            if let Err(e) = self.fill() {
+                self.last_error = Some(e);
+                return;
+            }
+        }
+    }
+    fn fill(&mut self) -> io::Result<()> {
+        let mut buf = self.inner.fill_buf()?;
+        if buf.len() == self.pending {
+            // All bytes of the current buffer have been loaded
+            self.inner.consume(self.pending);
+            self.consumed += self.pending as u64;
+            self.pending = 0;
+            self.buffered = 0;
+            buf = self.inner.fill_buf()?;
+            if buf.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Unexpected end of the bit stream",
+                ));
+            }
+        }
+
+        let (word, size) = next_word(&buf[self.pending..], self.nbits);
+        self.bits |= word << self.nbits;
+        self.nbits += (size * 8) as u8;
+        self.pending += size;
+        self.buffered = buf.len();
+        Ok(())
+    }
+    #[inline(never)]
+    fn load_buffered(&mut self) {
+        if let Ok(buf) = self.inner.fill_buf() {
+            let (word, size) = next_word(&buf[self.pending..], self.nbits);
+            self.bits |= word << self.nbits;
+            self.nbits += (size * 8) as u8;
+            self.pending += size;
+        }
     }
 }
 impl<R> BitReader<R> {
-    pub fn reset(&mut self) {
-        self.offset = 32;
+    /// Returns the number of the bytes read so far (a partially read byte is also counted).
+    pub fn total_in(&self) -> u64 {
+        self.consumed + self.pending as u64 - u64::from(self.nbits / 8)
+    }
+
+    /// Returns the whole bytes in the bit buffer which have already been consumed from the inner stream
+    /// but not read yet.
+    pub fn unread_bytes(&self) -> Vec<u8> {
+        let unread = usize::from(self.nbits / 8).saturating_sub(self.pending);
+        let offset = usize::from(self.nbits % 8);
+        (0..unread)
+            .map(|i| (self.bits >> (offset + i * 8)) as u8)
+            .collect()
+    }
+
+    /// Returns the number of the bytes which have been read from the internal buffer of the inner stream
+    /// but not consumed yet (they are consumed by `sync`).
+    pub(crate) fn read_unconsumed_bytes(&self) -> usize {
+        self.pending - cmp::min(usize::from(self.nbits / 8), self.pending)
     }
     pub fn as_inner_ref(&self) -> &R {
         &self.inner
     }
-    pub fn as_inner_mut(&mut self) -> &mut R {
+
+    /// Returns the mutable reference to the inner stream without consuming the loaded bytes.
+    pub(crate) fn as_inner_mut_unsynced(&mut self) -> &mut R {
         &mut self.inner
     }
-    pub fn into_inner(self) -> R {
-        self.inner
+}
+impl<R> io::Read for BitReader<R>
+where
+    R: io::BufRead,
+{
+    /// Reads byte-aligned data (`align_to_byte` must be called beforehand).
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        debug_assert_eq!(self.nbits % 8, 0);
+        if self.nbits < 8 {
+            self.sync();
+            let size = self.inner.read(buf)?;
+            self.consumed += size as u64;
+            return Ok(size);
+        }
+
+        let size = cmp::min(buf.len(), usize::from(self.nbits / 8));
+        for b in &mut buf[..size] {
+            *b = self.bits as u8;
+            self.bits >>= 8;
+        }
+        self.nbits -= (size * 8) as u8;
+        Ok(size)
+    }
+}
+
+/// Returns the bytes at the head of `buf` which fit in the bit buffer holding `nbits` bits, and the number of them.
+#[inline(always)]
+fn next_word(buf: &[u8], nbits: u8) -> (u64, usize) {
+    let size = cmp::min(buf.len(), usize::from((64 - nbits) / 8));
+    if size == 0 {
+        (0, 0)
+    } else if buf.len() >= 8 {
+        (LittleEndian::read_u64(buf) & (!0 >> (64 - size * 8)), size)
+    } else {
+        let word = buf[..size]
+            .iter()
+            .rev()
+            .fold(0, |word, &b| (word << 8) | u64::from(b));
+        (word, size)
     }
 }
 
 #[derive(Debug, Clone, Copy)]
 pub(crate) struct BitReaderState {
-    last_read: u32,
-    offset: u8,
+    bits: u64,
+    nbits: u8,
+    pending: usize,
+    buffered: usize,
+    consumed: u64,
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use std::io;
+    use io;
 
     #[test]
     fn writer_works() {
@@ -208,4 +376,34 @@ mod test {
             Err(io::ErrorKind::UnexpectedEof)
         );
     }
+
+    #[test]
+    fn reader_keeps_first_error() {
+        let buf = [0b10100101];
+        let mut reader = BitReader::new(&buf[..]);
+        reader.peek_bits_unchecked(16);
+        reader.set_last_error(invalid_data_error!("Invalid symbol"));
+        assert_eq!(
+            reader.check_last_error().map_err(|e| e.kind()),
+            Err(io::ErrorKind::UnexpectedEof)
+        );
+        assert!(reader.check_last_error().is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn reader_consumes_only_read_bytes() {
+        let buf = (0..100).collect::<Vec<u8>>();
+        for &capacity in &[1, 3, 8, 100] {
+            let mut reader = BitReader::new(io::BufReader::with_capacity(capacity, &buf[..]));
+            for &b in &buf[..50] {
+                assert_eq!(reader.read_bits(8).unwrap(), u16::from(b));
+            }
+            assert_eq!(reader.read_bits(3).unwrap(), u16::from(buf[50] & 0b111));
+
+            let mut rest = Vec::new();
+            io::Read::read_to_end(reader.as_inner_mut(), &mut rest).unwrap();
+            assert_eq!(rest, &buf[51..]);
+        }
+    }
 }
@@ -1,6 +1,6 @@
 use adler32::RollingAdler32;
+use core::fmt;
 use crc::{crc32, Hasher32};
-use std::fmt;
 
 pub struct Adler32(RollingAdler32);
 impl Adler32 {
@@ -38,6 +38,61 @@ impl fmt::Debug for Crc32 {
     }
 }
 
+/// Returns the Adler-32 checksum of the concatenation of two byte sequences.
+///
+/// `adler1` and `adler2` are the checksums of the sequences, and `len2` is the length of the latter.
+#[cfg_attr(not(feature = "std"), allow(dead_code))] // Used only by `ParallelEncoder` for now
+pub fn combine_adler32(adler1: u32, adler2: u32, len2: u64) -> u32 {
+    const BASE: u64 = 65_521;
+    let rem = len2 % BASE;
+    let a1 = u64::from(adler1 & 0xFFFF);
+    let b1 = u64::from(adler1 >> 16);
+    let a2 = u64::from(adler2 & 0xFFFF);
+    let b2 = u64::from(adler2 >> 16);
+    let a = (a1 + a2 + BASE - 1) % BASE;
+    let b = (rem * a1 + b1 + b2 + BASE - rem) % BASE;
+    ((b << 16) | a) as u32
+}
+
+/// Returns the CRC-32 checksum of the concatenation of two byte sequences.
+///
+/// `crc1` and `crc2` are the checksums of the sequences, and `len2` is the length of the latter.
+#[cfg_attr(not(feature = "std"), allow(dead_code))] // Used only by `ParallelEncoder` for now
+pub fn combine_crc32(crc1: u32, crc2: u32, len2: u64) -> u32 {
+    // `crc1` is shifted by `len2` zero bytes (i.e., multiplied by `x^(8 * len2)`),
+    // by the squaring of `x^(2^k)` in GF(2) modulo the CRC polynomial.
+    let mut power = 1 << 30; // x^1
+    let mut shift = 1 << 31; // x^0
+    let mut n = len2 * 8;
+    while n != 0 {
+        if n & 1 == 1 {
+            shift = multiply_mod_crc32(power, shift);
+        }
+        power = multiply_mod_crc32(power, power);
+        n >>= 1;
+    }
+    multiply_mod_crc32(shift, crc1) ^ crc2
+}
+
+/// Multiplies two polynomials in the bit-reflected representation modulo the CRC-32 polynomial.
+fn multiply_mod_crc32(a: u32, mut b: u32) -> u32 {
+    const POLYNOMIAL: u32 = 0xEDB8_8320;
+    let mut product = 0;
+    let mut m = 1 << 31;
+    while m != 0 {
+        if a & m != 0 {
+            product ^= b;
+        }
+        b = if b & 1 == 1 {
+            (b >> 1) ^ POLYNOMIAL
+        } else {
+            b >> 1
+        };
+        m >>= 1;
+    }
+    product
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -55,4 +110,27 @@ mod test {
         adler32.update(b"abcde");
         assert_eq!(adler32.value(), 0x05C801F0);
     }
+
+    #[test]
+    fn combine_works() {
+        let data = b"Hello World! Hello ZLIB!";
+        for i in 0..data.len() + 1 {
+            let (former, latter) = data.split_at(i);
+            let mut adler32 = [Adler32::new(), Adler32::new(), Adler32::new()];
+            let mut crc32 = [Crc32::new(), Crc32::new(), Crc32::new()];
+            for (j, buf) in [&data[..], former, latter].iter().enumerate() {
+                adler32[j].update(buf);
+                crc32[j].update(buf);
+            }
+            let len = latter.len() as u64;
+            assert_eq!(
+                combine_adler32(adler32[1].value(), adler32[2].value(), len),
+                adler32[0].value()
+            );
+            assert_eq!(
+                combine_crc32(crc32[1].value(), crc32[2].value(), len),
+                crc32[0].value()
+            );
+        }
+    }
 }
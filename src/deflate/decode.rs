@@ -1,19 +1,27 @@
+use alloc::vec::Vec;
 use byteorder::LittleEndian;
-use byteorder::ReadBytesExt;
-use std::cmp;
-use std::io;
-use std::io::Read;
-use std::ptr;
+use core::cmp;
+use core::ptr;
 
 use super::symbol;
 use bit;
+use io;
+use io::BufRead;
+use io::Read;
+use io::ReadBytesExt;
 use lz77;
 use util;
 
 /// DEFLATE decoder.
+///
+/// The inner stream is read in chunks, so no additional buffering (e.g., `std::io::BufReader`) is needed.
+/// The bytes following the DEFLATE stream can be taken by `unread_bytes`.
+///
+/// The decoded data can be accessed in place through `BufRead` (`fill_buf` and `consume`),
+/// without being copied to the buffer of the caller.
 #[derive(Debug)]
 pub struct Decoder<R> {
-    bit_reader: bit::BitReader<R>,
+    bit_reader: bit::BitReader<util::BufferedReader<R>>,
     buffer: Vec<u8>,
     offset: usize,
     eos: bool,
@@ -40,21 +48,52 @@ where
     /// ```
     pub fn new(inner: R) -> Self {
         Decoder {
-            bit_reader: bit::BitReader::new(inner),
+            bit_reader: bit::BitReader::new(util::BufferedReader::new(inner)),
             buffer: Vec::new(),
             offset: 0,
             eos: false,
         }
     }
 
+    /// Sets the preset dictionary.
+    ///
+    /// The dictionary is regarded as the data preceding the stream,
+    /// so that the back-references in the stream can refer to it.
+    /// It must be the same one as given to the encoder (see `Encoder::set_dictionary`).
+    ///
+    /// This should be called before reading any data, otherwise unread decoded data is discarded.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Read, Write};
+    /// use libflate::deflate::{Encoder, Decoder};
+    ///
+    /// let mut encoder = Encoder::new(Vec::new());
+    /// encoder.set_dictionary(b"Hello").unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    ///
+    /// let mut decoder = Decoder::new(&encoded_data[..]);
+    /// decoder.set_dictionary(b"Hello");
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    /// assert_eq!(buf, b"Hello World!");
+    /// ```
+    pub fn set_dictionary(&mut self, dictionary: &[u8]) {
+        let start = dictionary.len().saturating_sub(lz77::MAX_DISTANCE as usize);
+        self.buffer.clear();
+        self.buffer.extend_from_slice(&dictionary[start..]);
+        self.offset = self.buffer.len();
+    }
+
     /// Returns the immutable reference to the inner stream.
     pub fn as_inner_ref(&self) -> &R {
-        self.bit_reader.as_inner_ref()
+        self.bit_reader.as_inner_ref().get_ref()
     }
 
     /// Returns the mutable reference to the inner stream.
     pub fn as_inner_mut(&mut self) -> &mut R {
-        self.bit_reader.as_inner_mut()
+        self.bit_reader.as_inner_mut().get_mut()
     }
 
     /// Unwraps this `Decoder`, returning the underlying reader.
@@ -69,13 +108,111 @@ where
     /// assert_eq!(decoder.into_inner().into_inner(), &encoded_data);
     /// ```
     pub fn into_inner(self) -> R {
-        self.bit_reader.into_inner()
+        self.bit_reader.into_inner().into_inner()
+    }
+
+    /// Returns the number of the bytes of the DEFLATE stream which have been read so far.
+    ///
+    /// After the end of the stream is reached, this is the exact size of the stream.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    /// use libflate::deflate::Decoder;
+    ///
+    /// let mut encoded_data = vec![243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0];
+    /// encoded_data.extend_from_slice(b"trailing data");
+    ///
+    /// let mut decoder = Decoder::new(&encoded_data[..]);
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    ///
+    /// assert_eq!(buf, b"Hello World!");
+    /// assert_eq!(decoder.total_in(), 14);
+    /// ```
+    pub fn total_in(&self) -> u64 {
+        self.bit_reader.total_in()
+    }
+
+    /// Returns the bytes which have been read from the inner stream but not decoded yet.
+    ///
+    /// The inner stream is read in chunks, so after the end of the stream is reached,
+    /// these are the bytes following the DEFLATE stream (they are not returned to the inner stream by `into_inner`).
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    /// use libflate::deflate::Decoder;
+    ///
+    /// let mut encoded_data = vec![243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0];
+    /// encoded_data.extend_from_slice(b"trailing data");
+    ///
+    /// let mut input = &encoded_data[..];
+    /// let mut decoder = Decoder::new(&mut input);
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    ///
+    /// let mut trailing_data = decoder.unread_bytes();
+    /// trailing_data.extend_from_slice(input);
+    /// assert_eq!(trailing_data, b"trailing data");
+    /// ```
+    pub fn unread_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.bit_reader.unread_bytes();
+        let read = self.bit_reader.read_unconsumed_bytes();
+        bytes.extend_from_slice(&self.bit_reader.as_inner_ref().buffer()[read..]);
+        bytes
+    }
+
+    /// Reads the data following the DEFLATE stream (e.g., the checksum of ZLIB) by `f`.
+    pub(crate) fn read_trailer<F, T>(&mut self, f: F) -> io::Result<T>
+    where
+        F: FnOnce(&mut bit::BitReader<util::BufferedReader<R>>) -> io::Result<T>,
+    {
+        debug_assert!(self.eos);
+        let result = f(&mut self.bit_reader);
+        self.bit_reader.sync();
+        result
+    }
+
+    /// Prepares for decoding the DEFLATE stream following the current one.
+    pub(crate) fn reset(&mut self) {
+        self.buffer.clear();
+        self.offset = 0;
+        self.eos = false;
+    }
+
+    /// Returns the decoded data which have not been consumed yet (see `BufRead::fill_buf`).
+    pub(crate) fn buffered_data(&self) -> &[u8] {
+        &self.buffer[self.offset..]
     }
 
+    fn read_block(&mut self) -> io::Result<()> {
+        let bfinal = self.bit_reader.read_bit()?;
+        let btype = self.bit_reader.read_bits(2)?;
+        self.eos = bfinal;
+        self.truncate_old_buffer();
+        match btype {
+            0b00 => self.read_non_compressed_block()?,
+            0b01 => self.read_compressed_block(&symbol::FixedHuffmanCodec)?,
+            0b10 => self.read_compressed_block(&symbol::DynamicHuffmanCodec)?,
+            0b11 => {
+                return Err(invalid_data_error!(
+                    "btype 0x11 of DEFLATE is reserved(error) value"
+                ))
+            }
+            _ => unreachable!(),
+        }
+        if self.eos {
+            // The bytes following the stream should be left unconsumed (see `unread_bytes`)
+            self.bit_reader.align_to_byte();
+            self.bit_reader.sync();
+        }
+        Ok(())
+    }
     fn read_non_compressed_block(&mut self) -> io::Result<()> {
-        self.bit_reader.reset();
-        let len = self.bit_reader.as_inner_mut().read_u16::<LittleEndian>()?;
-        let nlen = self.bit_reader.as_inner_mut().read_u16::<LittleEndian>()?;
+        self.bit_reader.align_to_byte();
+        let len = self.bit_reader.read_u16::<LittleEndian>()?;
+        let nlen = self.bit_reader.read_u16::<LittleEndian>()?;
         if !len != nlen {
             Err(invalid_data_error!(
                 "LEN={} is not the one's complement of NLEN={}",
@@ -86,9 +223,7 @@ where
             let old_len = self.buffer.len();
             self.buffer.reserve(len as usize);
             unsafe { self.buffer.set_len(old_len + len as usize) };
-            self.bit_reader
-                .as_inner_mut()
-                .read_exact(&mut self.buffer[old_len..])?;
+            self.bit_reader.read_exact(&mut self.buffer[old_len..])?;
             Ok(())
         }
     }
@@ -101,10 +236,13 @@ where
             let s = symbol_decoder.decode_unchecked(&mut self.bit_reader);
             self.bit_reader.check_last_error()?;
             match s {
-                symbol::Symbol::Literal(b) => {
+                symbol::DecodedSymbol::Literal(b) => {
                     self.buffer.push(b);
                 }
-                symbol::Symbol::Share { length, distance } => {
+                symbol::DecodedSymbol::LiteralPair(b0, b1) => {
+                    self.buffer.extend_from_slice(&[b0, b1]);
+                }
+                symbol::DecodedSymbol::Share { length, distance } => {
                     if self.buffer.len() < distance as usize {
                         return Err(invalid_data_error!(
                             "Too long backword reference: buffer.len={}, distance={}",
@@ -126,7 +264,7 @@ where
                         );
                     }
                 }
-                symbol::Symbol::EndOfBlock => {
+                symbol::DecodedSymbol::EndOfBlock => {
                     break;
                 }
             }
@@ -151,45 +289,37 @@ where
     R: Read,
 {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        if self.offset < self.buffer.len() {
-            let copy_size = cmp::min(buf.len(), self.buffer.len() - self.offset);
-            buf[..copy_size].copy_from_slice(&self.buffer[self.offset..][..copy_size]);
-            self.offset += copy_size;
-            Ok(copy_size)
-        } else if self.eos {
-            Ok(0)
-        } else {
-            let bfinal = self.bit_reader.read_bit()?;
-            let btype = self.bit_reader.read_bits(2)?;
-            self.eos = bfinal;
-            self.truncate_old_buffer();
-            match btype {
-                0b00 => {
-                    self.read_non_compressed_block()?;
-                    self.read(buf)
-                }
-                0b01 => {
-                    self.read_compressed_block(&symbol::FixedHuffmanCodec)?;
-                    self.read(buf)
-                }
-                0b10 => {
-                    self.read_compressed_block(&symbol::DynamicHuffmanCodec)?;
-                    self.read(buf)
-                }
-                0b11 => Err(invalid_data_error!(
-                    "btype 0x11 of DEFLATE is reserved(error) value"
-                )),
-                _ => unreachable!(),
-            }
+        let copy_size = {
+            let data = self.fill_buf()?;
+            let copy_size = cmp::min(buf.len(), data.len());
+            buf[..copy_size].copy_from_slice(&data[..copy_size]);
+            copy_size
+        };
+        self.consume(copy_size);
+        Ok(copy_size)
+    }
+}
+impl<R> BufRead for Decoder<R>
+where
+    R: Read,
+{
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.offset == self.buffer.len() && !self.eos {
+            self.read_block()?;
         }
+        Ok(&self.buffer[self.offset..])
+    }
+    fn consume(&mut self, amt: usize) {
+        self.offset = cmp::min(self.offset + amt, self.buffer.len());
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use alloc::string::{String, ToString};
     use deflate::symbol::{DynamicHuffmanCodec, HuffmanCodec};
-    use std::io;
+    use io;
 
     #[test]
     fn test_issues_3() {
@@ -208,6 +338,21 @@ mod test {
         DynamicHuffmanCodec.load(&mut bit_reader).unwrap();
     }
 
+    #[test]
+    #[cfg(target_has_atomic = "ptr")]
+    fn fixed_huffman_codes_are_shared() {
+        use alloc::borrow::Cow;
+        use deflate::symbol::FixedHuffmanCodec;
+
+        let mut bit_reader = ::bit::BitReader::new(&[][..]);
+        let decoder0 = FixedHuffmanCodec.load(&mut bit_reader).unwrap();
+        let decoder1 = FixedHuffmanCodec.load(&mut bit_reader).unwrap();
+        match (decoder0, decoder1) {
+            (Cow::Borrowed(d0), Cow::Borrowed(d1)) => assert!(::core::ptr::eq(d0, d1)),
+            _ => panic!("The fixed Huffman decoder must not be rebuilt"),
+        }
+    }
+
     #[test]
     fn it_works() {
         let input = [
@@ -221,11 +366,89 @@ mod test {
         ];
         let mut decoder = Decoder::new(&input[..]);
 
-        let result = io::copy(&mut decoder, &mut io::sink());
+        let result = decoder.read_to_end(&mut Vec::new());
         assert!(result.is_err());
 
         let error = result.err().unwrap();
         assert_eq!(error.kind(), io::ErrorKind::InvalidData);
         assert!(error.to_string().starts_with("Too long backword reference"));
     }
+
+    #[test]
+    fn trailing_bytes_are_returned_as_unread_bytes() {
+        let plain = (0..10_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let mut encoder = ::deflate::Encoder::new(Vec::new());
+        io::Write::write_all(&mut encoder, &plain).unwrap();
+        let mut encoded = encoder.finish().into_result().unwrap();
+        encoded.extend_from_slice(b"trailer");
+
+        let mut input = &encoded[..];
+        let mut decoded = Vec::new();
+        let mut decoder = Decoder::new(&mut input);
+        decoder.read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, plain);
+
+        let mut rest = decoder.unread_bytes();
+        rest.extend_from_slice(input);
+        assert_eq!(rest, b"trailer");
+    }
+
+    #[test]
+    fn non_buffered_inner_stream_works() {
+        // A reader which implements only `Read` (not `BufRead`)
+        struct OneByteReader<'a>(&'a [u8]);
+        impl<'a> Read for OneByteReader<'a> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let size = cmp::min(1, buf.len());
+                self.0.read(&mut buf[..size])
+            }
+        }
+
+        let plain = (0..10_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let mut encoder = ::deflate::Encoder::new(Vec::new());
+        io::Write::write_all(&mut encoder, &plain).unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+
+        let mut decoded = Vec::new();
+        let mut decoder = Decoder::new(OneByteReader(&encoded));
+        decoder.read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, plain);
+        assert_eq!(decoder.total_in(), encoded.len() as u64);
+    }
+
+    #[test]
+    fn buf_read_works() {
+        let plain = (0..10_000)
+            .map(|i| format!("line {}\n", i % 1000))
+            .collect::<String>();
+        let mut encoder = ::deflate::Encoder::new(Vec::new());
+        io::Write::write_all(&mut encoder, plain.as_bytes()).unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+
+        let mut decoder = Decoder::new(&encoded[..]);
+        assert_eq!(&decoder.fill_buf().unwrap()[..5], b"line ");
+        decoder.consume(5);
+        assert_eq!(&decoder.fill_buf().unwrap()[..2], b"0\n");
+
+        let mut decoder = Decoder::new(&encoded[..]);
+        let mut decoded = Vec::new();
+        loop {
+            let size = {
+                let data = decoder.fill_buf().unwrap();
+                decoded.extend_from_slice(data);
+                data.len()
+            };
+            if size == 0 {
+                break;
+            }
+            decoder.consume(size);
+        }
+        assert_eq!(decoded, plain.as_bytes());
+    }
 }
@@ -1,13 +1,15 @@
+use alloc::vec::Vec;
 use byteorder::LittleEndian;
-use byteorder::WriteBytesExt;
-use std::cmp;
-use std::io;
+use core::cmp;
 
+use super::split;
 use super::symbol;
 use super::BlockType;
 use bit;
 use finish::{Complete, Finish};
-use lz77;
+use io;
+use io::WriteBytesExt;
+use lz77::{self, Lz77Encode};
 
 /// The default size of a DEFLATE block.
 pub const DEFAULT_BLOCK_SIZE: usize = 1024 * 1024;
@@ -43,6 +45,38 @@ impl EncodeOptions<lz77::DefaultLz77Encoder> {
             lz77: Some(lz77::DefaultLz77Encoder::new()),
         }
     }
+
+    /// Specifies the compression level (`0..=9`).
+    ///
+    /// `0` is equivalent to `no_compression()`.
+    /// The other levels select the parameters of `DefaultLz77Encoder` in the same manner as zlib.
+    /// Levels above `9` are treated as `9`.
+    ///
+    /// The default level is `6`.
+    ///
+    /// # Example
+    /// ```
+    /// use libflate::deflate::{Encoder, EncodeOptions};
+    ///
+    /// let options = EncodeOptions::new().level(9);
+    /// let encoder = Encoder::with_options(Vec::new(), options);
+    /// ```
+    pub fn level(mut self, level: u8) -> Self {
+        if level == 0 {
+            self.lz77 = None;
+        } else {
+            let window_size = self
+                .lz77
+                .as_ref()
+                .map_or(lz77::MAX_WINDOW_SIZE, |e| e.window_size());
+            let lz77 = lz77::DefaultLz77EncoderBuilder::new()
+                .window_size(window_size)
+                .level(level)
+                .finish();
+            self.lz77 = Some(lz77);
+        }
+        self
+    }
 }
 impl<E> EncodeOptions<E>
 where
@@ -99,6 +133,9 @@ where
 
     /// Specifies to compress with fixed huffman codes.
     ///
+    /// Dynamic huffman codes will never be used,
+    /// but each block may still be stored without compression if that is smaller.
+    ///
     /// # Example
     /// ```
     /// use libflate::deflate::{Encoder, EncodeOptions};
@@ -111,20 +148,25 @@ where
         self
     }
 
-    fn get_block_type(&self) -> BlockType {
+    fn get_block_size(&self) -> usize {
         if self.lz77.is_none() {
-            BlockType::Raw
-        } else if self.dynamic_huffman {
-            BlockType::Dynamic
+            cmp::min(self.block_size, MAX_NON_COMPRESSED_BLOCK_SIZE)
         } else {
-            BlockType::Fixed
+            self.block_size
         }
     }
-    fn get_block_size(&self) -> usize {
+
+    /// Returns the upper bound of the size of the stream which encodes `input_len` bytes at once.
+    pub(crate) fn max_encoded_len(&self, input_len: usize) -> usize {
+        let raw_blocks = input_len / MAX_NON_COMPRESSED_BLOCK_SIZE + 2;
         if self.lz77.is_none() {
-            cmp::min(self.block_size, MAX_NON_COMPRESSED_BLOCK_SIZE)
+            // A non-compressed block costs a byte for the header (and padding) and four for LEN/NLEN
+            input_len + raw_blocks * 5
         } else {
-            self.block_size
+            // A block is never larger than the non-compressed blocks of the same data,
+            // and the data is split into a block per `split::CHUNK_SIZE` symbols at most
+            let blocks = input_len / split::CHUNK_SIZE + 2 + raw_blocks;
+            input_len + blocks * 6 + 1
         }
     }
 }
@@ -152,8 +194,7 @@ where
     /// encoder.write_all(b"Hello World!").unwrap();
     ///
     /// assert_eq!(encoder.finish().into_result().unwrap(),
-    ///            [5, 128, 65, 9, 0, 0, 8, 3, 171, 104, 27, 27, 88, 64, 127,
-    ///             7, 131, 245, 127, 140, 121, 80, 173, 204, 117, 0]);
+    ///            [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0]);
     /// ```
     pub fn new(inner: W) -> Self {
         Self::with_options(inner, EncodeOptions::default())
@@ -199,8 +240,7 @@ where
     /// encoder.write_all(b"Hello World!").unwrap();
     ///
     /// assert_eq!(encoder.finish().into_result().unwrap(),
-    ///            [5, 128, 65, 9, 0, 0, 8, 3, 171, 104, 27, 27, 88, 64, 127,
-    ///             7, 131, 245, 127, 140, 121, 80, 173, 204, 117, 0]);
+    ///            [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0]);
     /// ```
     pub fn finish(mut self) -> Finish<W, io::Error> {
         match self.block.finish(&mut self.writer) {
@@ -209,6 +249,91 @@ where
         }
     }
 
+    /// Performs a sync flush (equivalent to `Z_SYNC_FLUSH` of zlib).
+    ///
+    /// The current block is terminated and an empty non-compressed block is emitted to
+    /// align the output to a byte boundary, so that a decoder can restore all the data written so far.
+    ///
+    /// This is also invoked by `io::Write::flush`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Read, Write};
+    /// use libflate::deflate::{Encoder, Decoder};
+    ///
+    /// let mut encoder = Encoder::new(Vec::new());
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// encoder.sync_flush().unwrap();
+    /// assert!(encoder.as_inner_ref().ends_with(&[0, 0, 255, 255]));
+    ///
+    /// let mut decoder = Decoder::new(&encoder.as_inner_ref()[..]);
+    /// let mut buf = [0; 12];
+    /// decoder.read_exact(&mut buf).unwrap();
+    /// assert_eq!(&buf, b"Hello World!");
+    /// ```
+    pub fn sync_flush(&mut self) -> io::Result<()> {
+        self.block.flush(&mut self.writer, false)
+    }
+
+    /// Performs a full flush (equivalent to `Z_FULL_FLUSH` of zlib).
+    ///
+    /// In addition to the processing of `sync_flush`, this discards the LZ77 history.
+    /// Therefore the data following this point can be decoded without the preceding data.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Read, Write};
+    /// use libflate::deflate::{Encoder, Decoder};
+    ///
+    /// let mut encoder = Encoder::new(Vec::new());
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// encoder.full_flush().unwrap();
+    /// let offset = encoder.as_inner_ref().len();
+    ///
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    ///
+    /// let mut decoder = Decoder::new(&encoded_data[offset..]);
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    /// assert_eq!(buf, b"Hello World!");
+    /// ```
+    pub fn full_flush(&mut self) -> io::Result<()> {
+        self.block.flush(&mut self.writer, true)
+    }
+
+    /// Sets the preset dictionary.
+    ///
+    /// The dictionary is regarded as the data preceding the stream,
+    /// so that the succeeding data can be compressed by referring to it.
+    /// The decoder needs the same dictionary (see `Decoder::set_dictionary`).
+    ///
+    /// This must be called before writing any data, otherwise an `InvalidInput` error is returned
+    /// (the decoder can not switch the dictionary in the middle of a stream).
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Read, Write};
+    /// use libflate::deflate::{Encoder, Decoder};
+    ///
+    /// let dictionary = b"Hello World!";
+    ///
+    /// let mut encoder = Encoder::new(Vec::new());
+    /// encoder.set_dictionary(dictionary).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    /// assert_eq!(encoded_data.len(), 4);
+    ///
+    /// let mut decoder = Decoder::new(&encoded_data[..]);
+    /// decoder.set_dictionary(dictionary);
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    /// assert_eq!(buf, b"Hello World!");
+    /// ```
+    pub fn set_dictionary(&mut self, dictionary: &[u8]) -> io::Result<()> {
+        self.block.set_dictionary(dictionary)
+    }
+
     /// Returns the immutable reference to the inner stream.
     pub fn as_inner_ref(&self) -> &W {
         self.writer.as_inner_ref()
@@ -234,7 +359,7 @@ where
         Ok(buf.len())
     }
     fn flush(&mut self) -> io::Result<()> {
-        self.writer.as_inner_mut().flush()
+        self.sync_flush()
     }
 }
 impl<W, E> Complete for Encoder<W, E>
@@ -249,9 +374,13 @@ where
 
 #[derive(Debug)]
 struct Block<E> {
-    block_type: BlockType,
     block_size: usize,
     block_buf: BlockBuf<E>,
+    written: bool,
+
+    // Whether any data has been written since the last flush
+    // (it may have been emitted as blocks already, but not aligned to a byte boundary yet)
+    unflushed: bool,
 }
 impl<E> Block<E>
 where
@@ -259,30 +388,61 @@ where
 {
     fn new(options: EncodeOptions<E>) -> Self {
         Block {
-            block_type: options.get_block_type(),
             block_size: options.get_block_size(),
             block_buf: BlockBuf::new(options.lz77, options.dynamic_huffman),
+            written: false,
+            unflushed: false,
         }
     }
     fn write<W>(&mut self, writer: &mut bit::BitWriter<W>, buf: &[u8]) -> io::Result<()>
     where
         W: io::Write,
     {
+        self.written |= !buf.is_empty();
+        self.unflushed |= !buf.is_empty();
         self.block_buf.append(buf);
         while self.block_buf.len() >= self.block_size {
+            self.block_buf.flush(writer, false)?;
+        }
+        Ok(())
+    }
+    fn flush<W>(&mut self, writer: &mut bit::BitWriter<W>, full: bool) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        if self.unflushed {
+            if self.block_buf.len() > 0 {
+                self.block_buf.flush(writer, false)?;
+            }
+
+            // Empty non-compressed block for byte alignment
             writer.write_bit(false)?;
-            writer.write_bits(2, self.block_type as u16)?;
-            self.block_buf.flush(writer)?;
+            writer.write_bits(2, BlockType::Raw as u16)?;
+            writer.flush()?;
+            writer.as_inner_mut().write_u16::<LittleEndian>(0)?;
+            writer.as_inner_mut().write_u16::<LittleEndian>(!0)?;
+            self.unflushed = false;
+        }
+        if full {
+            self.block_buf.reset();
         }
+        writer.as_inner_mut().flush()
+    }
+    fn set_dictionary(&mut self, dictionary: &[u8]) -> io::Result<()> {
+        if self.written {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "A dictionary can be set only before writing any data",
+            ));
+        }
+        self.block_buf.set_dictionary(dictionary);
         Ok(())
     }
     fn finish<W>(mut self, writer: &mut bit::BitWriter<W>) -> io::Result<()>
     where
         W: io::Write,
     {
-        writer.write_bit(true)?;
-        writer.write_bits(2, self.block_type as u16)?;
-        self.block_buf.flush(writer)?;
+        self.block_buf.flush(writer, true)?;
         writer.flush()?;
         Ok(())
     }
@@ -291,8 +451,7 @@ where
 #[derive(Debug)]
 enum BlockBuf<E> {
     Raw(RawBuf),
-    Fixed(CompressBuf<symbol::FixedHuffmanCodec, E>),
-    Dynamic(CompressBuf<symbol::DynamicHuffmanCodec, E>),
+    Compress(CompressBuf<E>),
 }
 impl<E> BlockBuf<E>
 where
@@ -300,11 +459,7 @@ where
 {
     fn new(lz77: Option<E>, dynamic: bool) -> Self {
         if let Some(lz77) = lz77 {
-            if dynamic {
-                BlockBuf::Dynamic(CompressBuf::new(symbol::DynamicHuffmanCodec, lz77))
-            } else {
-                BlockBuf::Fixed(CompressBuf::new(symbol::FixedHuffmanCodec, lz77))
-            }
+            BlockBuf::Compress(CompressBuf::new(lz77, dynamic))
         } else {
             BlockBuf::Raw(RawBuf::new())
         }
@@ -312,25 +467,34 @@ where
     fn append(&mut self, buf: &[u8]) {
         match *self {
             BlockBuf::Raw(ref mut b) => b.append(buf),
-            BlockBuf::Fixed(ref mut b) => b.append(buf),
-            BlockBuf::Dynamic(ref mut b) => b.append(buf),
+            BlockBuf::Compress(ref mut b) => b.append(buf),
         }
     }
     fn len(&self) -> usize {
         match *self {
             BlockBuf::Raw(ref b) => b.len(),
-            BlockBuf::Fixed(ref b) => b.len(),
-            BlockBuf::Dynamic(ref b) => b.len(),
+            BlockBuf::Compress(ref b) => b.len(),
+        }
+    }
+    fn reset(&mut self) {
+        match *self {
+            BlockBuf::Raw(_) => {}
+            BlockBuf::Compress(ref mut b) => b.reset(),
         }
     }
-    fn flush<W>(&mut self, writer: &mut bit::BitWriter<W>) -> io::Result<()>
+    fn set_dictionary(&mut self, dictionary: &[u8]) {
+        match *self {
+            BlockBuf::Raw(_) => {}
+            BlockBuf::Compress(ref mut b) => b.set_dictionary(dictionary),
+        }
+    }
+    fn flush<W>(&mut self, writer: &mut bit::BitWriter<W>, is_final: bool) -> io::Result<()>
     where
         W: io::Write,
     {
         match *self {
-            BlockBuf::Raw(ref mut b) => b.flush(writer),
-            BlockBuf::Fixed(ref mut b) => b.flush(writer),
-            BlockBuf::Dynamic(ref mut b) => b.flush(writer),
+            BlockBuf::Raw(ref mut b) => b.flush(writer, is_final),
+            BlockBuf::Compress(ref mut b) => b.flush(writer, is_final),
         }
     }
 }
@@ -349,67 +513,288 @@ impl RawBuf {
     fn len(&self) -> usize {
         self.buf.len()
     }
-    fn flush<W>(&mut self, writer: &mut bit::BitWriter<W>) -> io::Result<()>
+    fn flush<W>(&mut self, writer: &mut bit::BitWriter<W>, is_final: bool) -> io::Result<()>
     where
         W: io::Write,
     {
         let size = cmp::min(self.buf.len(), MAX_NON_COMPRESSED_BLOCK_SIZE);
-        writer.flush()?;
-        writer
-            .as_inner_mut()
-            .write_u16::<LittleEndian>(size as u16)?;
-        writer
-            .as_inner_mut()
-            .write_u16::<LittleEndian>(!size as u16)?;
-        writer.as_inner_mut().write_all(&self.buf[..size])?;
+        write_non_compressed_blocks(writer, &self.buf[..size], is_final)?;
         self.buf.drain(0..size);
         Ok(())
     }
 }
 
 #[derive(Debug)]
-struct CompressBuf<H, E> {
-    huffman: H,
+struct CompressBuf<E> {
     lz77: E,
+    dynamic: bool,
+    adaptive_split: bool,
     buf: Vec<symbol::Symbol>,
-    original_size: usize,
+
+    // The size of the original data of the symbols in `buf`
+    original_len: usize,
+
+    // The data preceding the symbols in `buf`, to restore them for non-compressed blocks
+    history: History,
 }
-impl<H, E> CompressBuf<H, E>
+impl<E> CompressBuf<E>
 where
-    H: symbol::HuffmanCodec,
     E: lz77::Lz77Encode,
 {
-    fn new(huffman: H, lz77: E) -> Self {
+    fn new(lz77: E, dynamic: bool) -> Self {
+        // Splitting blocks is not worth its cost when speed is preferred
+        let adaptive_split = dynamic && lz77.compression_level() != lz77::CompressionLevel::Fast;
         CompressBuf {
-            huffman,
             lz77,
+            dynamic,
+            adaptive_split,
             buf: Vec::new(),
-            original_size: 0,
+            original_len: 0,
+            history: History::new(),
         }
     }
     fn append(&mut self, buf: &[u8]) {
-        self.original_size += buf.len();
+        self.original_len += buf.len();
         self.lz77.encode(buf, &mut self.buf);
     }
     fn len(&self) -> usize {
-        self.original_size
+        self.original_len
+    }
+    fn reset(&mut self) {
+        self.lz77.reset();
+        self.history.clear();
     }
-    fn flush<W>(&mut self, writer: &mut bit::BitWriter<W>) -> io::Result<()>
+    fn set_dictionary(&mut self, dictionary: &[u8]) {
+        self.lz77.set_dictionary(dictionary);
+        self.history.set_dictionary(dictionary);
+    }
+    fn flush<W>(&mut self, writer: &mut bit::BitWriter<W>, is_final: bool) -> io::Result<()>
     where
         W: io::Write,
     {
         self.lz77.flush(&mut self.buf);
-        self.buf.push(symbol::Symbol::EndOfBlock);
-        let symbol_encoder = self.huffman.build(&self.buf)?;
-        self.huffman.save(writer, &symbol_encoder)?;
-        for s in self.buf.drain(..) {
-            symbol_encoder.encode(writer, &s)?;
+        let ends = if self.adaptive_split {
+            split::split(&self.buf)?
+        } else {
+            vec![self.buf.len()]
+        };
+        let mut start = 0;
+        for (i, &end) in ends.iter().enumerate() {
+            let symbols = &self.buf[start..end];
+            let is_final = is_final && i == ends.len() - 1;
+            write_block(writer, &mut self.history, self.dynamic, symbols, is_final)?;
+            start = end;
         }
-        self.original_size = 0;
+        self.buf.clear();
+        self.original_len = 0;
         Ok(())
     }
 }
 
+/// Writes `symbols` as the cheapest of a non-compressed, a fixed or a dynamic Huffman block.
+fn write_block<W>(
+    writer: &mut bit::BitWriter<W>,
+    history: &mut History,
+    dynamic: bool,
+    symbols: &[symbol::Symbol],
+    is_final: bool,
+) -> io::Result<()>
+where
+    W: io::Write,
+{
+    let mut histogram = symbol::Histogram::from_symbols(symbols);
+    histogram.add(&symbol::Symbol::EndOfBlock);
+
+    let size = symbols.iter().map(original_len).sum::<usize>();
+    let raw_bits = non_compressed_block_bits(writer.bit_offset(), size);
+    let fixed_bits = symbol::FixedHuffmanCodec.block_bits(&histogram);
+    let dynamic_bits = if dynamic {
+        symbol::DynamicHuffmanCodec.block_bits(&histogram)?
+    } else {
+        usize::MAX
+    };
+    if raw_bits <= cmp::min(fixed_bits, dynamic_bits) {
+        let mut written = 0;
+        history.restore(symbols, |data| {
+            written += data.len();
+            write_non_compressed_blocks(writer, data, is_final && written == size)
+        })
+    } else {
+        history.restore(symbols, |_| Ok(()))?;
+        if dynamic_bits < fixed_bits {
+            let codec = symbol::DynamicHuffmanCodec;
+            write_compressed_block(
+                writer,
+                &codec,
+                BlockType::Dynamic,
+                &histogram,
+                symbols,
+                is_final,
+            )
+        } else {
+            let codec = symbol::FixedHuffmanCodec;
+            write_compressed_block(
+                writer,
+                &codec,
+                BlockType::Fixed,
+                &histogram,
+                symbols,
+                is_final,
+            )
+        }
+    }
+}
+
+/// The data preceding the symbols being encoded.
+///
+/// The original data of the symbols are restored by resolving their pointers against this,
+/// so only the last `MAX_DISTANCE` bytes are kept instead of a copy of the whole block.
+#[derive(Debug)]
+struct History {
+    buf: Vec<u8>,
+}
+impl History {
+    fn new() -> Self {
+        History { buf: Vec::new() }
+    }
+    fn clear(&mut self) {
+        self.buf.clear();
+    }
+    fn set_dictionary(&mut self, dictionary: &[u8]) {
+        let start = dictionary.len().saturating_sub(lz77::MAX_DISTANCE as usize);
+        self.buf.clear();
+        self.buf.extend_from_slice(&dictionary[start..]);
+    }
+
+    /// Restores the original data of `symbols`, and passes it to `f` in pieces.
+    ///
+    /// Each piece fits in a non-compressed block, and `f` is called at least once (even if the data is empty).
+    fn restore<F>(&mut self, symbols: &[symbol::Symbol], mut f: F) -> io::Result<()>
+    where
+        F: FnMut(&[u8]) -> io::Result<()>,
+    {
+        let mut start = self.buf.len();
+        let mut is_first = true;
+        for s in symbols {
+            match *s {
+                symbol::Symbol::EndOfBlock => {}
+                symbol::Symbol::Literal(b) => self.buf.push(b),
+                symbol::Symbol::Share { length, distance } => {
+                    let (length, distance) = (length as usize, distance as usize);
+                    if distance == 0 || distance > self.buf.len() {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "The LZ77 encoder produced a pointer out of the history",
+                        ));
+                    }
+                    let from = self.buf.len() - distance;
+                    if length <= distance {
+                        self.buf.extend_from_within(from..from + length);
+                    } else {
+                        for i in from..from + length {
+                            let b = self.buf[i];
+                            self.buf.push(b);
+                        }
+                    }
+                }
+            }
+            while self.buf.len() - start >= MAX_NON_COMPRESSED_BLOCK_SIZE {
+                f(&self.buf[start..][..MAX_NON_COMPRESSED_BLOCK_SIZE])?;
+                start += MAX_NON_COMPRESSED_BLOCK_SIZE;
+                is_first = false;
+                start -= self.truncate(start);
+            }
+        }
+        if is_first || start < self.buf.len() {
+            f(&self.buf[start..])?;
+        }
+        let end = self.buf.len();
+        self.truncate(end);
+        Ok(())
+    }
+
+    /// Discards the bytes which are neither in the last `MAX_DISTANCE` bytes before `start` nor after it,
+    /// and returns the number of the discarded bytes.
+    fn truncate(&mut self, start: usize) -> usize {
+        let max_distance = lz77::MAX_DISTANCE as usize;
+        if start < max_distance * 2 {
+            return 0;
+        }
+        let size = start - max_distance;
+        self.buf.drain(..size);
+        size
+    }
+}
+
+fn original_len(symbol: &symbol::Symbol) -> usize {
+    match *symbol {
+        symbol::Symbol::EndOfBlock => 0,
+        symbol::Symbol::Literal(_) => 1,
+        symbol::Symbol::Share { length, .. } => length as usize,
+    }
+}
+
+/// Returns the size in bits of the non-compressed blocks which contain `size` bytes.
+///
+/// The size excludes the three bits of the first block header.
+fn non_compressed_block_bits(bit_offset: u8, size: usize) -> usize {
+    let blocks = cmp::max(
+        1,
+        (size + MAX_NON_COMPRESSED_BLOCK_SIZE - 1) / MAX_NON_COMPRESSED_BLOCK_SIZE,
+    );
+    let padding = (8 - (bit_offset as usize + 3) % 8) % 8;
+    padding + (blocks - 1) * 8 + blocks * 32 + size * 8
+}
+
+fn write_non_compressed_blocks<W>(
+    writer: &mut bit::BitWriter<W>,
+    mut buf: &[u8],
+    is_final: bool,
+) -> io::Result<()>
+where
+    W: io::Write,
+{
+    loop {
+        let size = cmp::min(buf.len(), MAX_NON_COMPRESSED_BLOCK_SIZE);
+        writer.write_bit(is_final && size == buf.len())?;
+        writer.write_bits(2, BlockType::Raw as u16)?;
+        writer.flush()?;
+        writer
+            .as_inner_mut()
+            .write_u16::<LittleEndian>(size as u16)?;
+        writer
+            .as_inner_mut()
+            .write_u16::<LittleEndian>(!size as u16)?;
+        writer.as_inner_mut().write_all(&buf[..size])?;
+        buf = &buf[size..];
+        if buf.is_empty() {
+            return Ok(());
+        }
+    }
+}
+
+fn write_compressed_block<W, H>(
+    writer: &mut bit::BitWriter<W>,
+    huffman: &H,
+    block_type: BlockType,
+    histogram: &symbol::Histogram,
+    symbols: &[symbol::Symbol],
+    is_final: bool,
+) -> io::Result<()>
+where
+    W: io::Write,
+    H: symbol::HuffmanCodec,
+{
+    writer.write_bit(is_final)?;
+    writer.write_bits(2, block_type as u16)?;
+    let symbol_encoder = huffman.build(histogram)?;
+    huffman.save(writer, &symbol_encoder)?;
+    for s in symbols {
+        symbol_encoder.encode(writer, s)?;
+    }
+    symbol_encoder.encode(writer, &symbol::Symbol::EndOfBlock)
+}
+
 impl lz77::Sink for Vec<symbol::Symbol> {
     fn consume(&mut self, code: lz77::Code) {
         let symbol = match code {
@@ -0,0 +1,219 @@
+//! The DEFLATE encoder and decoder which work on the asynchronous streams of `futures-io`.
+//!
+//! This module is available only if the `futures-io` feature is enabled.
+//!
+//! # Examples
+//! ```edition2018
+//! # extern crate futures;
+//! use futures::executor::block_on;
+//! use futures::io::{AsyncReadExt, AsyncWriteExt};
+//! use libflate::deflate::futures::{Decoder, Encoder};
+//!
+//! block_on(async {
+//!     // Encoding
+//!     let mut encoder = Encoder::new(Vec::new());
+//!     encoder.write_all(b"Hello World!").await.unwrap();
+//!     encoder.close().await.unwrap();
+//!     let encoded_data = encoder.into_inner();
+//!
+//!     // Decoding
+//!     let mut decoder = Decoder::new(&encoded_data[..]);
+//!     let mut decoded_data = Vec::new();
+//!     decoder.read_to_end(&mut decoded_data).await.unwrap();
+//!
+//!     assert_eq!(decoded_data, b"Hello World!");
+//! });
+//! ```
+use futures_io::{AsyncRead, AsyncWrite};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use super::EncodeOptions;
+use async_io::{self, AsyncIo, FuturesIo};
+use io::{self, Read, Write};
+use lz77;
+use non_blocking;
+
+/// DEFLATE decoder which implements `futures_io::AsyncRead`.
+///
+/// This is driven by the resumable state machine of `non_blocking::deflate::Decoder`,
+/// so no thread is blocked while the inner stream is pending.
+///
+/// The inner stream is read in chunks, so no additional buffering (e.g., `futures::io::BufReader`) is needed.
+/// The bytes following the DEFLATE stream can be taken by `unread_bytes`.
+#[derive(Debug)]
+pub struct Decoder<R> {
+    inner: non_blocking::deflate::Decoder<AsyncIo<R, FuturesIo>>,
+}
+impl<R> Decoder<R>
+where
+    R: AsyncRead + Unpin,
+{
+    /// Makes a new decoder instance.
+    ///
+    /// `inner` is to be decoded DEFLATE stream.
+    pub fn new(inner: R) -> Self {
+        Decoder {
+            inner: non_blocking::deflate::Decoder::new(AsyncIo::new(inner)),
+        }
+    }
+
+    /// Sets the preset dictionary.
+    ///
+    /// See `deflate::Decoder::set_dictionary` for more details.
+    pub fn set_dictionary(&mut self, dictionary: &[u8]) {
+        self.inner.set_dictionary(dictionary);
+    }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &R {
+        self.inner.as_inner_ref().as_inner_ref()
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut R {
+        self.inner.as_inner_mut().as_inner_mut()
+    }
+
+    /// Unwraps this `Decoder`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner().into_inner()
+    }
+
+    /// Returns the bytes which have been read from the inner stream but not decoded yet.
+    ///
+    /// See `non_blocking::deflate::Decoder::unread_bytes` for more details.
+    pub fn unread_bytes(&self) -> Vec<u8> {
+        self.inner.unread_bytes()
+    }
+}
+impl<R> AsyncRead for Decoder<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        this.inner.as_inner_mut().register(cx);
+        async_io::into_poll(this.inner.read(buf))
+    }
+}
+
+/// DEFLATE encoder which implements `futures_io::AsyncWrite`.
+///
+/// This is driven by `non_blocking::deflate::Encoder`.
+/// `poll_close` must be called to complete the stream (it also closes the inner stream).
+#[derive(Debug)]
+pub struct Encoder<W, E = lz77::DefaultLz77Encoder> {
+    inner: non_blocking::deflate::Encoder<AsyncIo<W, FuturesIo>, E>,
+    finished: bool,
+}
+impl<W> Encoder<W, lz77::DefaultLz77Encoder>
+where
+    W: AsyncWrite + Unpin,
+{
+    /// Makes a new encoder instance.
+    ///
+    /// Encoded DEFLATE stream is written to `inner`.
+    pub fn new(inner: W) -> Self {
+        Self::with_options(inner, EncodeOptions::default())
+    }
+}
+impl<W, E> Encoder<W, E>
+where
+    W: AsyncWrite + Unpin,
+    E: lz77::Lz77Encode,
+{
+    /// Makes a new encoder instance with specified options.
+    ///
+    /// Encoded DEFLATE stream is written to `inner`.
+    pub fn with_options(inner: W, options: EncodeOptions<E>) -> Self {
+        Encoder {
+            inner: non_blocking::deflate::Encoder::with_options(AsyncIo::new(inner), options),
+            finished: false,
+        }
+    }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &W {
+        self.inner.as_inner_ref().as_inner_ref()
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut W {
+        self.inner.as_inner_mut().as_inner_mut()
+    }
+
+    /// Unwraps the `Encoder`, returning the inner stream.
+    ///
+    /// The data which is not written to the inner stream yet is discarded.
+    pub fn into_inner(self) -> W {
+        self.inner.into_inner().into_inner()
+    }
+}
+impl<W, E> AsyncWrite for Encoder<W, E>
+where
+    W: AsyncWrite + Unpin,
+    E: lz77::Lz77Encode + Unpin,
+{
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        this.inner.as_inner_mut().register(cx);
+        async_io::into_poll(this.inner.write(buf))
+    }
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.inner.as_inner_mut().register(cx);
+        async_io::into_poll(this.inner.flush())
+    }
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.inner.as_inner_mut().register(cx);
+        if !this.finished {
+            match async_io::into_poll(this.inner.finish()) {
+                Poll::Ready(Ok(())) => this.finished = true,
+                poll => return poll,
+            }
+        }
+        async_io::into_poll(this.inner.as_inner_mut().close())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::executor::block_on;
+    use futures::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use async_io::Intermittent;
+
+    #[test]
+    fn async_encode_decode_works() {
+        let plain = (0..10_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+
+        let mut encoder = Encoder::new(Intermittent::new(Vec::new()));
+        for chunk in plain.chunks(1000) {
+            block_on(encoder.write_all(chunk)).unwrap();
+            block_on(encoder.flush()).unwrap();
+        }
+        block_on(encoder.close()).unwrap();
+        let encoded = encoder.into_inner().into_inner();
+
+        let mut input = encoded.clone();
+        input.extend_from_slice(b"trailing data");
+        let mut decoder = Decoder::new(Intermittent::new(&input[..]));
+        let mut decoded = Vec::new();
+        block_on(decoder.read_to_end(&mut decoded)).unwrap();
+        assert_eq!(decoded, plain);
+        assert_eq!(decoder.unread_bytes(), b"trailing data");
+
+        let mut decoder = Decoder::new(&encoded[..encoded.len() - 1]);
+        assert!(block_on(decoder.read_to_end(&mut Vec::new())).is_err());
+    }
+}
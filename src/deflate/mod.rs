@@ -23,10 +23,24 @@ pub use self::decode::Decoder;
 pub use self::encode::EncodeOptions;
 pub use self::encode::Encoder;
 pub use self::encode::DEFAULT_BLOCK_SIZE;
+#[cfg(feature = "std")]
+pub use self::parallel::{ParallelEncoder, ParallelOptions, DEFAULT_CHUNK_SIZE};
+pub use self::slice::{compress_into, decompress_into, max_compressed_len, SliceError};
 
 mod decode;
 mod encode;
+#[cfg(feature = "futures-io")]
+pub mod futures;
+#[cfg(feature = "std")]
+pub(crate) mod parallel;
+pub mod raw;
+pub mod read;
+mod slice;
+mod split;
 pub(crate) mod symbol;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+pub mod write;
 
 #[derive(Debug, Clone, Copy)]
 enum BlockType {
@@ -37,7 +51,9 @@ enum BlockType {
 
 #[cfg(test)]
 mod test {
-    use std::io::{Read, Write};
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use io::{Read, Write};
 
     use super::*;
     use lz77;
@@ -59,4 +75,206 @@ mod test {
 
         assert_eq!(buffer, plain);
     }
+
+    #[test]
+    fn compression_levels_work() {
+        let plain = (0..100_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        for level in 0..10 {
+            let options = EncodeOptions::new().level(level);
+            let mut encoder = Encoder::with_options(Vec::new(), options);
+            encoder.write_all(&plain[..]).expect("encode");
+            let encoded = encoder.finish().into_result().unwrap();
+            if level == 0 {
+                assert!(encoded.len() > plain.len());
+            } else {
+                assert!(encoded.len() < plain.len() / 2, "level={}", level);
+            }
+
+            let mut buffer = Vec::new();
+            let mut decoder = Decoder::new(&encoded[..]);
+            decoder.read_to_end(&mut buffer).expect("decode");
+            assert_eq!(buffer, plain, "level={}", level);
+        }
+    }
+
+    #[test]
+    fn sync_flush_works() {
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.write_all(b"Hello World!").unwrap();
+        encoder.sync_flush().unwrap();
+        assert!(encoder.as_inner_ref().ends_with(&[0, 0, 0xFF, 0xFF]));
+
+        // Flushing without new data emits nothing
+        let size = encoder.as_inner_ref().len();
+        encoder.flush().unwrap();
+        assert_eq!(encoder.as_inner_ref().len(), size);
+
+        let mut buffer = [0; 12];
+        let mut decoder = Decoder::new(&encoder.as_inner_ref()[..]);
+        decoder.read_exact(&mut buffer).expect("decode");
+        assert_eq!(&buffer, b"Hello World!");
+
+        encoder.write_all(b" Hello DEFLATE!").unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+
+        let mut buffer = Vec::new();
+        let mut decoder = Decoder::new(&encoded[..]);
+        decoder.read_to_end(&mut buffer).expect("decode");
+        assert_eq!(buffer, &b"Hello World! Hello DEFLATE!"[..]);
+    }
+
+    #[test]
+    fn sync_flush_after_filling_block_works() {
+        let plain = (0..1024).map(|i| (i % 7) as u8).collect::<Vec<_>>();
+        let options = EncodeOptions::new().block_size(plain.len());
+        let mut encoder = Encoder::with_options(Vec::new(), options);
+        encoder.write_all(&plain).unwrap();
+        encoder.sync_flush().unwrap();
+        assert!(encoder.as_inner_ref().ends_with(&[0, 0, 0xFF, 0xFF]));
+
+        let mut buffer = vec![0; plain.len()];
+        let mut decoder = Decoder::new(&encoder.as_inner_ref()[..]);
+        decoder.read_exact(&mut buffer).expect("decode");
+        assert_eq!(buffer, plain);
+    }
+
+    #[test]
+    fn full_flush_works() {
+        let plain = b"Hello World! Hello World! Hello World!";
+        let options = EncodeOptions::new().fixed_huffman_codes();
+        let mut encoder = Encoder::with_options(Vec::new(), options);
+        encoder.write_all(plain).unwrap();
+        encoder.full_flush().unwrap();
+        let offset = encoder.as_inner_ref().len();
+        encoder.write_all(plain).unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+
+        let mut buffer = Vec::new();
+        let mut decoder = Decoder::new(&encoded[offset..]);
+        decoder.read_to_end(&mut buffer).expect("decode");
+        assert_eq!(buffer, &plain[..]);
+    }
+
+    #[test]
+    fn cheapest_block_type_is_chosen() {
+        // Tiny data: fixed Huffman codes
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.write_all(b"Hello World!").unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+        assert_eq!(encoded[0] & 0b111, 0b011);
+
+        // Incompressible data: non-compressed blocks
+        let mut x: u32 = 1;
+        let plain = (0..100_000)
+            .map(|_| {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (x >> 16) as u8
+            })
+            .collect::<Vec<_>>();
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.write_all(&plain).unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+        assert_eq!(encoded[0] & 0b111, 0b000);
+        assert_eq!(encoded.len(), plain.len() + 5 * 2);
+
+        let mut buffer = Vec::new();
+        let mut decoder = Decoder::new(&encoded[..]);
+        decoder.read_to_end(&mut buffer).expect("decode");
+        assert_eq!(buffer, plain);
+    }
+
+    #[test]
+    fn non_compressed_blocks_work_with_preceding_blocks() {
+        let mut x: u32 = 1;
+        let random = (0..20_000)
+            .map(|_| {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (x >> 16) as u8
+            })
+            .collect::<Vec<_>>();
+        let mut plain = (0..10_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        for _ in 0..5 {
+            plain.extend_from_slice(&random);
+        }
+
+        for &block_size in &[1000, 4096, 70_000, DEFAULT_BLOCK_SIZE] {
+            let options = EncodeOptions::new().block_size(block_size);
+            let mut encoder = Encoder::with_options(Vec::new(), options);
+            encoder.set_dictionary(&random[..1000]).unwrap();
+            for chunk in plain.chunks(block_size) {
+                encoder.write_all(chunk).unwrap();
+            }
+            let encoded = encoder.finish().into_result().unwrap();
+
+            let mut buffer = Vec::new();
+            let mut decoder = Decoder::new(&encoded[..]);
+            decoder.set_dictionary(&random[..1000]);
+            decoder.read_to_end(&mut buffer).expect("decode");
+            assert!(buffer == plain, "block_size={}", block_size);
+        }
+    }
+
+    #[test]
+    fn small_blocks_refer_to_preceding_blocks() {
+        let plain = (0..100_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let encode = |block_size| {
+            let options = EncodeOptions::new().block_size(block_size);
+            let mut encoder = Encoder::with_options(Vec::new(), options);
+            for chunk in plain.chunks(block_size) {
+                encoder.write_all(chunk).unwrap();
+            }
+            encoder.finish().into_result().unwrap()
+        };
+        let large = encode(DEFAULT_BLOCK_SIZE);
+        let small = encode(4096);
+        assert!(small.len() < large.len() * 3 / 2);
+
+        let mut buffer = Vec::new();
+        let mut decoder = Decoder::new(&small[..]);
+        decoder.read_to_end(&mut buffer).expect("decode");
+        assert_eq!(buffer, plain);
+    }
+
+    #[test]
+    fn dictionary_works() {
+        let dictionary = br#"{"id":0,"name":"","tags":["alpha","beta","gamma"],"enabled":true}"#;
+        let plain = br#"{"id":12,"name":"foo","tags":["alpha","gamma"],"enabled":false}"#;
+        let encode = |dictionary: Option<&[u8]>| {
+            let mut encoder = Encoder::new(Vec::new());
+            if let Some(dictionary) = dictionary {
+                encoder.set_dictionary(dictionary).unwrap();
+            }
+            encoder.write_all(plain).unwrap();
+            encoder.finish().into_result().unwrap()
+        };
+        let encoded = encode(Some(dictionary));
+        assert!(encoded.len() < encode(None).len() / 2);
+
+        let mut buffer = Vec::new();
+        let mut decoder = Decoder::new(&encoded[..]);
+        decoder.set_dictionary(dictionary);
+        decoder.read_to_end(&mut buffer).expect("decode");
+        assert_eq!(buffer, &plain[..]);
+
+        let mut decoder = Decoder::new(&encoded[..]);
+        assert!(decoder.read_to_end(&mut Vec::new()).is_err());
+
+        // A dictionary can be set only before writing any data
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.sync_flush().unwrap();
+        assert!(encoder.set_dictionary(dictionary).is_ok());
+        encoder.write_all(plain).unwrap();
+        assert!(encoder.set_dictionary(dictionary).is_err());
+        encoder.sync_flush().unwrap();
+        assert!(encoder.set_dictionary(dictionary).is_err());
+    }
 }
@@ -0,0 +1,578 @@
+use alloc::vec::Vec;
+use core::cmp;
+use core::marker::PhantomData;
+use core::mem;
+use std::collections::BTreeMap;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use super::EncodeOptions;
+use super::Encoder;
+use checksum;
+use finish::{Complete, Finish};
+use io::{self, Write};
+use lz77;
+
+/// The default size of a chunk compressed by a worker thread.
+pub const DEFAULT_CHUNK_SIZE: usize = 128 * 1024;
+
+/// Options for the parallelism of `ParallelEncoder`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ParallelOptions {
+    threads: usize,
+    chunk_size: usize,
+}
+impl ParallelOptions {
+    /// Makes a default instance.
+    ///
+    /// The number of threads is the available parallelism of the system,
+    /// and the chunk size is `DEFAULT_CHUNK_SIZE`.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::deflate::{EncodeOptions, ParallelEncoder, ParallelOptions};
+    ///
+    /// let parallel = ParallelOptions::new();
+    /// let encoder = ParallelEncoder::with_options(Vec::new(), EncodeOptions::new(), parallel);
+    /// ```
+    pub fn new() -> Self {
+        ParallelOptions {
+            threads: thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+
+    /// Specifies the number of worker threads.
+    ///
+    /// If `threads` is `0`, it is treated as `1`.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::deflate::ParallelOptions;
+    ///
+    /// let parallel = ParallelOptions::new().threads(4);
+    /// assert_eq!(parallel.get_threads(), 4);
+    /// ```
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = cmp::max(1, threads);
+        self
+    }
+
+    /// Specifies the size of a chunk of input data which is compressed by a worker thread.
+    ///
+    /// Smaller chunks make the workers busy with less buffered data,
+    /// but each chunk boundary costs a few bytes of the output and resets the Huffman codes.
+    /// If `size` is `0`, it is treated as `1`.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::deflate::ParallelOptions;
+    ///
+    /// let parallel = ParallelOptions::new().chunk_size(1024 * 1024);
+    /// assert_eq!(parallel.get_chunk_size(), 1024 * 1024);
+    /// ```
+    pub fn chunk_size(mut self, size: usize) -> Self {
+        self.chunk_size = cmp::max(1, size);
+        self
+    }
+
+    /// Returns the number of worker threads.
+    pub fn get_threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Returns the size of a chunk.
+    pub fn get_chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+}
+impl Default for ParallelOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// DEFLATE encoder which compresses data on multiple threads (as pigz).
+///
+/// The input data is split into chunks, and each chunk is compressed by a worker thread
+/// with the preceding 32 KiB of the input as the preset dictionary.
+/// The chunks except the last one are terminated by a sync flush,
+/// and are concatenated in order into a single DEFLATE stream.
+///
+/// The output is slightly larger than the one of `Encoder`.
+///
+/// # Examples
+/// ```
+/// use std::io::{Read, Write};
+/// use libflate::deflate::{Decoder, ParallelEncoder};
+///
+/// let plain = (0..100_000).map(|i| format!("{} ", i)).collect::<String>();
+///
+/// let mut encoder = ParallelEncoder::new(Vec::new());
+/// encoder.write_all(plain.as_bytes()).unwrap();
+/// let encoded_data = encoder.finish().into_result().unwrap();
+///
+/// let mut decoder = Decoder::new(&encoded_data[..]);
+/// let mut decoded_data = Vec::new();
+/// decoder.read_to_end(&mut decoded_data).unwrap();
+/// assert_eq!(decoded_data, plain.as_bytes());
+/// ```
+#[derive(Debug)]
+pub struct ParallelEncoder<W, E = lz77::DefaultLz77Encoder> {
+    writer: W,
+    chunk_size: usize,
+    max_jobs: usize,
+    buf: Vec<u8>,
+    dictionary: Vec<u8>,
+    checksum: Checksum,
+    checksum_value: u32,
+    jobs: Option<mpsc::Sender<Job>>,
+    outputs: mpsc::Receiver<Output>,
+    workers: Vec<thread::JoinHandle<()>>,
+    submitted: usize,
+    written: usize,
+    pending: BTreeMap<usize, Output>,
+    _lz77: PhantomData<E>,
+}
+impl<W> ParallelEncoder<W, lz77::DefaultLz77Encoder>
+where
+    W: io::Write,
+{
+    /// Makes a new encoder instance.
+    ///
+    /// Encoded DEFLATE stream is written to `inner`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::ParallelEncoder;
+    ///
+    /// let mut encoder = ParallelEncoder::new(Vec::new());
+    /// encoder.write_all(b"Hello World!").unwrap();
+    ///
+    /// assert_eq!(encoder.finish().into_result().unwrap(),
+    ///            [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0]);
+    /// ```
+    pub fn new(inner: W) -> Self {
+        Self::with_options(inner, EncodeOptions::new(), ParallelOptions::new())
+    }
+}
+impl<W, E> ParallelEncoder<W, E>
+where
+    W: io::Write,
+    E: lz77::Lz77Encode + Clone + Send + 'static,
+{
+    /// Makes a new encoder instance with specified options.
+    ///
+    /// Each chunk is compressed by an `Encoder` made from a clone of `options`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::{EncodeOptions, ParallelEncoder, ParallelOptions};
+    ///
+    /// let options = EncodeOptions::new().level(9);
+    /// let parallel = ParallelOptions::new().threads(2).chunk_size(1024);
+    /// let mut encoder = ParallelEncoder::with_options(Vec::new(), options, parallel);
+    /// encoder.write_all(&[0; 10_000]).unwrap();
+    /// encoder.finish().into_result().unwrap();
+    /// ```
+    pub fn with_options(inner: W, options: EncodeOptions<E>, parallel: ParallelOptions) -> Self {
+        Self::with_checksum(inner, options, parallel, Checksum::None)
+    }
+
+    pub(crate) fn with_checksum(
+        inner: W,
+        options: EncodeOptions<E>,
+        parallel: ParallelOptions,
+        checksum: Checksum,
+    ) -> Self {
+        let (job_tx, job_rx) = mpsc::channel::<Job>();
+        let (output_tx, output_rx) = mpsc::channel();
+        let job_rx = Arc::new(Mutex::new(job_rx));
+        let workers = (0..parallel.threads)
+            .map(|_| {
+                let job_rx = Arc::clone(&job_rx);
+                let output_tx = output_tx.clone();
+                let options = options.clone();
+                thread::spawn(move || loop {
+                    let job = match job_rx.lock().expect("Never fails").recv() {
+                        Ok(job) => job,
+                        Err(_) => break,
+                    };
+                    if output_tx.send(job.run(&options, checksum)).is_err() {
+                        break;
+                    }
+                })
+            })
+            .collect();
+        ParallelEncoder {
+            writer: inner,
+            chunk_size: parallel.chunk_size,
+            max_jobs: parallel.threads * 2,
+            buf: Vec::with_capacity(parallel.chunk_size),
+            dictionary: Vec::new(),
+            checksum,
+            checksum_value: checksum.initial_value(),
+            jobs: Some(job_tx),
+            outputs: output_rx,
+            workers,
+            submitted: 0,
+            written: 0,
+            pending: BTreeMap::new(),
+            _lz77: PhantomData,
+        }
+    }
+
+    /// Compresses the remaining data, and returns the inner stream.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::ParallelEncoder;
+    ///
+    /// let mut encoder = ParallelEncoder::new(Vec::new());
+    /// encoder.write_all(b"Hello World!").unwrap();
+    ///
+    /// assert_eq!(encoder.finish().into_result().unwrap(),
+    ///            [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0]);
+    /// ```
+    pub fn finish(self) -> Finish<W, io::Error> {
+        self.finish_with_checksum().0
+    }
+
+    /// Performs a sync flush (equivalent to `Z_SYNC_FLUSH` of zlib).
+    ///
+    /// The buffered data is compressed as a chunk, and this waits until all the chunks are written.
+    ///
+    /// This is also invoked by `io::Write::flush`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Read, Write};
+    /// use libflate::deflate::{Decoder, ParallelEncoder};
+    ///
+    /// let mut encoder = ParallelEncoder::new(Vec::new());
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// encoder.sync_flush().unwrap();
+    /// assert!(encoder.as_inner_ref().ends_with(&[0, 0, 255, 255]));
+    ///
+    /// let mut decoder = Decoder::new(&encoder.as_inner_ref()[..]);
+    /// let mut buf = [0; 12];
+    /// decoder.read_exact(&mut buf).unwrap();
+    /// assert_eq!(&buf, b"Hello World!");
+    /// ```
+    pub fn sync_flush(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            self.submit(false)?;
+        }
+        while self.written < self.submitted {
+            self.receive()?;
+        }
+        self.writer.flush()
+    }
+
+    /// Sets the preset dictionary.
+    ///
+    /// See `Encoder::set_dictionary` for more details.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Read, Write};
+    /// use libflate::deflate::{Decoder, ParallelEncoder};
+    ///
+    /// let mut encoder = ParallelEncoder::new(Vec::new());
+    /// encoder.set_dictionary(b"Hello World!").unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    ///
+    /// let mut decoder = Decoder::new(&encoded_data[..]);
+    /// decoder.set_dictionary(b"Hello World!");
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    /// assert_eq!(buf, b"Hello World!");
+    /// ```
+    pub fn set_dictionary(&mut self, dictionary: &[u8]) -> io::Result<()> {
+        if self.submitted > 0 || !self.buf.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "A dictionary can be set only before writing any data",
+            ));
+        }
+        self.dictionary = next_dictionary(&[], dictionary);
+        Ok(())
+    }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Unwraps the `ParallelEncoder`, returning the inner stream.
+    ///
+    /// The data which is not flushed yet is discarded.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Finishes the stream, and returns the checksum of the whole input data.
+    pub(crate) fn finish_with_checksum(mut self) -> (Finish<W, io::Error>, u32) {
+        let result = self.submit(true).and_then(|_| {
+            while self.written < self.submitted {
+                self.receive()?;
+            }
+            self.writer.flush()
+        });
+        self.jobs = None;
+        for worker in self.workers {
+            let _ = worker.join();
+        }
+        let finish = match result {
+            Ok(_) => Finish::new(self.writer, None),
+            Err(e) => Finish::new(self.writer, Some(e)),
+        };
+        (finish, self.checksum_value)
+    }
+
+    fn submit(&mut self, last: bool) -> io::Result<()> {
+        let data = mem::replace(&mut self.buf, Vec::with_capacity(self.chunk_size));
+        let dictionary = next_dictionary(&self.dictionary, &data);
+        let job = Job {
+            index: self.submitted,
+            dictionary: mem::replace(&mut self.dictionary, dictionary),
+            data,
+            last,
+        };
+        self.jobs
+            .as_ref()
+            .expect("Never fails")
+            .send(job)
+            .map_err(|_| worker_terminated_error())?;
+        self.submitted += 1;
+        while self.submitted - self.written >= self.max_jobs {
+            self.receive()?;
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self) -> io::Result<()> {
+        let output = self.outputs.recv().map_err(|_| worker_terminated_error())?;
+        self.pending.insert(output.index, output);
+        while let Some(output) = self.pending.remove(&self.written) {
+            self.writer.write_all(&output.encoded?)?;
+            self.checksum_value =
+                self.checksum
+                    .combine(self.checksum_value, output.checksum, output.size);
+            self.written += 1;
+        }
+        Ok(())
+    }
+}
+impl<W, E> io::Write for ParallelEncoder<W, E>
+where
+    W: io::Write,
+    E: lz77::Lz77Encode + Clone + Send + 'static,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let size = cmp::min(buf.len(), self.chunk_size - self.buf.len());
+        self.buf.extend_from_slice(&buf[..size]);
+        if self.buf.len() == self.chunk_size {
+            self.submit(false)?;
+        }
+        Ok(size)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.sync_flush()
+    }
+}
+impl<W, E> Complete for ParallelEncoder<W, E>
+where
+    W: io::Write,
+    E: lz77::Lz77Encode + Clone + Send + 'static,
+{
+    fn complete(self) -> io::Result<()> {
+        self.finish().into_result().map(|_| ())
+    }
+}
+
+/// The checksum of the input data calculated by the worker threads.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Checksum {
+    None,
+    Adler32,
+    Crc32,
+}
+impl Checksum {
+    fn initial_value(self) -> u32 {
+        match self {
+            Checksum::None => 0,
+            Checksum::Adler32 => checksum::Adler32::new().value(),
+            Checksum::Crc32 => checksum::Crc32::new().value(),
+        }
+    }
+    fn calculate(self, data: &[u8]) -> u32 {
+        match self {
+            Checksum::None => 0,
+            Checksum::Adler32 => {
+                let mut adler32 = checksum::Adler32::new();
+                adler32.update(data);
+                adler32.value()
+            }
+            Checksum::Crc32 => {
+                let mut crc32 = checksum::Crc32::new();
+                crc32.update(data);
+                crc32.value()
+            }
+        }
+    }
+    fn combine(self, value1: u32, value2: u32, len2: usize) -> u32 {
+        match self {
+            Checksum::None => 0,
+            Checksum::Adler32 => checksum::combine_adler32(value1, value2, len2 as u64),
+            Checksum::Crc32 => checksum::combine_crc32(value1, value2, len2 as u64),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Job {
+    index: usize,
+    dictionary: Vec<u8>,
+    data: Vec<u8>,
+    last: bool,
+}
+impl Job {
+    fn run<E>(self, options: &EncodeOptions<E>, checksum: Checksum) -> Output
+    where
+        E: lz77::Lz77Encode + Clone,
+    {
+        Output {
+            index: self.index,
+            encoded: self.encode(options),
+            checksum: checksum.calculate(&self.data),
+            size: self.data.len(),
+        }
+    }
+    fn encode<E>(&self, options: &EncodeOptions<E>) -> io::Result<Vec<u8>>
+    where
+        E: lz77::Lz77Encode + Clone,
+    {
+        let mut encoder = Encoder::with_options(Vec::new(), options.clone());
+        encoder.set_dictionary(&self.dictionary)?;
+        encoder.write_all(&self.data)?;
+        if self.last {
+            encoder.finish().into_result()
+        } else {
+            encoder.sync_flush()?;
+            Ok(encoder.into_inner())
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Output {
+    index: usize,
+    encoded: io::Result<Vec<u8>>,
+    checksum: u32,
+    size: usize,
+}
+
+/// Returns the last `lz77::MAX_DISTANCE` bytes of the concatenation of `dictionary` and `data`.
+fn next_dictionary(dictionary: &[u8], data: &[u8]) -> Vec<u8> {
+    let window_size = lz77::MAX_DISTANCE as usize;
+    let data = &data[data.len() - cmp::min(data.len(), window_size)..];
+    let rest = cmp::min(dictionary.len(), window_size - data.len());
+    let mut next = Vec::with_capacity(rest + data.len());
+    next.extend_from_slice(&dictionary[dictionary.len() - rest..]);
+    next.extend_from_slice(data);
+    next
+}
+
+fn worker_terminated_error() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "Worker threads are terminated")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use deflate::Decoder;
+    use io::Read;
+
+    fn decode(encoded: &[u8]) -> Vec<u8> {
+        let mut decoder = Decoder::new(encoded);
+        let mut buf = Vec::new();
+        decoder.read_to_end(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn next_dictionary_works() {
+        let window_size = lz77::MAX_DISTANCE as usize;
+        assert_eq!(next_dictionary(b"abc", b"de"), b"abcde");
+
+        let data = (0..window_size + 10).map(|i| i as u8).collect::<Vec<_>>();
+        assert_eq!(next_dictionary(b"abc", &data), &data[10..]);
+
+        let dictionary = next_dictionary(&data, b"xyz");
+        assert_eq!(dictionary.len(), window_size);
+        assert_eq!(&dictionary[..window_size - 3], &data[13..]);
+        assert_eq!(&dictionary[window_size - 3..], b"xyz");
+    }
+
+    #[test]
+    fn parallel_encode_works() {
+        let plain = (0..200_000)
+            .map(|i| format!("{} {} ", i % 907, i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        for &(threads, chunk_size) in &[(1, 1000), (3, 40_000), (4, DEFAULT_CHUNK_SIZE)] {
+            let parallel = ParallelOptions::new()
+                .threads(threads)
+                .chunk_size(chunk_size);
+            let mut encoder = ParallelEncoder::with_checksum(
+                Vec::new(),
+                EncodeOptions::new(),
+                parallel,
+                Checksum::Crc32,
+            );
+            encoder.write_all(&plain).unwrap();
+            let (finish, crc32) = encoder.finish_with_checksum();
+            let encoded = finish.into_result().unwrap();
+            assert_eq!(decode(&encoded), plain);
+            assert_eq!(crc32, Checksum::Crc32.calculate(&plain));
+        }
+
+        let encoded = ParallelEncoder::new(Vec::new())
+            .finish()
+            .into_result()
+            .unwrap();
+        assert_eq!(decode(&encoded), b"");
+    }
+
+    #[test]
+    fn chunks_refer_to_preceding_chunks() {
+        let plain = (0..100_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let encode = |chunk_size| {
+            let parallel = ParallelOptions::new().threads(2).chunk_size(chunk_size);
+            let mut encoder =
+                ParallelEncoder::with_options(Vec::new(), EncodeOptions::new(), parallel);
+            encoder.write_all(&plain).unwrap();
+            encoder.finish().into_result().unwrap()
+        };
+        let single = encode(plain.len());
+        let chunked = encode(4096);
+        assert_eq!(decode(&chunked), plain);
+        assert!(chunked.len() < single.len() * 2);
+    }
+}
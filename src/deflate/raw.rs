@@ -0,0 +1,443 @@
+//! Low-level DEFLATE compressor and decompressor which work on slices.
+//!
+//! Like `deflate()` and `inflate()` of zlib, `Compress` and `Decompress` consume as much input
+//! and produce as much output as possible for each call,
+//! and the caller keeps track of the progress through `total_in` and `total_out`.
+//!
+//! # Examples
+//! ```
+//! use libflate::deflate::raw::{Compress, Decompress, FlushMode, Status};
+//!
+//! let mut compress = Compress::new();
+//! let mut encoded_data = [0; 64];
+//! let status = compress.compress(b"Hello World!", &mut encoded_data, FlushMode::Finish);
+//! assert_eq!(status, Status::StreamEnd);
+//! let encoded_data = &encoded_data[..compress.total_out() as usize];
+//!
+//! let mut decompress = Decompress::new();
+//! let mut decoded_data = [0; 64];
+//! let status = decompress
+//!     .decompress(encoded_data, &mut decoded_data, FlushMode::Finish)
+//!     .unwrap();
+//! assert_eq!(status, Status::StreamEnd);
+//! assert_eq!(&decoded_data[..decompress.total_out() as usize], b"Hello World!");
+//! ```
+use alloc::vec::Vec;
+use core::cmp;
+use core::mem;
+
+use super::{EncodeOptions, Encoder};
+use adapter::InputBuffer;
+use io::{self, Read, Write};
+use lz77;
+use non_blocking;
+
+/// Flush mode of `Compress::compress` and `Decompress::decompress`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FlushMode {
+    /// The compressor may keep the input in the internal buffer to decide the block boundaries.
+    None,
+
+    /// All the input is made available to the decompressor (equivalent to `Z_SYNC_FLUSH` of zlib).
+    ///
+    /// See `deflate::Encoder::sync_flush` for more details.
+    Sync,
+
+    /// Same as `Sync`, and the succeeding data is made independent of the preceding data
+    /// (equivalent to `Z_FULL_FLUSH` of zlib).
+    ///
+    /// See `deflate::Encoder::full_flush` for more details.
+    Full,
+
+    /// The input is the last one of the stream.
+    ///
+    /// The compressor terminates the stream,
+    /// and the decompressor reports an `UnexpectedEof` error if the stream is truncated.
+    Finish,
+}
+
+/// Progress of `Compress::compress` and `Decompress::decompress`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Status {
+    /// Some progress has been made.
+    Ok,
+
+    /// No progress was possible (e.g., the output buffer is empty or more input is needed).
+    BufError,
+
+    /// The end of the stream has been reached, and all the output has been produced.
+    StreamEnd,
+}
+
+/// Low-level DEFLATE compressor.
+///
+/// The output which does not fit in the output buffer is kept in the internal buffer,
+/// and no input is consumed until it is taken by the succeeding calls.
+#[derive(Debug)]
+pub struct Compress<E = lz77::DefaultLz77Encoder> {
+    encoder: Option<Encoder<Vec<u8>, E>>,
+    buf: Vec<u8>,
+    offset: usize,
+    flushed: bool,
+    total_in: u64,
+    total_out: u64,
+}
+impl Compress<lz77::DefaultLz77Encoder> {
+    /// Makes a new compressor instance.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::deflate::raw::{Compress, FlushMode, Status};
+    ///
+    /// let mut compress = Compress::new();
+    /// let mut buf = [0; 64];
+    /// compress.compress(b"Hello World!", &mut buf, FlushMode::Finish);
+    /// assert_eq!(&buf[..compress.total_out() as usize],
+    ///            [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0]);
+    /// ```
+    pub fn new() -> Self {
+        Self::with_options(EncodeOptions::default())
+    }
+}
+impl Default for Compress<lz77::DefaultLz77Encoder> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<E> Compress<E>
+where
+    E: lz77::Lz77Encode,
+{
+    /// Makes a new compressor instance with specified options.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::deflate::EncodeOptions;
+    /// use libflate::deflate::raw::{Compress, FlushMode, Status};
+    ///
+    /// let mut compress = Compress::with_options(EncodeOptions::new().no_compression());
+    /// let mut buf = [0; 64];
+    /// compress.compress(b"Hello World!", &mut buf, FlushMode::Finish);
+    /// assert_eq!(&buf[..compress.total_out() as usize],
+    ///            [1, 12, 0, 243, 255, 72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100, 33]);
+    /// ```
+    pub fn with_options(options: EncodeOptions<E>) -> Self {
+        Compress {
+            encoder: Some(Encoder::with_options(Vec::new(), options)),
+            buf: Vec::new(),
+            offset: 0,
+            flushed: false,
+            total_in: 0,
+            total_out: 0,
+        }
+    }
+
+    /// Compresses `input` and writes the result to `output`.
+    ///
+    /// The numbers of the consumed and produced bytes are added to `total_in` and `total_out`.
+    /// If `flush` is not `FlushMode::None` and the output is not fully produced,
+    /// this should be called again with the same `flush` (and the unconsumed input).
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::deflate::raw::{Compress, FlushMode, Status};
+    ///
+    /// let mut compress = Compress::new();
+    /// let mut encoded_data = Vec::new();
+    /// let mut buf = [0; 4];
+    /// loop {
+    ///     let total_out = compress.total_out();
+    ///     let input = &b"Hello World!"[compress.total_in() as usize..];
+    ///     let status = compress.compress(input, &mut buf, FlushMode::Finish);
+    ///     encoded_data.extend_from_slice(&buf[..(compress.total_out() - total_out) as usize]);
+    ///     if status == Status::StreamEnd {
+    ///         break;
+    ///     }
+    /// }
+    /// assert_eq!(encoded_data, [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0]);
+    /// ```
+    pub fn compress(&mut self, input: &[u8], output: &mut [u8], flush: FlushMode) -> Status {
+        let mut written = self.write_pending(output);
+        let mut consumed = 0;
+        if self.offset == self.buf.len() {
+            if let Some(ref mut encoder) = self.encoder {
+                if !input.is_empty() {
+                    encoder.write_all(input).expect("Never fails");
+                    consumed = input.len();
+                    self.flushed = false;
+                }
+                if !self.flushed {
+                    match flush {
+                        FlushMode::Sync => encoder.sync_flush().expect("Never fails"),
+                        FlushMode::Full => encoder.full_flush().expect("Never fails"),
+                        FlushMode::None | FlushMode::Finish => {}
+                    }
+                    self.flushed = flush == FlushMode::Sync || flush == FlushMode::Full;
+                }
+                self.buf.clear();
+                self.offset = 0;
+                mem::swap(&mut self.buf, encoder.as_inner_mut());
+            }
+            if flush == FlushMode::Finish {
+                if let Some(encoder) = self.encoder.take() {
+                    let rest = encoder.finish().into_result().expect("Never fails");
+                    self.buf.extend_from_slice(&rest);
+                }
+            }
+            written += self.write_pending(&mut output[written..]);
+        }
+        self.total_in += consumed as u64;
+        self.total_out += written as u64;
+
+        if self.encoder.is_none() && self.offset == self.buf.len() {
+            Status::StreamEnd
+        } else if consumed == 0 && written == 0 {
+            Status::BufError
+        } else {
+            Status::Ok
+        }
+    }
+
+    /// Returns the total number of the consumed input bytes.
+    pub fn total_in(&self) -> u64 {
+        self.total_in
+    }
+
+    /// Returns the total number of the produced output bytes.
+    pub fn total_out(&self) -> u64 {
+        self.total_out
+    }
+
+    fn write_pending(&mut self, output: &mut [u8]) -> usize {
+        let size = cmp::min(output.len(), self.buf.len() - self.offset);
+        output[..size].copy_from_slice(&self.buf[self.offset..][..size]);
+        self.offset += size;
+        size
+    }
+}
+
+/// Low-level DEFLATE decompressor.
+///
+/// This is built on `non_blocking::deflate::Decoder`,
+/// so the input which ends in the middle of the stream is resumed by the succeeding calls.
+#[derive(Debug)]
+pub struct Decompress {
+    decoder: non_blocking::deflate::Decoder<InputBuffer>,
+    eos: bool,
+    total_in: u64,
+    total_out: u64,
+}
+impl Decompress {
+    /// Makes a new decompressor instance.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::deflate::raw::{Decompress, FlushMode, Status};
+    ///
+    /// let encoded_data = [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0];
+    ///
+    /// let mut decompress = Decompress::new();
+    /// let mut buf = [0; 64];
+    /// decompress.decompress(&encoded_data, &mut buf, FlushMode::None).unwrap();
+    /// assert_eq!(&buf[..decompress.total_out() as usize], b"Hello World!");
+    /// ```
+    pub fn new() -> Self {
+        Decompress {
+            decoder: non_blocking::deflate::Decoder::new(InputBuffer::new()),
+            eos: false,
+            total_in: 0,
+            total_out: 0,
+        }
+    }
+
+    /// Sets the preset dictionary.
+    ///
+    /// See `deflate::Decoder::set_dictionary` for more details.
+    pub fn set_dictionary(&mut self, dictionary: &[u8]) {
+        self.decoder.set_dictionary(dictionary);
+    }
+
+    /// Decompresses `input` and writes the result to `output`.
+    ///
+    /// The numbers of the consumed and produced bytes are added to `total_in` and `total_out`.
+    /// The bytes following the end of the stream are not consumed.
+    ///
+    /// If `flush` is `FlushMode::Finish` and the stream ends in the middle of `input`,
+    /// an `UnexpectedEof` error is returned.
+    /// The other modes do not affect the behavior.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::deflate::raw::{Decompress, FlushMode, Status};
+    ///
+    /// let encoded_data = [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0];
+    ///
+    /// let mut decompress = Decompress::new();
+    /// let mut buf = [0; 64];
+    /// let status = decompress.decompress(&encoded_data[..5], &mut buf, FlushMode::None).unwrap();
+    /// assert_eq!(status, Status::Ok);
+    ///
+    /// let status = decompress.decompress(&encoded_data[5..], &mut buf, FlushMode::None).unwrap();
+    /// assert_eq!(status, Status::StreamEnd);
+    /// assert_eq!(decompress.total_in(), 14);
+    /// assert_eq!(decompress.total_out(), 12);
+    ///
+    /// let mut decompress = Decompress::new();
+    /// assert!(decompress.decompress(&encoded_data[..5], &mut buf, FlushMode::Finish).is_err());
+    /// ```
+    pub fn decompress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        flush: FlushMode,
+    ) -> io::Result<Status> {
+        self.decoder.as_inner_mut().push(input);
+        let mut written = 0;
+        let mut result = Ok(());
+        while written < output.len() && !self.eos {
+            match self.decoder.read(&mut output[written..]) {
+                Ok(0) => self.eos = true,
+                Ok(size) => written += size,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        let consumed = input.len() - self.decoder.as_inner_mut().discard_remaining();
+        self.total_in += consumed as u64;
+        self.total_out += written as u64;
+        result?;
+
+        if self.eos {
+            Ok(Status::StreamEnd)
+        } else if flush == FlushMode::Finish && written < output.len() {
+            Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "The DEFLATE stream is truncated",
+            ))
+        } else if consumed == 0 && written == 0 {
+            Ok(Status::BufError)
+        } else {
+            Ok(Status::Ok)
+        }
+    }
+
+    /// Returns the total number of the consumed input bytes.
+    pub fn total_in(&self) -> u64 {
+        self.total_in
+    }
+
+    /// Returns the total number of the produced output bytes.
+    pub fn total_out(&self) -> u64 {
+        self.total_out
+    }
+}
+impl Default for Decompress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::string::String;
+
+    fn compress_all(compress: &mut Compress, input: &[u8], buf_size: usize) -> Vec<u8> {
+        let mut output = Vec::new();
+        let mut buf = vec![0; buf_size];
+        loop {
+            let total_out = compress.total_out();
+            let rest = &input[compress.total_in() as usize..];
+            let chunk = &rest[..cmp::min(rest.len(), 1000)];
+            let flush = if chunk.len() == rest.len() {
+                FlushMode::Finish
+            } else {
+                FlushMode::None
+            };
+            let status = compress.compress(chunk, &mut buf, flush);
+            output.extend_from_slice(&buf[..(compress.total_out() - total_out) as usize]);
+            if status == Status::StreamEnd {
+                return output;
+            }
+        }
+    }
+
+    fn decompress_all(input: &[u8], chunk_size: usize, buf_size: usize) -> io::Result<Vec<u8>> {
+        let mut decompress = Decompress::new();
+        let mut output = Vec::new();
+        let mut buf = vec![0; buf_size];
+        loop {
+            let total_out = decompress.total_out();
+            let rest = &input[decompress.total_in() as usize..];
+            let chunk = &rest[..cmp::min(rest.len(), chunk_size)];
+            let flush = if chunk.len() == rest.len() {
+                FlushMode::Finish
+            } else {
+                FlushMode::None
+            };
+            let status = decompress.decompress(chunk, &mut buf, flush)?;
+            output.extend_from_slice(&buf[..(decompress.total_out() - total_out) as usize]);
+            if status == Status::StreamEnd {
+                return Ok(output);
+            }
+        }
+    }
+
+    #[test]
+    fn compress_and_decompress_works() {
+        let plain = (0..100_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        for &buf_size in &[1, 100, 100_000] {
+            let mut compress = Compress::with_options(EncodeOptions::new().block_size(4096));
+            let encoded = compress_all(&mut compress, &plain, buf_size);
+            assert_eq!(compress.total_in(), plain.len() as u64);
+            assert_eq!(compress.total_out(), encoded.len() as u64);
+
+            let mut encoded_with_trailer = encoded.clone();
+            encoded_with_trailer.extend_from_slice(b"trailer");
+            for &chunk_size in &[1, 1000, encoded.len()] {
+                let decoded = decompress_all(&encoded_with_trailer, chunk_size, buf_size).unwrap();
+                assert_eq!(decoded, plain);
+            }
+
+            let truncated = &encoded[..encoded.len() - 1];
+            assert!(decompress_all(truncated, 1000, buf_size).is_err());
+        }
+    }
+
+    #[test]
+    fn sync_flush_works() {
+        let mut compress = Compress::new();
+        let mut buf = [0; 64];
+        assert_eq!(
+            compress.compress(b"Hello", &mut buf, FlushMode::None),
+            Status::Ok
+        );
+        assert_eq!(compress.total_out(), 0);
+        assert_eq!(
+            compress.compress(b"", &mut buf, FlushMode::Sync),
+            Status::Ok
+        );
+        let size = compress.total_out() as usize;
+        assert!(size > 0);
+        assert_eq!(
+            compress.compress(b"", &mut buf[size..], FlushMode::Sync),
+            Status::BufError
+        );
+
+        let mut decompress = Decompress::new();
+        let mut decoded = [0; 64];
+        assert_eq!(
+            decompress
+                .decompress(&buf[..size], &mut decoded, FlushMode::None)
+                .unwrap(),
+            Status::Ok
+        );
+        assert_eq!(&decoded[..decompress.total_out() as usize], b"Hello");
+    }
+}
@@ -0,0 +1,142 @@
+//! The DEFLATE encoder which compresses the data read from an inner reader.
+//!
+//! # Examples
+//! ```
+//! use std::io::Read;
+//! use libflate::deflate::Decoder;
+//! use libflate::deflate::read::Encoder;
+//!
+//! let mut encoder = Encoder::new(&b"Hello World!"[..]);
+//! let mut encoded_data = Vec::new();
+//! encoder.read_to_end(&mut encoded_data).unwrap();
+//!
+//! let mut decoder = Decoder::new(&encoded_data[..]);
+//! let mut decoded_data = Vec::new();
+//! decoder.read_to_end(&mut decoded_data).unwrap();
+//!
+//! assert_eq!(decoded_data, b"Hello World!");
+//! ```
+use alloc::vec::Vec;
+
+use super::EncodeOptions;
+use adapter::ReadEncoder;
+use io::{self, Read};
+use lz77;
+
+/// DEFLATE encoder which implements `io::Read`.
+///
+/// The plain data is read from the inner reader, and `read` returns the encoded data.
+/// This is useful to pass compressed data to an API which takes a reader.
+#[derive(Debug)]
+pub struct Encoder<R, E = lz77::DefaultLz77Encoder> {
+    inner: ReadEncoder<super::Encoder<Vec<u8>, E>, R>,
+}
+impl<R> Encoder<R, lz77::DefaultLz77Encoder>
+where
+    R: Read,
+{
+    /// Makes a new encoder instance.
+    ///
+    /// The data read from `inner` is to be encoded.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    /// use libflate::deflate::read::Encoder;
+    ///
+    /// let mut encoder = Encoder::new(&b"Hello World!"[..]);
+    /// let mut buf = Vec::new();
+    /// encoder.read_to_end(&mut buf).unwrap();
+    ///
+    /// assert_eq!(buf, [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0]);
+    /// ```
+    pub fn new(inner: R) -> Self {
+        Self::with_options(inner, EncodeOptions::default())
+    }
+}
+impl<R, E> Encoder<R, E>
+where
+    R: Read,
+    E: lz77::Lz77Encode,
+{
+    /// Makes a new encoder instance with specified options.
+    ///
+    /// The data read from `inner` is to be encoded.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    /// use libflate::deflate::EncodeOptions;
+    /// use libflate::deflate::read::Encoder;
+    ///
+    /// let options = EncodeOptions::new().no_compression();
+    /// let mut encoder = Encoder::with_options(&b"Hello World!"[..], options);
+    /// let mut buf = Vec::new();
+    /// encoder.read_to_end(&mut buf).unwrap();
+    ///
+    /// assert_eq!(buf, [1, 12, 0, 243, 255, 72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100, 33]);
+    /// ```
+    pub fn with_options(inner: R, options: EncodeOptions<E>) -> Self {
+        let encoder = super::Encoder::with_options(Vec::new(), options);
+        Encoder {
+            inner: ReadEncoder::new(encoder, inner),
+        }
+    }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &R {
+        self.inner.as_inner_ref()
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut R {
+        self.inner.as_inner_mut()
+    }
+
+    /// Unwraps the `Encoder`, returning the inner stream.
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+}
+impl<R, E> Read for Encoder<R, E>
+where
+    R: Read,
+    E: lz77::Lz77Encode,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::string::String;
+    use deflate::Decoder;
+
+    #[test]
+    fn read_encode_works() {
+        let plain = (0..100_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let options = EncodeOptions::new().block_size(4096);
+        let mut encoder = Encoder::with_options(&plain[..], options);
+        let mut encoded = Vec::new();
+        let mut buf = [0; 100];
+        loop {
+            let size = encoder.read(&mut buf).unwrap();
+            if size == 0 {
+                break;
+            }
+            encoded.extend_from_slice(&buf[..size]);
+        }
+        assert_eq!(encoder.read(&mut buf).unwrap(), 0);
+
+        let mut decoded = Vec::new();
+        Decoder::new(&encoded[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, plain);
+    }
+}
@@ -0,0 +1,349 @@
+use byteorder::LittleEndian;
+use core::fmt;
+
+use super::symbol;
+use super::{EncodeOptions, Encoder};
+use bit;
+use io::ReadBytesExt;
+use io::{self, Read, Write};
+use lz77;
+use util;
+
+const BUFFER_TOO_SMALL: &str = "The output buffer is too small";
+
+/// The error type of the slice-based functions (e.g., `deflate::decompress_into`).
+///
+/// `SliceError::BufferTooSmall` is converted from and into an `io::Error` of `WriteZero` kind.
+#[derive(Debug)]
+pub enum SliceError {
+    /// The output buffer is too small to hold the result.
+    BufferTooSmall,
+
+    /// The input is not a valid stream (or an I/O error occurred).
+    Io(io::Error),
+}
+impl fmt::Display for SliceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SliceError::BufferTooSmall => write!(f, "{}", BUFFER_TOO_SMALL),
+            SliceError::Io(ref e) => e.fmt(f),
+        }
+    }
+}
+#[cfg(feature = "std")]
+impl ::std::error::Error for SliceError {
+    fn source(&self) -> Option<&(dyn (::std::error::Error) + 'static)> {
+        match *self {
+            SliceError::BufferTooSmall => None,
+            SliceError::Io(ref e) => Some(e),
+        }
+    }
+}
+impl From<io::Error> for SliceError {
+    fn from(f: io::Error) -> Self {
+        if f.kind() == io::ErrorKind::WriteZero {
+            SliceError::BufferTooSmall
+        } else {
+            SliceError::Io(f)
+        }
+    }
+}
+impl From<SliceError> for io::Error {
+    fn from(f: SliceError) -> Self {
+        match f {
+            SliceError::BufferTooSmall => {
+                io::Error::new(io::ErrorKind::WriteZero, BUFFER_TOO_SMALL)
+            }
+            SliceError::Io(e) => e,
+        }
+    }
+}
+
+/// Encodes `input` into `output` as a DEFLATE stream, and returns the size of the encoded data.
+///
+/// If `output` is too small, `SliceError::BufferTooSmall` is returned.
+/// `output` never runs short if its size is `deflate::max_compressed_len(input.len(), &options)`.
+///
+/// # Examples
+/// ```
+/// use libflate::deflate::{self, EncodeOptions, SliceError};
+///
+/// let mut buf = [0; 64];
+/// let size = deflate::compress_into(b"Hello World!", &mut buf, EncodeOptions::new()).unwrap();
+/// assert_eq!(&buf[..size], [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0]);
+///
+/// let mut buf = [0; 13];
+/// match deflate::compress_into(b"Hello World!", &mut buf, EncodeOptions::new()) {
+///     Err(SliceError::BufferTooSmall) => {}
+///     _ => unreachable!(),
+/// }
+/// ```
+pub fn compress_into<E>(
+    input: &[u8],
+    output: &mut [u8],
+    options: EncodeOptions<E>,
+) -> Result<usize, SliceError>
+where
+    E: lz77::Lz77Encode,
+{
+    let output_len = output.len();
+    let mut encoder = Encoder::with_options(output, options);
+    encoder.write_all(input)?;
+    let rest = encoder.finish().into_result()?;
+    Ok(output_len - rest.len())
+}
+
+/// Returns the upper bound of the size of the DEFLATE stream
+/// which `deflate::compress_into` produces from `input_len` bytes.
+///
+/// # Examples
+/// ```
+/// use libflate::deflate::{self, EncodeOptions};
+///
+/// let options = EncodeOptions::new();
+/// let mut buf = vec![0; deflate::max_compressed_len(12, &options)];
+/// let size = deflate::compress_into(b"Hello World!", &mut buf, options).unwrap();
+/// assert_eq!(size, 14);
+/// ```
+pub fn max_compressed_len<E>(input_len: usize, options: &EncodeOptions<E>) -> usize
+where
+    E: lz77::Lz77Encode,
+{
+    options.max_encoded_len(input_len)
+}
+
+/// Decodes the DEFLATE stream `input` into `output`, and returns the size of the decoded data.
+///
+/// `output` itself is used as the history window of the back-references,
+/// so no buffer is allocated for the decoded data.
+/// If `output` is too small, `SliceError::BufferTooSmall` is returned.
+///
+/// The bytes following the end of the stream in `input` are ignored.
+///
+/// # Examples
+/// ```
+/// use libflate::deflate::{self, SliceError};
+///
+/// let encoded_data = [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0];
+///
+/// let mut buf = [0; 12];
+/// let size = deflate::decompress_into(&encoded_data, &mut buf).unwrap();
+/// assert_eq!(&buf[..size], b"Hello World!");
+///
+/// let mut buf = [0; 11];
+/// match deflate::decompress_into(&encoded_data, &mut buf) {
+///     Err(SliceError::BufferTooSmall) => {}
+///     _ => unreachable!(),
+/// }
+/// ```
+pub fn decompress_into(input: &[u8], output: &mut [u8]) -> Result<usize, SliceError> {
+    let mut bit_reader = bit::BitReader::new(input);
+    let mut size = 0;
+    loop {
+        let bfinal = bit_reader.read_bit()?;
+        let btype = bit_reader.read_bits(2)?;
+        size = match btype {
+            0b00 => read_non_compressed_block(&mut bit_reader, output, size)?,
+            0b01 => {
+                read_compressed_block(&mut bit_reader, &symbol::FixedHuffmanCodec, output, size)?
+            }
+            0b10 => {
+                read_compressed_block(&mut bit_reader, &symbol::DynamicHuffmanCodec, output, size)?
+            }
+            0b11 => {
+                return Err(SliceError::Io(invalid_data_error!(
+                    "btype 0x11 of DEFLATE is reserved(error) value"
+                )))
+            }
+            _ => unreachable!(),
+        };
+        if bfinal {
+            return Ok(size);
+        }
+    }
+}
+
+fn read_non_compressed_block(
+    bit_reader: &mut bit::BitReader<&[u8]>,
+    output: &mut [u8],
+    size: usize,
+) -> Result<usize, SliceError> {
+    bit_reader.align_to_byte();
+    let len = bit_reader.read_u16::<LittleEndian>()?;
+    let nlen = bit_reader.read_u16::<LittleEndian>()?;
+    if !len != nlen {
+        return Err(SliceError::Io(invalid_data_error!(
+            "LEN={} is not the one's complement of NLEN={}",
+            len,
+            nlen
+        )));
+    }
+    let end = size + len as usize;
+    if end > output.len() {
+        return Err(SliceError::BufferTooSmall);
+    }
+    bit_reader.read_exact(&mut output[size..end])?;
+    Ok(end)
+}
+
+fn read_compressed_block<H>(
+    bit_reader: &mut bit::BitReader<&[u8]>,
+    huffman: &H,
+    output: &mut [u8],
+    mut size: usize,
+) -> Result<usize, SliceError>
+where
+    H: symbol::HuffmanCodec,
+{
+    let symbol_decoder = huffman.load(bit_reader)?;
+    loop {
+        let s = symbol_decoder.decode_unchecked(bit_reader);
+        bit_reader.check_last_error()?;
+        match s {
+            symbol::DecodedSymbol::Literal(b) => {
+                if size == output.len() {
+                    return Err(SliceError::BufferTooSmall);
+                }
+                output[size] = b;
+                size += 1;
+            }
+            symbol::DecodedSymbol::LiteralPair(b0, b1) => {
+                if output.len() - size < 2 {
+                    return Err(SliceError::BufferTooSmall);
+                }
+                output[size] = b0;
+                output[size + 1] = b1;
+                size += 2;
+            }
+            symbol::DecodedSymbol::Share { length, distance } => {
+                if size < distance as usize {
+                    return Err(SliceError::Io(invalid_data_error!(
+                        "Too long backword reference: buffer.len={}, distance={}",
+                        size,
+                        distance
+                    )));
+                }
+                if output.len() - size < length as usize {
+                    return Err(SliceError::BufferTooSmall);
+                }
+                unsafe {
+                    let start = size - distance as usize;
+                    let ptr = output.as_mut_ptr();
+                    util::ptr_copy(
+                        ptr.add(start),
+                        ptr.add(size),
+                        length as usize,
+                        length > distance,
+                    );
+                }
+                size += length as usize;
+            }
+            symbol::DecodedSymbol::EndOfBlock => {
+                return Ok(size);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use lz77::{BinaryTreeLz77Encoder, DefaultLz77Encoder, OptimalLz77Encoder};
+
+    /// Returns pseudo-random bytes which are hardly compressible.
+    fn random_bytes(len: usize) -> Vec<u8> {
+        let mut x: u32 = 123_456_789;
+        (0..len)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                x as u8
+            })
+            .collect()
+    }
+
+    fn check_max_compressed_len<E>(options: EncodeOptions<E>)
+    where
+        E: lz77::Lz77Encode + Clone,
+    {
+        let mut text = (0..100_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        text.extend_from_slice(&random_bytes(70_000));
+        let inputs = [
+            Vec::new(),
+            b"a".to_vec(),
+            random_bytes(100),
+            random_bytes(200_000),
+            text,
+        ];
+        for input in inputs.iter() {
+            let max_len = max_compressed_len(input.len(), &options);
+            let mut buf = vec![0; max_len];
+            let size = compress_into(input, &mut buf, options.clone()).unwrap();
+
+            let mut decoded = vec![0; input.len()];
+            assert_eq!(
+                decompress_into(&buf[..size], &mut decoded).unwrap(),
+                input.len()
+            );
+            assert_eq!(&decoded, input);
+
+            match compress_into(input, &mut buf[..size - 1], options.clone()) {
+                Err(SliceError::BufferTooSmall) => {}
+                r => panic!("Unexpected result: {:?}", r),
+            }
+        }
+    }
+
+    #[test]
+    fn compress_into_works() {
+        check_max_compressed_len(EncodeOptions::new());
+        check_max_compressed_len(EncodeOptions::new().block_size(1000));
+        check_max_compressed_len(EncodeOptions::new().fixed_huffman_codes());
+        check_max_compressed_len(EncodeOptions::new().no_compression());
+        check_max_compressed_len(EncodeOptions::new().no_compression().block_size(1));
+        check_max_compressed_len(EncodeOptions::with_lz77(
+            DefaultLz77Encoder::with_window_size(1024),
+        ));
+        check_max_compressed_len(EncodeOptions::with_lz77(BinaryTreeLz77Encoder::new()));
+        check_max_compressed_len(EncodeOptions::with_lz77(
+            OptimalLz77Encoder::with_iterations(1),
+        ));
+    }
+
+    #[test]
+    fn decompress_into_works() {
+        let plain = (0..100_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let options = [
+            EncodeOptions::new().block_size(4096),
+            EncodeOptions::new().fixed_huffman_codes(),
+            EncodeOptions::new().no_compression(),
+        ];
+        for options in options.iter() {
+            let mut encoder = Encoder::with_options(Vec::new(), options.clone());
+            encoder.write_all(&plain).unwrap();
+            let encoded = encoder.finish().into_result().unwrap();
+
+            let mut buf = vec![0; plain.len() + 10];
+            let size = decompress_into(&encoded, &mut buf).unwrap();
+            assert_eq!(&buf[..size], &plain[..]);
+
+            let mut buf = vec![0; plain.len() - 1];
+            match decompress_into(&encoded, &mut buf) {
+                Err(SliceError::BufferTooSmall) => {}
+                r => panic!("Unexpected result: {:?}", r),
+            }
+
+            let mut buf = vec![0; plain.len()];
+            assert!(decompress_into(&encoded[..encoded.len() - 1], &mut buf).is_err());
+        }
+    }
+}
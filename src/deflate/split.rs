@@ -0,0 +1,97 @@
+use alloc::vec::Vec;
+
+use super::symbol::{DynamicHuffmanCodec, Histogram, Symbol};
+use io;
+
+/// The number of symbols in a unit of splitting.
+pub const CHUNK_SIZE: usize = 1024;
+
+/// Returns the end positions of the dynamic Huffman blocks into which `symbols` should be split.
+///
+/// The boundaries are searched for recursively:
+/// the symbols are divided where the statistics differ the most,
+/// and the division is adopted only if a fresh Huffman table makes the blocks smaller in total.
+///
+/// The last element of the result is always `symbols.len()`.
+pub fn split(symbols: &[Symbol]) -> io::Result<Vec<usize>> {
+    let chunks = symbols
+        .chunks(CHUNK_SIZE)
+        .map(Histogram::from_symbols)
+        .collect::<Vec<_>>();
+    let mut whole = Histogram::new();
+    for c in &chunks {
+        whole.merge(c);
+    }
+    let whole_bits = block_bits(&whole)?;
+
+    let mut ends = Vec::new();
+    split_chunks(&chunks, &whole, whole_bits, 0, &mut ends)?;
+    for end in &mut ends {
+        *end *= CHUNK_SIZE;
+    }
+    ends.push(symbols.len());
+    Ok(ends)
+}
+
+fn split_chunks(
+    chunks: &[Histogram],
+    whole: &Histogram,
+    whole_bits: usize,
+    offset: usize,
+    ends: &mut Vec<usize>,
+) -> io::Result<()> {
+    if chunks.len() < 2 {
+        return Ok(());
+    }
+
+    let mut left = Histogram::new();
+    let mut best = None;
+    for (i, c) in chunks[..chunks.len() - 1].iter().enumerate() {
+        left.merge(c);
+        let mut right = whole.clone();
+        right.subtract(&left);
+        let bits = left.entropy_bits() + right.entropy_bits();
+        if best.map(|(b, _)| bits < b).unwrap_or(true) {
+            best = Some((bits, i + 1));
+        }
+    }
+    let middle = best.expect("Never fails").1;
+
+    let mut left = Histogram::new();
+    for c in &chunks[..middle] {
+        left.merge(c);
+    }
+    let mut right = whole.clone();
+    right.subtract(&left);
+    let left_bits = block_bits(&left)?;
+    let right_bits = block_bits(&right)?;
+    if left_bits + right_bits + 3 < whole_bits {
+        split_chunks(&chunks[..middle], &left, left_bits, offset, ends)?;
+        ends.push(offset + middle);
+        split_chunks(&chunks[middle..], &right, right_bits, offset + middle, ends)?;
+    }
+    Ok(())
+}
+
+fn block_bits(histogram: &Histogram) -> io::Result<usize> {
+    let mut histogram = histogram.clone();
+    histogram.add(&Symbol::EndOfBlock);
+    DynamicHuffmanCodec.block_bits(&histogram)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_works() {
+        let mut symbols = Vec::new();
+        assert_eq!(split(&symbols).unwrap(), [0]);
+
+        symbols.extend((0..10_000).map(|i| Symbol::Literal(b'a' + (i % 4) as u8)));
+        assert_eq!(split(&symbols).unwrap(), [10_000]);
+
+        symbols.extend((0..10_000).map(|i| Symbol::Literal(b'A' + (i % 16) as u8)));
+        assert_eq!(split(&symbols).unwrap(), [10_240, 20_000]);
+    }
+}
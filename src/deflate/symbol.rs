@@ -1,11 +1,17 @@
-use std::cmp;
-use std::io;
-use std::iter;
-use std::ops::Range;
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cmp;
+use core::iter;
+use core::ops::Range;
 
 use bit;
 use huffman;
 use huffman::Builder;
+use io;
+use util;
+#[cfg(target_has_atomic = "ptr")]
+use util::Lazy;
 
 const FIXED_LITERAL_OR_LENGTH_CODE_TABLE: [(u8, Range<u16>, u16); 4] = [
     (8, 000..144, 0b0_0011_0000),
@@ -85,6 +91,18 @@ const DISTANCE_TABLE: [(u16, u8); 30] = [
     (24_577, 13),
 ];
 
+// The kinds of the entries of the decoding tables
+const LITERAL_ENTRY: u8 = 0;
+const LITERAL_PAIR_ENTRY: u8 = 1;
+const LENGTH_ENTRY: u8 = 2;
+const DISTANCE_ENTRY: u8 = 3;
+const END_OF_BLOCK_ENTRY: u8 = 4;
+const UNUSED_ENTRY: u8 = 5;
+
+const LITERAL_TABLE_BITS: u8 = 11;
+const DISTANCE_TABLE_BITS: u8 = 8;
+const BITWIDTH_TABLE_BITS: u8 = 7;
+
 #[derive(Debug)]
 pub enum Symbol {
     EndOfBlock,
@@ -150,7 +168,79 @@ impl Symbol {
     }
 }
 
-#[derive(Debug)]
+/// Frequencies of the literal/length codes and the distance codes of symbols.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    pub literals: [usize; 286],
+    pub distances: [usize; 30],
+
+    // The total number of the extra bits of the lengths and the distances.
+    pub extra_bits: usize,
+}
+impl Histogram {
+    pub fn new() -> Self {
+        Histogram {
+            literals: [0; 286],
+            distances: [0; 30],
+            extra_bits: 0,
+        }
+    }
+    pub fn from_symbols(symbols: &[Symbol]) -> Self {
+        let mut histogram = Self::new();
+        for s in symbols {
+            histogram.add(s);
+        }
+        histogram
+    }
+    pub fn add(&mut self, symbol: &Symbol) {
+        self.literals[symbol.code() as usize] += 1;
+        if let Some((bits, _)) = symbol.extra_lengh() {
+            self.extra_bits += bits as usize;
+        }
+        if let Some((d, bits, _)) = symbol.distance() {
+            self.distances[d as usize] += 1;
+            self.extra_bits += bits as usize;
+        }
+    }
+    pub fn merge(&mut self, other: &Self) {
+        for (x, y) in self.literals.iter_mut().zip(other.literals.iter()) {
+            *x += *y;
+        }
+        for (x, y) in self.distances.iter_mut().zip(other.distances.iter()) {
+            *x += *y;
+        }
+        self.extra_bits += other.extra_bits;
+    }
+    pub fn subtract(&mut self, other: &Self) {
+        for (x, y) in self.literals.iter_mut().zip(other.literals.iter()) {
+            *x -= *y;
+        }
+        for (x, y) in self.distances.iter_mut().zip(other.distances.iter()) {
+            *x -= *y;
+        }
+        self.extra_bits -= other.extra_bits;
+    }
+
+    /// Returns the size in bits of the symbols encoded with ideal (entropy) codes.
+    pub fn entropy_bits(&self) -> f64 {
+        entropy_bits(&self.literals) + entropy_bits(&self.distances) + self.extra_bits as f64
+    }
+}
+
+fn entropy_bits(counts: &[usize]) -> f64 {
+    let total = counts.iter().sum::<usize>();
+    if total == 0 {
+        return 0.0;
+    }
+    let log_total = util::log2(total as f64);
+    counts
+        .iter()
+        .filter(|&&n| n > 0)
+        .map(|&n| n as f64 * (log_total - util::log2(n as f64)))
+        .sum()
+}
+
+#[derive(Debug, Clone)]
 pub struct Encoder {
     literal: huffman::Encoder,
     distance: huffman::Encoder,
@@ -174,98 +264,190 @@ impl Encoder {
     }
 }
 
+/// A symbol decoded by `Decoder`.
+///
+/// Unlike `Symbol`, two successive literals may be decoded at once.
 #[derive(Debug)]
+pub enum DecodedSymbol {
+    EndOfBlock,
+    Literal(u8),
+    LiteralPair(u8, u8),
+    Share { length: u16, distance: u16 },
+}
+
+#[derive(Debug, Clone)]
 pub struct Decoder {
     literal: huffman::Decoder,
     distance: huffman::Decoder,
 }
 impl Decoder {
-    #[inline(always)]
-    pub fn decode_unchecked<R>(&self, reader: &mut bit::BitReader<R>) -> Symbol
-    where
-        R: io::Read,
-    {
-        let mut symbol = self.decode_literal_or_length(reader);
-        if let Symbol::Share {
-            ref mut distance, ..
-        } = symbol
-        {
-            *distance = self.decode_distance(reader);
-        }
-        symbol
+    fn new(mut literal: huffman::Decoder, distance: huffman::Decoder) -> Self {
+        literal.combine(combine_literals);
+        Decoder { literal, distance }
     }
+
     #[inline(always)]
-    fn decode_literal_or_length<R>(&self, reader: &mut bit::BitReader<R>) -> Symbol
+    pub fn decode_unchecked<R>(&self, reader: &mut bit::BitReader<R>) -> DecodedSymbol
     where
-        R: io::Read,
+        R: io::BufRead,
     {
-        let decoded = self.literal.decode_unchecked(reader);
-        match decoded {
-            0...255 => Symbol::Literal(decoded as u8),
-            256 => Symbol::EndOfBlock,
-            286 | 287 => {
-                let message = format!("The value {} must not occur in compressed data", decoded);
-                reader.set_last_error(io::Error::new(io::ErrorKind::InvalidData, message));
-                Symbol::EndOfBlock // dummy value
+        let entry = self.literal.peek_unchecked(reader, split_literal_pair);
+        match entry.kind() {
+            LITERAL_ENTRY => {
+                reader.skip_bits(entry.width());
+                DecodedSymbol::Literal(entry.value() as u8)
             }
-            length_code => {
-                let (base, extra_bits) =
-                    unsafe { *LENGTH_TABLE.get_unchecked(length_code as usize - 257) };
-                let extra = reader.read_bits_unchecked(extra_bits);
-                Symbol::Share {
-                    length: base + extra,
-                    distance: 0,
+            LITERAL_PAIR_ENTRY => {
+                reader.skip_bits(entry.width());
+                DecodedSymbol::LiteralPair(entry.value() as u8, (entry.value() >> 8) as u8)
+            }
+            LENGTH_ENTRY => {
+                let length = read_base_and_extra_bits(reader, entry);
+                let entry = self.distance.peek_unchecked(reader, |_, _| None);
+                if entry.kind() != DISTANCE_ENTRY {
+                    reader.set_last_error(unexpected_entry_error(entry));
                 }
+                let distance = read_base_and_extra_bits(reader, entry);
+                DecodedSymbol::Share { length, distance }
+            }
+            END_OF_BLOCK_ENTRY => {
+                reader.skip_bits(entry.width());
+                DecodedSymbol::EndOfBlock
+            }
+            _ => {
+                reader.set_last_error(unexpected_entry_error(entry));
+                DecodedSymbol::EndOfBlock // dummy value
             }
         }
     }
-    #[inline(always)]
-    fn decode_distance<R>(&self, reader: &mut bit::BitReader<R>) -> u16
-    where
-        R: io::Read,
-    {
-        let decoded = self.distance.decode_unchecked(reader) as usize;
-        let (base, extra_bits) = unsafe { *DISTANCE_TABLE.get_unchecked(decoded) };
-        let extra = reader.read_bits_unchecked(extra_bits);
+}
+
+/// Consumes the code of `entry` and its extra bits, and returns the sum of the base value and the extra bits.
+#[inline(always)]
+fn read_base_and_extra_bits<R>(reader: &mut bit::BitReader<R>, entry: huffman::Entry) -> u16
+where
+    R: io::BufRead,
+{
+    let base = entry.value() as u16;
+    let extra_bits = (entry.value() >> 16) as u8;
+    let width = entry.width();
+    if width + extra_bits <= reader.buffered_bitwidth() {
+        let extra = (reader.buffered_bits() >> width) as u16 & ((1 << extra_bits) - 1);
+        reader.skip_bits(width + extra_bits);
         base + extra
+    } else {
+        reader.skip_bits(width);
+        base + reader.read_bits_unchecked(extra_bits)
+    }
+}
+
+fn unexpected_entry_error(entry: huffman::Entry) -> io::Error {
+    if entry.kind() == UNUSED_ENTRY {
+        invalid_data_error!(
+            "The value {} must not occur in compressed data",
+            entry.value()
+        )
+    } else {
+        invalid_data_error!("Invalid huffman coded stream")
+    }
+}
+
+fn literal_or_length_entry(symbol: u16) -> huffman::Entry {
+    match symbol {
+        END_OF_BLOCK => huffman::Entry::new(0, END_OF_BLOCK_ENTRY, 0),
+        _ if symbol < END_OF_BLOCK => huffman::Entry::new(0, LITERAL_ENTRY, u32::from(symbol)),
+        _ => match LENGTH_TABLE.get(symbol as usize - 257) {
+            Some(&(base, extra_bits)) => {
+                let value = (u32::from(extra_bits) << 16) | u32::from(base);
+                huffman::Entry::new(0, LENGTH_ENTRY, value)
+            }
+            None => huffman::Entry::new(0, UNUSED_ENTRY, u32::from(symbol)),
+        },
+    }
+}
+
+fn distance_entry(symbol: u16) -> huffman::Entry {
+    match DISTANCE_TABLE.get(symbol as usize) {
+        Some(&(base, extra_bits)) => {
+            let value = (u32::from(extra_bits) << 16) | u32::from(base);
+            huffman::Entry::new(0, DISTANCE_ENTRY, value)
+        }
+        None => huffman::Entry::new(0, UNUSED_ENTRY, u32::from(symbol)),
+    }
+}
+
+/// Makes the entry of two literals (the bitwidth of the first one is kept in the upper bits of the value).
+fn combine_literals(first: huffman::Entry, second: huffman::Entry) -> Option<huffman::Entry> {
+    if first.kind() == LITERAL_ENTRY && second.kind() == LITERAL_ENTRY {
+        let value = (u32::from(first.width()) << 16) | (second.value() << 8) | first.value();
+        Some(huffman::Entry::new(
+            first.width() + second.width(),
+            LITERAL_PAIR_ENTRY,
+            value,
+        ))
+    } else {
+        None
+    }
+}
+
+/// Returns the entry of the first literal of a pair, if the loaded `nbits` bits are too few for the second one.
+fn split_literal_pair(entry: huffman::Entry, nbits: u8) -> Option<huffman::Entry> {
+    let first_width = (entry.value() >> 16) as u8;
+    if entry.kind() == LITERAL_PAIR_ENTRY && first_width <= nbits {
+        let literal = entry.value() & 0xFF;
+        Some(huffman::Entry::new(first_width, LITERAL_ENTRY, literal))
+    } else {
+        None
     }
 }
 
 pub trait HuffmanCodec {
-    fn build(&self, symbols: &[Symbol]) -> io::Result<Encoder>;
+    fn build(&self, histogram: &Histogram) -> io::Result<Cow<'static, Encoder>>;
     fn save<W>(&self, writer: &mut bit::BitWriter<W>, codec: &Encoder) -> io::Result<()>
     where
         W: io::Write;
-    fn load<R>(&self, reader: &mut bit::BitReader<R>) -> io::Result<Decoder>
+    fn load<R>(&self, reader: &mut bit::BitReader<R>) -> io::Result<Cow<'static, Decoder>>
     where
-        R: io::Read;
+        R: io::BufRead;
+}
+
+/// The symbol encoder of the fixed Huffman codes, which is built once and shared by all encoders.
+#[cfg(target_has_atomic = "ptr")]
+static FIXED_ENCODER: Lazy<Encoder> = Lazy::new(build_fixed_encoder);
+
+/// The symbol decoder of the fixed Huffman codes, which is built once and shared by all decoders.
+#[cfg(target_has_atomic = "ptr")]
+static FIXED_DECODER: Lazy<Decoder> = Lazy::new(build_fixed_decoder);
+
+#[cfg(target_has_atomic = "ptr")]
+fn fixed_encoder() -> Cow<'static, Encoder> {
+    Cow::Borrowed(FIXED_ENCODER.get())
+}
+
+/// The targets without the atomic operations on pointers can not share the tables,
+/// so they are built for each block.
+#[cfg(not(target_has_atomic = "ptr"))]
+fn fixed_encoder() -> Cow<'static, Encoder> {
+    Cow::Owned(build_fixed_encoder())
+}
+
+#[cfg(target_has_atomic = "ptr")]
+fn fixed_decoder() -> Cow<'static, Decoder> {
+    Cow::Borrowed(FIXED_DECODER.get())
+}
+
+/// See `fixed_encoder`.
+#[cfg(not(target_has_atomic = "ptr"))]
+fn fixed_decoder() -> Cow<'static, Decoder> {
+    Cow::Owned(build_fixed_decoder())
 }
 
 #[derive(Debug)]
 pub struct FixedHuffmanCodec;
 impl HuffmanCodec for FixedHuffmanCodec {
     #[allow(unused_variables)]
-    fn build(&self, symbols: &[Symbol]) -> io::Result<Encoder> {
-        let mut literal_builder = huffman::EncoderBuilder::new(288);
-        for &(bitwidth, ref symbols, code_base) in &FIXED_LITERAL_OR_LENGTH_CODE_TABLE {
-            for (code, symbol) in symbols
-                .clone()
-                .enumerate()
-                .map(|(i, s)| (code_base + i as u16, s))
-            {
-                literal_builder.set_mapping(symbol, huffman::Code::new(bitwidth, code))?;
-            }
-        }
-
-        let mut distance_builder = huffman::EncoderBuilder::new(30);
-        for i in 0..30 {
-            distance_builder.set_mapping(i, huffman::Code::new(5, i))?;
-        }
-
-        Ok(Encoder {
-            literal: literal_builder.finish(),
-            distance: distance_builder.finish(),
-        })
+    fn build(&self, histogram: &Histogram) -> io::Result<Cow<'static, Encoder>> {
+        Ok(fixed_encoder())
     }
     #[allow(unused_variables)]
     fn save<W>(&self, writer: &mut bit::BitWriter<W>, codec: &Encoder) -> io::Result<()>
@@ -275,97 +457,125 @@ impl HuffmanCodec for FixedHuffmanCodec {
         Ok(())
     }
     #[allow(unused_variables)]
-    fn load<R>(&self, reader: &mut bit::BitReader<R>) -> io::Result<Decoder>
+    fn load<R>(&self, reader: &mut bit::BitReader<R>) -> io::Result<Cow<'static, Decoder>>
     where
-        R: io::Read,
+        R: io::BufRead,
     {
-        let mut literal_builder = huffman::DecoderBuilder::new(9, Some(END_OF_BLOCK));
-        for &(bitwidth, ref symbols, code_base) in &FIXED_LITERAL_OR_LENGTH_CODE_TABLE {
-            for (code, symbol) in symbols
-                .clone()
-                .enumerate()
-                .map(|(i, s)| (code_base + i as u16, s))
-            {
-                literal_builder.set_mapping(symbol, huffman::Code::new(bitwidth, code))?;
-            }
-        }
+        Ok(fixed_decoder())
+    }
+}
 
-        let mut distance_builder = huffman::DecoderBuilder::new(5, None);
-        for i in 0..30 {
-            distance_builder.set_mapping(i, huffman::Code::new(5, i))?;
-        }
+fn build_fixed_encoder() -> Encoder {
+    let mut literal_builder = huffman::EncoderBuilder::new(288);
+    for (symbol, code) in fixed_literal_codes() {
+        literal_builder
+            .set_mapping(symbol, code)
+            .expect("The fixed Huffman codes never conflict");
+    }
+
+    let mut distance_builder = huffman::EncoderBuilder::new(30);
+    for i in 0..30 {
+        distance_builder
+            .set_mapping(i, huffman::Code::new(5, i))
+            .expect("The fixed Huffman codes never conflict");
+    }
 
-        Ok(Decoder {
-            literal: literal_builder.finish(),
-            distance: distance_builder.finish(),
+    Encoder {
+        literal: literal_builder.finish(),
+        distance: distance_builder.finish(),
+    }
+}
+
+fn build_fixed_decoder() -> Decoder {
+    let mut literal_builder = huffman::DecoderBuilder::new(
+        9,
+        Some(END_OF_BLOCK),
+        LITERAL_TABLE_BITS,
+        literal_or_length_entry,
+    );
+    for (symbol, code) in fixed_literal_codes() {
+        literal_builder
+            .set_mapping(symbol, code)
+            .expect("The fixed Huffman codes never conflict");
+    }
+
+    let mut distance_builder =
+        huffman::DecoderBuilder::new(5, None, DISTANCE_TABLE_BITS, distance_entry);
+    for i in 0..30 {
+        distance_builder
+            .set_mapping(i, huffman::Code::new(5, i))
+            .expect("The fixed Huffman codes never conflict");
+    }
+
+    Decoder::new(literal_builder.finish(), distance_builder.finish())
+}
+
+/// Returns the pairs of the literal/length symbols and their fixed Huffman codes.
+fn fixed_literal_codes() -> impl Iterator<Item = (u16, huffman::Code)> {
+    FIXED_LITERAL_OR_LENGTH_CODE_TABLE
+        .iter()
+        .flat_map(|&(bitwidth, ref symbols, code_base)| {
+            symbols
+                .clone()
+                .enumerate()
+                .map(move |(i, s)| (s, huffman::Code::new(bitwidth, code_base + i as u16)))
         })
+}
+
+impl FixedHuffmanCodec {
+    /// Returns the size in bits of a block which consists of the symbols counted in `histogram`.
+    ///
+    /// The size excludes the three bits of the block header.
+    pub fn block_bits(&self, histogram: &Histogram) -> usize {
+        let literal_bits = FIXED_LITERAL_OR_LENGTH_CODE_TABLE
+            .iter()
+            .map(|&(bitwidth, ref symbols, _)| {
+                let end = cmp::min(symbols.end as usize, histogram.literals.len());
+                let count = histogram.literals[symbols.start as usize..end]
+                    .iter()
+                    .sum::<usize>();
+                count * bitwidth as usize
+            })
+            .sum::<usize>();
+        let distance_bits = histogram.distances.iter().sum::<usize>() * 5;
+        literal_bits + distance_bits + histogram.extra_bits
     }
 }
 
 #[derive(Debug)]
 pub struct DynamicHuffmanCodec;
 impl HuffmanCodec for DynamicHuffmanCodec {
-    fn build(&self, symbols: &[Symbol]) -> io::Result<Encoder> {
-        let mut literal_counts = [0; 286];
-        let mut distance_counts = [0; 30];
-        for s in symbols {
-            literal_counts[s.code() as usize] += 1;
-            if let Some((d, _, _)) = s.distance() {
-                distance_counts[d as usize] += 1;
-            }
-        }
-        Ok(Encoder {
-            literal: huffman::EncoderBuilder::from_frequencies(&literal_counts, 15)?,
-            distance: huffman::EncoderBuilder::from_frequencies(&distance_counts, 15)?,
-        })
+    fn build(&self, histogram: &Histogram) -> io::Result<Cow<'static, Encoder>> {
+        Ok(Cow::Owned(Encoder {
+            literal: huffman::EncoderBuilder::from_frequencies(&histogram.literals, 15)?,
+            distance: huffman::EncoderBuilder::from_frequencies(&histogram.distances, 15)?,
+        }))
     }
     fn save<W>(&self, writer: &mut bit::BitWriter<W>, codec: &Encoder) -> io::Result<()>
     where
         W: io::Write,
     {
-        let literal_code_count = cmp::max(257, codec.literal.used_max_symbol().unwrap_or(0) + 1);
-        let distance_code_count = cmp::max(1, codec.distance.used_max_symbol().unwrap_or(0) + 1);
-        let codes = build_bitwidth_codes(codec, literal_code_count, distance_code_count);
-
-        let mut code_counts = [0; 19];
-        for x in &codes {
-            code_counts[x.0 as usize] += 1;
-        }
-        let bitwidth_encoder = huffman::EncoderBuilder::from_frequencies(&code_counts, 7)?;
-
-        let bitwidth_code_count = cmp::max(
-            4,
-            BITWIDTH_CODE_ORDER
-                .iter()
-                .rev()
-                .position(|&i| bitwidth_encoder.lookup(i as u16).width > 0)
-                .map_or(0, |trailing_zeros| 19 - trailing_zeros),
-        ) as u16;
-        writer.write_bits(5, literal_code_count - 257)?;
-        writer.write_bits(5, distance_code_count - 1)?;
-        writer.write_bits(4, bitwidth_code_count - 4)?;
+        let header = DynamicHeader::new(codec)?;
+        writer.write_bits(5, header.literal_code_count - 257)?;
+        writer.write_bits(5, header.distance_code_count - 1)?;
+        writer.write_bits(4, header.bitwidth_code_count - 4)?;
         for &i in BITWIDTH_CODE_ORDER
             .iter()
-            .take(bitwidth_code_count as usize)
+            .take(header.bitwidth_code_count as usize)
         {
-            let width = if code_counts[i] == 0 {
-                0
-            } else {
-                u16::from(bitwidth_encoder.lookup(i as u16).width)
-            };
-            writer.write_bits(3, width)?;
+            writer.write_bits(3, u16::from(header.bitwidth(i)))?;
         }
-        for &(code, bits, extra) in &codes {
-            bitwidth_encoder.encode(writer, u16::from(code))?;
+        for &(code, bits, extra) in &header.codes {
+            header.bitwidth_encoder.encode(writer, u16::from(code))?;
             if bits > 0 {
                 writer.write_bits(bits, u16::from(extra))?;
             }
         }
         Ok(())
     }
-    fn load<R>(&self, reader: &mut bit::BitReader<R>) -> io::Result<Decoder>
+    fn load<R>(&self, reader: &mut bit::BitReader<R>) -> io::Result<Cow<'static, Decoder>>
     where
-        R: io::Read,
+        R: io::BufRead,
     {
         let literal_code_count = reader.read_bits(5)? + 257;
         let distance_code_count = reader.read_bits(5)? + 1;
@@ -378,8 +588,12 @@ impl HuffmanCodec for DynamicHuffmanCodec {
         {
             bitwidth_code_bitwidthes[i] = reader.read_bits(3)? as u8;
         }
-        let bitwidth_decoder =
-            huffman::DecoderBuilder::from_bitwidthes(&bitwidth_code_bitwidthes, None)?;
+        let bitwidth_decoder = huffman::DecoderBuilder::from_bitwidthes(
+            &bitwidth_code_bitwidthes,
+            None,
+            BITWIDTH_TABLE_BITS,
+            huffman::symbol_entry,
+        )?;
 
         let mut literal_code_bitwidthes = Vec::with_capacity(literal_code_count as usize);
         while literal_code_bitwidthes.len() < literal_code_count as usize {
@@ -408,14 +622,101 @@ impl HuffmanCodec for DynamicHuffmanCodec {
             return Err(io::Error::new(io::ErrorKind::InvalidData, message));
         }
 
-        Ok(Decoder {
-            literal: huffman::DecoderBuilder::from_bitwidthes(
+        Ok(Cow::Owned(Decoder::new(
+            huffman::DecoderBuilder::from_bitwidthes(
                 &literal_code_bitwidthes,
                 Some(END_OF_BLOCK),
+                LITERAL_TABLE_BITS,
+                literal_or_length_entry,
             )?,
-            distance: huffman::DecoderBuilder::from_bitwidthes(&distance_code_bitwidthes, None)?,
+            huffman::DecoderBuilder::from_bitwidthes(
+                &distance_code_bitwidthes,
+                None,
+                DISTANCE_TABLE_BITS,
+                distance_entry,
+            )?,
+        )))
+    }
+}
+
+impl DynamicHuffmanCodec {
+    /// Returns the size in bits of a block which consists of the symbols counted in `histogram`.
+    ///
+    /// The size includes the code tables, but excludes the three bits of the block header.
+    pub fn block_bits(&self, histogram: &Histogram) -> io::Result<usize> {
+        let codec = self.build(histogram)?;
+        let header = DynamicHeader::new(&codec)?;
+        let literal_bits = histogram
+            .literals
+            .iter()
+            .enumerate()
+            .filter(|&(_, &n)| n > 0)
+            .map(|(i, &n)| n * codec.literal.lookup(i as u16).width as usize)
+            .sum::<usize>();
+        let distance_bits = histogram
+            .distances
+            .iter()
+            .enumerate()
+            .filter(|&(_, &n)| n > 0)
+            .map(|(i, &n)| n * codec.distance.lookup(i as u16).width as usize)
+            .sum::<usize>();
+        Ok(header.bits() + literal_bits + distance_bits + histogram.extra_bits)
+    }
+}
+
+/// The code tables of a dynamic Huffman block.
+struct DynamicHeader {
+    literal_code_count: u16,
+    distance_code_count: u16,
+    bitwidth_code_count: u16,
+    codes: Vec<(u8, u8, u8)>,
+    code_counts: [usize; 19],
+    bitwidth_encoder: huffman::Encoder,
+}
+impl DynamicHeader {
+    fn new(codec: &Encoder) -> io::Result<Self> {
+        let literal_code_count = cmp::max(257, codec.literal.used_max_symbol().unwrap_or(0) + 1);
+        let distance_code_count = cmp::max(1, codec.distance.used_max_symbol().unwrap_or(0) + 1);
+        let codes = build_bitwidth_codes(codec, literal_code_count, distance_code_count);
+
+        let mut code_counts = [0; 19];
+        for x in &codes {
+            code_counts[x.0 as usize] += 1;
+        }
+        let bitwidth_encoder = huffman::EncoderBuilder::from_frequencies(&code_counts, 7)?;
+
+        let bitwidth_code_count = cmp::max(
+            4,
+            BITWIDTH_CODE_ORDER
+                .iter()
+                .rev()
+                .position(|&i| bitwidth_encoder.lookup(i as u16).width > 0)
+                .map_or(0, |trailing_zeros| 19 - trailing_zeros),
+        ) as u16;
+        Ok(DynamicHeader {
+            literal_code_count,
+            distance_code_count,
+            bitwidth_code_count,
+            codes,
+            code_counts,
+            bitwidth_encoder,
         })
     }
+    fn bitwidth(&self, code: usize) -> u8 {
+        if self.code_counts[code] == 0 {
+            0
+        } else {
+            self.bitwidth_encoder.lookup(code as u16).width
+        }
+    }
+    fn bits(&self) -> usize {
+        let codes = self
+            .codes
+            .iter()
+            .map(|&(code, bits, _)| (self.bitwidth(code as usize) + bits) as usize)
+            .sum::<usize>();
+        5 + 5 + 4 + 3 * self.bitwidth_code_count as usize + codes
+    }
 }
 
 fn load_bitwidthes<R>(
@@ -424,7 +725,7 @@ fn load_bitwidthes<R>(
     last: Option<u8>,
 ) -> io::Result<Box<Iterator<Item = u8>>>
 where
-    R: io::Read,
+    R: io::BufRead,
 {
     Ok(match code {
         0...15 => Box::new(iter::once(code as u8)),
@@ -0,0 +1,198 @@
+//! The DEFLATE decoder which receives the encoded data through `io::Write`.
+//!
+//! # Examples
+//! ```
+//! use std::io::Write;
+//! use libflate::deflate::write::Decoder;
+//!
+//! let encoded_data = [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0];
+//!
+//! let mut decoder = Decoder::new(Vec::new());
+//! for chunk in encoded_data.chunks(3) {
+//!     decoder.write_all(chunk).unwrap();
+//! }
+//! let (decoded_data, _) = decoder.finish().into_result().unwrap();
+//!
+//! assert_eq!(decoded_data, b"Hello World!");
+//! ```
+use alloc::vec::Vec;
+
+use adapter::{InputBuffer, WriteDecoder};
+use finish::{Complete, Finish};
+use io::{self, Write};
+use non_blocking;
+
+/// DEFLATE decoder which implements `io::Write`.
+///
+/// The encoded data is given through `write`, and the decoded data is written to the inner writer.
+/// This is useful when the encoded data arrives in chunks (e.g., from callbacks of a network library).
+///
+/// The bytes written after the end of the DEFLATE stream are not decoded,
+/// and they are returned by `finish` as the trailing bytes.
+#[derive(Debug)]
+pub struct Decoder<W> {
+    inner: WriteDecoder<non_blocking::deflate::Decoder<InputBuffer>, W>,
+}
+impl<W> Decoder<W>
+where
+    W: Write,
+{
+    /// Makes a new decoder instance.
+    ///
+    /// The decoded data is written to `inner`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::write::Decoder;
+    ///
+    /// let encoded_data = [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0];
+    ///
+    /// let mut decoder = Decoder::new(Vec::new());
+    /// decoder.write_all(&encoded_data).unwrap();
+    ///
+    /// assert_eq!(decoder.as_inner_ref(), b"Hello World!");
+    /// ```
+    pub fn new(inner: W) -> Self {
+        let decoder = non_blocking::deflate::Decoder::new(InputBuffer::new());
+        Decoder {
+            inner: WriteDecoder::new(decoder, inner),
+        }
+    }
+
+    /// Sets the preset dictionary.
+    ///
+    /// See `deflate::Decoder::set_dictionary` for more details.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::Encoder;
+    /// use libflate::deflate::write::Decoder;
+    ///
+    /// let mut encoder = Encoder::new(Vec::new());
+    /// encoder.set_dictionary(b"Hello").unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    ///
+    /// let mut decoder = Decoder::new(Vec::new());
+    /// decoder.set_dictionary(b"Hello");
+    /// decoder.write_all(&encoded_data).unwrap();
+    /// assert_eq!(decoder.as_inner_ref(), b"Hello World!");
+    /// ```
+    pub fn set_dictionary(&mut self, dictionary: &[u8]) {
+        self.inner.decoder_mut().set_dictionary(dictionary);
+    }
+
+    /// Returns `true` if the end of the DEFLATE stream has been reached.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::write::Decoder;
+    ///
+    /// let encoded_data = [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0];
+    ///
+    /// let mut decoder = Decoder::new(Vec::new());
+    /// decoder.write_all(&encoded_data[..10]).unwrap();
+    /// assert!(!decoder.is_finished());
+    /// decoder.write_all(&encoded_data[10..]).unwrap();
+    /// assert!(decoder.is_finished());
+    /// ```
+    pub fn is_finished(&self) -> bool {
+        self.inner.is_eos()
+    }
+
+    /// Decodes the remaining data, and returns the inner writer and the trailing bytes.
+    ///
+    /// The trailing bytes are the ones written after the end of the DEFLATE stream.
+    /// If the stream is truncated, an `UnexpectedEof` error is returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::write::Decoder;
+    ///
+    /// let encoded_data = [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0];
+    ///
+    /// let mut decoder = Decoder::new(Vec::new());
+    /// decoder.write_all(&encoded_data).unwrap();
+    /// decoder.write_all(b"trailer").unwrap();
+    /// let (decoded_data, trailing) = decoder.finish().into_result().unwrap();
+    /// assert_eq!(decoded_data, b"Hello World!");
+    /// assert_eq!(trailing, b"trailer");
+    ///
+    /// let mut decoder = Decoder::new(Vec::new());
+    /// decoder.write_all(&encoded_data[..10]).unwrap();
+    /// assert!(decoder.finish().into_result().is_err());
+    /// ```
+    pub fn finish(self) -> Finish<(W, Vec<u8>), io::Error> {
+        let (inner, trailing, error) = self.inner.finish();
+        Finish::new((inner, trailing), error)
+    }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &W {
+        self.inner.as_inner_ref()
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut W {
+        self.inner.as_inner_mut()
+    }
+
+    /// Unwraps the `Decoder`, returning the inner stream.
+    ///
+    /// Unlike `finish`, the end of the stream is not verified.
+    pub fn into_inner(self) -> W {
+        self.inner.into_inner()
+    }
+}
+impl<W> Write for Decoder<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+impl<W> Complete for Decoder<W>
+where
+    W: Write,
+{
+    fn complete(self) -> io::Result<()> {
+        self.finish().into_result().map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::string::String;
+    use deflate::{EncodeOptions, Encoder};
+
+    #[test]
+    fn write_decode_works() {
+        let plain = (0..100_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let mut encoder = Encoder::with_options(Vec::new(), EncodeOptions::new().block_size(4096));
+        encoder.write_all(&plain).unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+
+        for &chunk_size in &[1, 7, 1000, encoded.len()] {
+            let mut decoder = Decoder::new(Vec::new());
+            for chunk in encoded.chunks(chunk_size) {
+                decoder.write_all(chunk).unwrap();
+            }
+            decoder.write_all(b"foo").unwrap();
+            let (decoded, trailing) = decoder.finish().into_result().unwrap();
+            assert_eq!(decoded, plain);
+            assert_eq!(trailing, b"foo");
+        }
+    }
+}
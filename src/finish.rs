@@ -1,6 +1,7 @@
 //! `Finish` and related types.
-use std::io::{self, Write};
-use std::ops::{Deref, DerefMut};
+use core::ops::{Deref, DerefMut};
+
+use io::{self, Write};
 
 /// `Finish` is a type that represents a value which
 /// may have an error occurred during the computation.
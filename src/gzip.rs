@@ -19,19 +19,27 @@
 //!
 //! assert_eq!(decoded_data, b"Hello World!");
 //! ```
+use alloc::ffi::CString;
+use alloc::vec::Vec;
 use byteorder::LittleEndian;
-use byteorder::ReadBytesExt;
-use byteorder::WriteBytesExt;
-use std::ffi::CString;
-use std::io;
-use std::mem;
-use std::time;
+use core::cmp;
 
 use checksum;
 use deflate;
 use finish::{Complete, Finish};
+use io;
+use io::BufRead;
+use io::ReadBytesExt;
+use io::WriteBytesExt;
 use lz77;
 
+#[cfg(feature = "futures-io")]
+pub mod futures;
+pub mod read;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+pub mod write;
+
 const GZIP_ID: [u8; 2] = [31, 139];
 const COMPRESSION_METHOD_DEFLATE: u8 = 8;
 
@@ -77,6 +85,13 @@ impl CompressionLevel {
             CompressionLevel::Unknown => 0,
         }
     }
+    fn from_level(level: u8) -> Self {
+        match level {
+            1 => CompressionLevel::Fastest,
+            9..=255 => CompressionLevel::Slowest,
+            _ => CompressionLevel::Unknown,
+        }
+    }
     fn from_u8(x: u8) -> Self {
         match x {
             4 => CompressionLevel::Fastest,
@@ -131,6 +146,9 @@ pub struct HeaderBuilder {
 impl HeaderBuilder {
     /// Makes a new builder instance.
     ///
+    /// The modification time is set to the current time
+    /// (or `0` if the `std` feature is disabled).
+    ///
     /// # Examples
     /// ```
     /// use libflate::gzip::{HeaderBuilder, CompressionLevel, Os};
@@ -145,10 +163,13 @@ impl HeaderBuilder {
     /// assert_eq!(header.comment(), None);
     /// ```
     pub fn new() -> Self {
-        let modification_time = time::UNIX_EPOCH
+        #[cfg(feature = "std")]
+        let modification_time = ::std::time::UNIX_EPOCH
             .elapsed()
             .map(|d| d.as_secs() as u32)
             .unwrap_or(0);
+        #[cfg(not(feature = "std"))]
+        let modification_time = 0;
         let header = Header {
             modification_time,
             compression_level: CompressionLevel::Unknown,
@@ -326,6 +347,19 @@ impl Header {
         self.comment.as_ref()
     }
 
+    fn encoded_len(&self) -> usize {
+        let extra_field_len = self.extra_field.as_ref().map_or(0, |x| 4 + x.data.len());
+        let filename_len = self
+            .filename
+            .as_ref()
+            .map_or(0, |x| x.as_bytes_with_nul().len());
+        let comment_len = self
+            .comment
+            .as_ref()
+            .map_or(0, |x| x.as_bytes_with_nul().len());
+        let crc16_len = if self.is_verified { 2 } else { 0 };
+        10 + extra_field_len + filename_len + comment_len + crc16_len
+    }
     fn flags(&self) -> u8 {
         [
             (F_TEXT, self.is_text),
@@ -608,6 +642,25 @@ impl EncodeOptions<lz77::DefaultLz77Encoder> {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Specifies the compression level (`0..=9`).
+    ///
+    /// The XFL field of the header is also derived from `level`.
+    /// See `deflate::EncodeOptions::level` for more details.
+    ///
+    /// # Example
+    /// ```
+    /// use libflate::gzip::{CompressionLevel, Encoder, EncodeOptions};
+    ///
+    /// let options = EncodeOptions::new().level(9);
+    /// let encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// assert_eq!(encoder.header().compression_level(), CompressionLevel::Slowest);
+    /// ```
+    pub fn level(mut self, level: u8) -> Self {
+        self.options = self.options.level(level);
+        self.header.compression_level = CompressionLevel::from_level(level);
+        self
+    }
 }
 impl<E> EncodeOptions<E>
 where
@@ -808,6 +861,42 @@ where
         }
     }
 
+    /// Performs a sync flush (equivalent to `Z_SYNC_FLUSH` of zlib).
+    ///
+    /// See `deflate::Encoder::sync_flush` for more details.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::gzip::Encoder;
+    ///
+    /// let mut encoder = Encoder::new(Vec::new()).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// encoder.sync_flush().unwrap();
+    /// assert!(encoder.as_inner_ref().ends_with(&[0, 0, 255, 255]));
+    /// ```
+    pub fn sync_flush(&mut self) -> io::Result<()> {
+        self.writer.sync_flush()
+    }
+
+    /// Performs a full flush (equivalent to `Z_FULL_FLUSH` of zlib).
+    ///
+    /// See `deflate::Encoder::full_flush` for more details.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::gzip::Encoder;
+    ///
+    /// let mut encoder = Encoder::new(Vec::new()).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// encoder.full_flush().unwrap();
+    /// assert!(encoder.as_inner_ref().ends_with(&[0, 0, 255, 255]));
+    /// ```
+    pub fn full_flush(&mut self) -> io::Result<()> {
+        self.writer.full_flush()
+    }
+
     /// Returns the immutable reference to the inner stream.
     pub fn as_inner_ref(&self) -> &W {
         self.writer.as_inner_ref()
@@ -848,7 +937,227 @@ where
     }
 }
 
+/// GZIP encoder which compresses data on multiple threads.
+///
+/// See `deflate::ParallelEncoder` for more details.
+///
+/// # Examples
+/// ```
+/// use std::io::{Read, Write};
+/// use libflate::gzip::{Decoder, ParallelEncoder};
+///
+/// let plain = (0..100_000).map(|i| format!("{} ", i)).collect::<String>();
+///
+/// let mut encoder = ParallelEncoder::new(Vec::new()).unwrap();
+/// encoder.write_all(plain.as_bytes()).unwrap();
+/// let encoded_data = encoder.finish().into_result().unwrap();
+///
+/// let mut decoder = Decoder::new(&encoded_data[..]).unwrap();
+/// let mut decoded_data = Vec::new();
+/// decoder.read_to_end(&mut decoded_data).unwrap();
+/// assert_eq!(decoded_data, plain.as_bytes());
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct ParallelEncoder<W, E = lz77::DefaultLz77Encoder> {
+    header: Header,
+    input_size: u32,
+    writer: deflate::ParallelEncoder<W, E>,
+}
+#[cfg(feature = "std")]
+impl<W> ParallelEncoder<W, lz77::DefaultLz77Encoder>
+where
+    W: io::Write,
+{
+    /// Makes a new encoder instance.
+    ///
+    /// Encoded GZIP stream is written to `inner`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::gzip::ParallelEncoder;
+    ///
+    /// let mut encoder = ParallelEncoder::new(Vec::new()).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// encoder.finish().into_result().unwrap();
+    /// ```
+    pub fn new(inner: W) -> io::Result<Self> {
+        Self::with_options(inner, EncodeOptions::new(), deflate::ParallelOptions::new())
+    }
+}
+#[cfg(feature = "std")]
+impl<W, E> ParallelEncoder<W, E>
+where
+    W: io::Write,
+    E: lz77::Lz77Encode + Clone + Send + 'static,
+{
+    /// Makes a new encoder instance with specified options.
+    ///
+    /// Encoded GZIP stream is written to `inner`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::ParallelOptions;
+    /// use libflate::gzip::{EncodeOptions, HeaderBuilder, ParallelEncoder};
+    ///
+    /// let header = HeaderBuilder::new().modification_time(123).finish();
+    /// let options = EncodeOptions::new().header(header);
+    /// let parallel = ParallelOptions::new().threads(4);
+    /// let mut encoder = ParallelEncoder::with_options(Vec::new(), options, parallel).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// encoder.finish().into_result().unwrap();
+    /// ```
+    pub fn with_options(
+        mut inner: W,
+        options: EncodeOptions<E>,
+        parallel: deflate::ParallelOptions,
+    ) -> io::Result<Self> {
+        options.header.write_to(&mut inner)?;
+        Ok(ParallelEncoder {
+            header: options.header.clone(),
+            input_size: 0,
+            writer: deflate::ParallelEncoder::with_checksum(
+                inner,
+                options.options,
+                parallel,
+                deflate::parallel::Checksum::Crc32,
+            ),
+        })
+    }
+
+    /// Returns the header of the GZIP stream.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Writes the GZIP trailer and returns the inner stream.
+    ///
+    /// The CRC-32 checksum of the whole input is combined from the ones of the chunks.
+    pub fn finish(self) -> Finish<W, io::Error> {
+        let (finish, crc32) = self.writer.finish_with_checksum();
+        let trailer = Trailer {
+            crc32,
+            input_size: self.input_size,
+        };
+        let mut inner = finish_try!(finish);
+        match trailer.write_to(&mut inner).and_then(|_| inner.flush()) {
+            Ok(_) => Finish::new(inner, None),
+            Err(e) => Finish::new(inner, Some(e)),
+        }
+    }
+
+    /// Performs a sync flush (equivalent to `Z_SYNC_FLUSH` of zlib).
+    ///
+    /// See `deflate::ParallelEncoder::sync_flush` for more details.
+    pub fn sync_flush(&mut self) -> io::Result<()> {
+        self.writer.sync_flush()
+    }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &W {
+        self.writer.as_inner_ref()
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut W {
+        self.writer.as_inner_mut()
+    }
+
+    /// Unwraps the `ParallelEncoder`, returning the inner stream.
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner()
+    }
+}
+#[cfg(feature = "std")]
+impl<W, E> io::Write for ParallelEncoder<W, E>
+where
+    W: io::Write,
+    E: lz77::Lz77Encode + Clone + Send + 'static,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written_size = self.writer.write(buf)?;
+        self.input_size = self.input_size.wrapping_add(written_size as u32);
+        Ok(written_size)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+#[cfg(feature = "std")]
+impl<W, E> Complete for ParallelEncoder<W, E>
+where
+    W: io::Write,
+    E: lz77::Lz77Encode + Clone + Send + 'static,
+{
+    fn complete(self) -> io::Result<()> {
+        self.finish().into_result().map(|_| ())
+    }
+}
+
+/// Encodes `input` into `output` as a GZIP stream, and returns the size of the encoded data.
+///
+/// See `deflate::compress_into` for more details.
+///
+/// # Examples
+/// ```
+/// use libflate::deflate::SliceError;
+/// use libflate::gzip::{self, EncodeOptions, HeaderBuilder};
+///
+/// let options = || {
+///     let header = HeaderBuilder::new().modification_time(123).finish();
+///     EncodeOptions::new().header(header).no_compression()
+/// };
+/// let mut buf = [0; 64];
+/// let size = gzip::compress_into(b"Hello World!", &mut buf, options()).unwrap();
+/// assert_eq!(&buf[..size], [31, 139, 8, 0, 123, 0, 0, 0, 0, 3, 1, 12, 0, 243, 255,
+///                           72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100, 33,
+///                           163, 28, 41, 28, 12, 0, 0, 0]);
+///
+/// let mut buf = [0; 34];
+/// match gzip::compress_into(b"Hello World!", &mut buf, options()) {
+///     Err(SliceError::BufferTooSmall) => {}
+///     _ => unreachable!(),
+/// }
+/// ```
+pub fn compress_into<E>(
+    input: &[u8],
+    output: &mut [u8],
+    options: EncodeOptions<E>,
+) -> Result<usize, deflate::SliceError>
+where
+    E: lz77::Lz77Encode,
+{
+    let output_len = output.len();
+    let mut encoder = Encoder::with_options(output, options)?;
+    io::Write::write_all(&mut encoder, input)?;
+    let rest = encoder.finish().into_result()?;
+    Ok(output_len - rest.len())
+}
+
+/// Returns the upper bound of the size of the GZIP stream
+/// which `gzip::compress_into` produces from `input_len` bytes.
+///
+/// # Examples
+/// ```
+/// use libflate::gzip::{self, EncodeOptions};
+///
+/// let options = EncodeOptions::new();
+/// let mut buf = vec![0; gzip::max_compressed_len(12, &options)];
+/// let size = gzip::compress_into(b"Hello World!", &mut buf, options).unwrap();
+/// assert_eq!(size, 32);
+/// ```
+pub fn max_compressed_len<E>(input_len: usize, options: &EncodeOptions<E>) -> usize
+where
+    E: lz77::Lz77Encode,
+{
+    options.header.encoded_len() + deflate::max_compressed_len(input_len, &options.options) + 8
+}
+
 /// GZIP decoder.
+///
+/// Like `deflate::Decoder`, this implements `BufRead` as well as `Read`.
 #[derive(Debug)]
 pub struct Decoder<R> {
     header: Header,
@@ -929,6 +1238,50 @@ where
         self.reader.into_inner()
     }
 
+    /// Returns the number of the bytes of the GZIP member (including the header and the trailer)
+    /// which have been read so far.
+    ///
+    /// After the end of the member is reached, this is the exact size of the member,
+    /// and the following bytes can be taken by `unread_bytes`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    /// use libflate::gzip::Decoder;
+    ///
+    /// let mut encoded_data = vec![31, 139, 8, 0, 123, 0, 0, 0, 0, 3, 1, 12, 0, 243, 255,
+    ///                             72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100, 33,
+    ///                             163, 28, 41, 28, 12, 0, 0, 0];
+    /// encoded_data.extend_from_slice(b"trailing data");
+    ///
+    /// let mut decoder = Decoder::new(&encoded_data[..]).unwrap();
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    ///
+    /// assert_eq!(buf, b"Hello World!");
+    /// assert_eq!(decoder.total_in(), 35);
+    /// assert_eq!(decoder.unread_bytes(), b"trailing data");
+    /// ```
+    pub fn total_in(&self) -> u64 {
+        self.header.encoded_len() as u64 + self.reader.total_in()
+    }
+
+    /// Returns the bytes which have been read from the inner stream but not decoded yet.
+    ///
+    /// See `deflate::Decoder::unread_bytes` for more details.
+    pub fn unread_bytes(&self) -> Vec<u8> {
+        self.reader.unread_bytes()
+    }
+
+    /// Reads the header of the member following the current one, and starts decoding it.
+    fn read_next_header(&mut self) -> io::Result<()> {
+        self.header = self.reader.read_trailer(|r| Header::read_from(r))?;
+        self.reader.reset();
+        self.crc32 = checksum::Crc32::new();
+        self.eos = false;
+        Ok(())
+    }
+
     fn with_header(inner: R, header: Header) -> Self {
         Decoder {
             header,
@@ -943,35 +1296,46 @@ where
     R: io::Read,
 {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        if self.eos {
-            Ok(0)
-        } else {
-            let read_size = self.reader.read(buf)?;
-            self.crc32.update(&buf[..read_size]);
-            if read_size == 0 {
-                self.eos = true;
-                let trailer = Trailer::read_from(self.reader.as_inner_mut())?;
-                if trailer.crc32 != self.crc32.value() {
-                    Err(invalid_data_error!(
-                        "CRC32 mismatched: value={}, expected={}",
-                        self.crc32.value(),
-                        trailer.crc32
-                    ))
-                } else {
-                    Ok(0)
-                }
-            } else {
-                Ok(read_size)
+        let read_size = {
+            let data = self.fill_buf()?;
+            let read_size = cmp::min(buf.len(), data.len());
+            buf[..read_size].copy_from_slice(&data[..read_size]);
+            read_size
+        };
+        self.consume(read_size);
+        Ok(read_size)
+    }
+}
+impl<R> io::BufRead for Decoder<R>
+where
+    R: io::Read,
+{
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if !self.eos && self.reader.fill_buf()?.is_empty() {
+            self.eos = true;
+            let trailer = self.reader.read_trailer(|r| Trailer::read_from(r))?;
+            if trailer.crc32 != self.crc32.value() {
+                return Err(invalid_data_error!(
+                    "CRC32 mismatched: value={}, expected={}",
+                    self.crc32.value(),
+                    trailer.crc32
+                ));
             }
         }
+        Ok(self.reader.buffered_data())
+    }
+    fn consume(&mut self, amt: usize) {
+        let amt = cmp::min(amt, self.reader.buffered_data().len());
+        self.crc32.update(&self.reader.buffered_data()[..amt]);
+        self.reader.consume(amt);
     }
 }
 
 /// A decoder that decodes all members in a GZIP stream.
 #[derive(Debug)]
 pub struct MultiDecoder<R> {
-    header: Header,
-    decoder: Result<Decoder<R>, R>,
+    decoder: Decoder<R>,
+    eos: bool,
 }
 impl<R> MultiDecoder<R>
 where
@@ -1005,8 +1369,8 @@ where
     pub fn new(inner: R) -> io::Result<Self> {
         let decoder = Decoder::new(inner)?;
         Ok(MultiDecoder {
-            header: decoder.header().clone(),
-            decoder: Ok(decoder),
+            decoder,
+            eos: false,
         })
     }
 
@@ -1024,23 +1388,17 @@ where
     /// assert_eq!(decoder.header().os(), Os::Unix);
     /// ```
     pub fn header(&self) -> &Header {
-        &self.header
+        self.decoder.header()
     }
 
     /// Returns the immutable reference to the inner stream.
     pub fn as_inner_ref(&self) -> &R {
-        match self.decoder {
-            Err(ref reader) => reader,
-            Ok(ref decoder) => decoder.as_inner_ref(),
-        }
+        self.decoder.as_inner_ref()
     }
 
     /// Returns the mutable reference to the inner stream.
     pub fn as_inner_mut(&mut self) -> &mut R {
-        match self.decoder {
-            Err(ref mut reader) => reader,
-            Ok(ref mut decoder) => decoder.as_inner_mut(),
-        }
+        self.decoder.as_inner_mut()
     }
 
     /// Unwraps this `MultiDecoder`, returning the underlying reader.
@@ -1058,10 +1416,7 @@ where
     /// assert_eq!(decoder.into_inner().into_inner(), &encoded_data[..]);
     /// ```
     pub fn into_inner(self) -> R {
-        match self.decoder {
-            Err(reader) => reader,
-            Ok(decoder) => decoder.into_inner(),
-        }
+        self.decoder.into_inner()
     }
 }
 impl<R> io::Read for MultiDecoder<R>
@@ -1069,33 +1424,21 @@ where
     R: io::Read,
 {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let read_size = match self.decoder {
-            Err(_) => return Ok(0),
-            Ok(ref mut decoder) => decoder.read(buf)?,
-        };
+        if self.eos {
+            return Ok(0);
+        }
+        let read_size = self.decoder.read(buf)?;
         if read_size == 0 {
-            let mut reader = mem::replace(&mut self.decoder, Err(unsafe { mem::uninitialized() }))
-                .ok()
-                .take()
-                .expect("Never fails")
-                .into_inner();
-            match Header::read_from(&mut reader) {
+            match self.decoder.read_next_header() {
                 Err(e) => {
-                    mem::forget(mem::replace(&mut self.decoder, Err(reader)));
+                    self.eos = true;
                     if e.kind() == io::ErrorKind::UnexpectedEof {
                         Ok(0)
                     } else {
                         Err(e)
                     }
                 }
-                Ok(header) => {
-                    self.header = header.clone();
-                    mem::forget(mem::replace(
-                        &mut self.decoder,
-                        Ok(Decoder::with_header(reader, header)),
-                    ));
-                    self.read(buf)
-                }
+                Ok(()) => self.read(buf),
             }
         } else {
             Ok(read_size)
@@ -1106,8 +1449,9 @@ where
 #[cfg(test)]
 mod test {
     use super::*;
+    use alloc::string::String;
     use finish::AutoFinish;
-    use std::io;
+    use io;
 
     fn decode(buf: &[u8]) -> io::Result<Vec<u8>> {
         let mut decoder = Decoder::new(buf).unwrap();
@@ -1138,6 +1482,49 @@ mod test {
         assert_eq!(decode(&encoded).unwrap(), plain);
     }
 
+    #[test]
+    fn buf_read_works() {
+        use io::BufRead;
+
+        let plain = (0..10_000)
+            .map(|i| format!("line {}\n", i % 1000))
+            .collect::<String>();
+        let mut encoder = Encoder::new(Vec::new()).unwrap();
+        io::copy(&mut plain.as_bytes(), &mut encoder).unwrap();
+        let mut encoded = encoder.finish().into_result().unwrap();
+
+        let mut decoder = Decoder::new(&encoded[..]).unwrap();
+        let mut decoded = Vec::new();
+        loop {
+            let size = {
+                let data = decoder.fill_buf().unwrap();
+                decoded.extend_from_slice(data);
+                data.len()
+            };
+            if size == 0 {
+                break;
+            }
+            decoder.consume(size);
+        }
+        assert_eq!(decoded, plain.as_bytes());
+
+        // The checksum covers the data consumed through `BufRead`
+        let len = encoded.len();
+        encoded[len - 8] ^= 1;
+        let mut decoder = Decoder::new(&encoded[..]).unwrap();
+        loop {
+            let size = match decoder.fill_buf() {
+                Ok(data) => data.len(),
+                Err(e) => {
+                    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+                    break;
+                }
+            };
+            assert_ne!(size, 0);
+            decoder.consume(size);
+        }
+    }
+
     #[test]
     fn encoder_auto_finish_works() {
         let plain = b"Hello World! Hello GZIP!!";
@@ -1149,9 +1536,30 @@ mod test {
         assert_eq!(decode(&buf).unwrap(), plain);
     }
 
+    #[test]
+    #[cfg(feature = "std")]
+    fn parallel_encode_works() {
+        let plain = (0..100_000)
+            .map(|i| format!("{} {} ", i % 907, i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let parallel = deflate::ParallelOptions::new()
+            .threads(3)
+            .chunk_size(10_000);
+        let mut encoder =
+            ParallelEncoder::with_options(Vec::new(), EncodeOptions::new(), parallel).unwrap();
+        io::copy(&mut &plain[..], &mut encoder).unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+        assert_eq!(decode(&encoded).unwrap(), plain);
+        let input_size = (&encoded[encoded.len() - 4..])
+            .read_u32::<LittleEndian>()
+            .unwrap();
+        assert_eq!(input_size as usize, plain.len());
+    }
+
     #[test]
     fn multi_decode_works() {
-        use std::iter;
+        use core::iter;
         let text = b"Hello World!";
         let encoded: Vec<u8> = iter::repeat(encode(text).unwrap())
             .take(2)
@@ -1181,4 +1589,38 @@ mod test {
         let data = b"\x1F\x8B\x08\xC1\x91\x28\x71\xDC\xF2\x2D\x34\x35\x31\x35\x34\x30\x70\x6E\x60\x35\x31\x32\x32\x33\x32\x33\x37\x32\x36\x38\xDD\x1C\xE5\x2A\xDD\xDD\xDD\x22\xDD\xDD\xDD\xDC\x88\x13\xC9\x40\x60\xA7";
         assert!(decode(&data[..]).is_err());
     }
+
+    #[test]
+    fn compress_into_works() {
+        let plain = (0..100_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let header = HeaderBuilder::new()
+            .filename(CString::new("foo.txt").unwrap())
+            .comment(CString::new("bar").unwrap())
+            .extra_field(ExtraField {
+                id: [1, 2],
+                data: vec![3, 4, 5],
+            })
+            .verify()
+            .finish();
+        let options = || EncodeOptions::new().header(header.clone()).block_size(4096);
+
+        let mut buf = vec![0; max_compressed_len(plain.len(), &options())];
+        let size = compress_into(&plain, &mut buf, options()).unwrap();
+
+        let mut encoder = Encoder::with_options(Vec::new(), options()).unwrap();
+        io::Write::write_all(&mut encoder, &plain).unwrap();
+        assert_eq!(&buf[..size], &encoder.finish().into_result().unwrap()[..]);
+
+        let mut header_buf = Vec::new();
+        header.write_to(&mut header_buf).unwrap();
+        assert_eq!(header.encoded_len(), header_buf.len());
+
+        match compress_into(&plain, &mut buf[..size - 1], options()) {
+            Err(deflate::SliceError::BufferTooSmall) => {}
+            r => panic!("Unexpected result: {:?}", r),
+        }
+    }
 }
@@ -0,0 +1,190 @@
+//! The GZIP decoder which receives the encoded data through `io::Write`.
+//!
+//! # Examples
+//! ```
+//! use std::io::Write;
+//! use libflate::gzip::write::Decoder;
+//!
+//! let encoded_data = [31, 139, 8, 0, 123, 0, 0, 0, 0, 3, 1, 12, 0, 243, 255,
+//!                     72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100, 33,
+//!                     163, 28, 41, 28, 12, 0, 0, 0];
+//!
+//! let mut decoder = Decoder::new(Vec::new());
+//! for chunk in encoded_data.chunks(3) {
+//!     decoder.write_all(chunk).unwrap();
+//! }
+//! let (decoded_data, _) = decoder.finish().into_result().unwrap();
+//!
+//! assert_eq!(decoded_data, b"Hello World!");
+//! ```
+use alloc::vec::Vec;
+
+use super::Header;
+use adapter::{InputBuffer, WriteDecoder};
+use finish::{Complete, Finish};
+use io::{self, Write};
+use non_blocking;
+
+/// GZIP decoder which implements `io::Write`.
+///
+/// Only the first member of the GZIP stream is decoded,
+/// and the succeeding members are returned by `finish` as the trailing bytes.
+///
+/// See `deflate::write::Decoder` for more details.
+#[derive(Debug)]
+pub struct Decoder<W> {
+    inner: WriteDecoder<non_blocking::gzip::Decoder<InputBuffer>, W>,
+}
+impl<W> Decoder<W>
+where
+    W: Write,
+{
+    /// Makes a new decoder instance.
+    ///
+    /// The decoded data is written to `inner`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::gzip::write::Decoder;
+    ///
+    /// let encoded_data = [31, 139, 8, 0, 123, 0, 0, 0, 0, 3, 1, 12, 0, 243, 255,
+    ///                     72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100, 33,
+    ///                     163, 28, 41, 28, 12, 0, 0, 0];
+    ///
+    /// let mut decoder = Decoder::new(Vec::new());
+    /// decoder.write_all(&encoded_data).unwrap();
+    ///
+    /// assert_eq!(decoder.as_inner_ref(), b"Hello World!");
+    /// ```
+    pub fn new(inner: W) -> Self {
+        let decoder = non_blocking::gzip::Decoder::new(InputBuffer::new());
+        Decoder {
+            inner: WriteDecoder::new(decoder, inner),
+        }
+    }
+
+    /// Returns the header of the GZIP stream.
+    ///
+    /// `None` means that the header has not been written yet.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::gzip::Os;
+    /// use libflate::gzip::write::Decoder;
+    ///
+    /// let mut decoder = Decoder::new(Vec::new());
+    /// decoder.write_all(&[31, 139, 8, 0, 123, 0, 0]).unwrap();
+    /// assert!(decoder.header().is_none());
+    ///
+    /// decoder.write_all(&[0, 0, 3]).unwrap();
+    /// assert_eq!(decoder.header().unwrap().os(), Os::Unix);
+    /// ```
+    pub fn header(&mut self) -> Option<&Header> {
+        self.inner.decoder_mut().header().ok()
+    }
+
+    /// Returns `true` if the end of the GZIP member (including the trailer) has been reached.
+    pub fn is_finished(&self) -> bool {
+        self.inner.is_eos()
+    }
+
+    /// Decodes the remaining data, and returns the inner writer and the trailing bytes.
+    ///
+    /// The trailing bytes are the ones written after the end of the first GZIP member.
+    /// If the stream is truncated, an `UnexpectedEof` error is returned,
+    /// and if the checksum is mismatched, an `InvalidData` error is returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::gzip::write::Decoder;
+    ///
+    /// let encoded_data = [31, 139, 8, 0, 123, 0, 0, 0, 0, 3, 1, 12, 0, 243, 255,
+    ///                     72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100, 33,
+    ///                     163, 28, 41, 28, 12, 0, 0, 0];
+    ///
+    /// let mut decoder = Decoder::new(Vec::new());
+    /// decoder.write_all(&encoded_data).unwrap();
+    /// decoder.write_all(b"trailer").unwrap();
+    /// let (decoded_data, trailing) = decoder.finish().into_result().unwrap();
+    /// assert_eq!(decoded_data, b"Hello World!");
+    /// assert_eq!(trailing, b"trailer");
+    ///
+    /// let mut decoder = Decoder::new(Vec::new());
+    /// decoder.write_all(&encoded_data[..30]).unwrap();
+    /// assert!(decoder.finish().into_result().is_err());
+    /// ```
+    pub fn finish(self) -> Finish<(W, Vec<u8>), io::Error> {
+        let (inner, trailing, error) = self.inner.finish();
+        Finish::new((inner, trailing), error)
+    }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &W {
+        self.inner.as_inner_ref()
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut W {
+        self.inner.as_inner_mut()
+    }
+
+    /// Unwraps the `Decoder`, returning the inner stream.
+    ///
+    /// Unlike `finish`, the end of the stream is not verified.
+    pub fn into_inner(self) -> W {
+        self.inner.into_inner()
+    }
+}
+impl<W> Write for Decoder<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+impl<W> Complete for Decoder<W>
+where
+    W: Write,
+{
+    fn complete(self) -> io::Result<()> {
+        self.finish().into_result().map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::string::String;
+    use gzip::{EncodeOptions, Encoder};
+
+    #[test]
+    fn write_decode_works() {
+        let plain = (0..100_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let options = EncodeOptions::new().block_size(4096);
+        let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+        encoder.write_all(&plain).unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+
+        for &chunk_size in &[1, 7, 1000, encoded.len()] {
+            let mut decoder = Decoder::new(Vec::new());
+            for chunk in encoded.chunks(chunk_size) {
+                decoder.write_all(chunk).unwrap();
+            }
+            assert!(decoder.is_finished());
+            decoder.write_all(&encoded).unwrap();
+            let (decoded, trailing) = decoder.finish().into_result().unwrap();
+            assert_eq!(decoded, plain);
+            assert_eq!(trailing, encoded);
+        }
+    }
+}
@@ -1,9 +1,10 @@
-use std::cmp;
 /// Length-limited Huffman Codes
 ///
-use std::io;
+use alloc::vec::Vec;
+use core::cmp;
 
 use bit;
+use io;
 
 const MAX_BITWIDTH: u8 = 15;
 
@@ -56,26 +57,124 @@ pub trait Builder: Sized {
     }
 }
 
+/// An entry of the decoding tables of `Decoder`.
+///
+/// The lowest 5 bits are the bitwidth of the code, the next 3 bits are the kind of the entry,
+/// and the remaining 24 bits are the value associated with the code.
+/// The kinds other than `SUBTABLE` and `INVALID` are defined by the users of `Decoder`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry(u32);
+impl Entry {
+    /// The kind of the entries of the main table which refer to subtables.
+    pub const SUBTABLE: u8 = 6;
+
+    /// The kind of the entries which correspond to no code.
+    ///
+    /// The bitwidth of such an entry is the number of the bits needed to look it up.
+    pub const INVALID: u8 = 7;
+
+    #[inline(always)]
+    pub fn new(width: u8, kind: u8, value: u32) -> Self {
+        debug_assert!(width < 32 && kind < 8 && value < 1 << 24);
+        Entry((value << 8) | (u32::from(kind) << 5) | u32::from(width))
+    }
+    #[inline(always)]
+    pub fn width(self) -> u8 {
+        (self.0 & 0b1_1111) as u8
+    }
+    #[inline(always)]
+    pub fn kind(self) -> u8 {
+        ((self.0 >> 5) & 0b111) as u8
+    }
+    #[inline(always)]
+    pub fn value(self) -> u32 {
+        self.0 >> 8
+    }
+    fn invalid(width: u8) -> Self {
+        Self::new(width, Self::INVALID, 0)
+    }
+    fn subtable(width: u8, offset: usize, subtable_bits: u8) -> Self {
+        Self::new(
+            width,
+            Self::SUBTABLE,
+            (offset << 4) as u32 | u32::from(subtable_bits),
+        )
+    }
+    fn with_width(self, width: u8) -> Self {
+        Entry((self.0 & !0b1_1111) | u32::from(width))
+    }
+}
+
+/// Returns the entry of which value is `symbol` itself.
+pub fn symbol_entry(symbol: u16) -> Entry {
+    Entry::new(0, 0, u32::from(symbol))
+}
+
 pub struct DecoderBuilder {
-    table: Vec<u16>,
+    table: Vec<Entry>,
+    table_bits: u8,
     eob_symbol: Option<u16>,
     eob_bitwidth: u8,
     max_bitwidth: u8,
+    entry_of: fn(u16) -> Entry,
 }
 impl DecoderBuilder {
-    pub fn new(max_bitwidth: u8, eob_symbol: Option<u16>) -> Self {
+    /// Makes a new builder.
+    ///
+    /// The codes up to `table_bits` bits are decoded by a lookup of the main table,
+    /// and the longer ones are decoded via its subtables.
+    /// `entry_of` returns the entry of each symbol (its bitwidth is replaced with the one of the code).
+    pub fn new(
+        max_bitwidth: u8,
+        eob_symbol: Option<u16>,
+        table_bits: u8,
+        entry_of: fn(u16) -> Entry,
+    ) -> Self {
         debug_assert!(max_bitwidth <= MAX_BITWIDTH);
+        let table_bits = cmp::min(table_bits, max_bitwidth);
         DecoderBuilder {
-            table: vec![u16::from(MAX_BITWIDTH) + 1; 1 << max_bitwidth],
+            table: vec![Entry::invalid(table_bits); 1 << table_bits],
+            table_bits,
             eob_symbol,
-            eob_bitwidth: max_bitwidth,
+            eob_bitwidth: 0,
             max_bitwidth,
+            entry_of,
         }
     }
-    pub fn from_bitwidthes(bitwidthes: &[u8], eob_symbol: Option<u16>) -> io::Result<Decoder> {
-        let builder = Self::new(bitwidthes.iter().cloned().max().unwrap_or(0), eob_symbol);
+    pub fn from_bitwidthes(
+        bitwidthes: &[u8],
+        eob_symbol: Option<u16>,
+        table_bits: u8,
+        entry_of: fn(u16) -> Entry,
+    ) -> io::Result<Decoder> {
+        let max_bitwidth = bitwidthes.iter().cloned().max().unwrap_or(0);
+        let builder = Self::new(max_bitwidth, eob_symbol, table_bits, entry_of);
         builder.restore_canonical_huffman_codes(bitwidthes)
     }
+
+    /// Sets `entry` to all the indices of the table at `offset` which start with `code` (in reverse order).
+    fn set_entries(
+        &mut self,
+        offset: usize,
+        index_bits: u8,
+        code: &Code,
+        entry: Entry,
+    ) -> io::Result<()> {
+        for padding in 0..(1 << (index_bits - code.width)) {
+            let i = offset + ((padding << code.width) | code.bits) as usize;
+            if self.table[i].kind() != Entry::INVALID {
+                let message = format!(
+                    "Bit region conflict: i={}, old_entry={:?}, new_entry={:?}, code={:?}",
+                    i, self.table[i], entry, code
+                );
+                return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+            }
+            unsafe {
+                *self.table.get_unchecked_mut(i) = entry;
+            }
+        }
+        Ok(())
+    }
 }
 impl Builder for DecoderBuilder {
     type Instance = Decoder;
@@ -85,38 +184,54 @@ impl Builder for DecoderBuilder {
             self.eob_bitwidth = code.width;
         }
 
-        // `bitwidth` encoded `to` value
-        let value = (symbol << 5) | u16::from(code.width);
-
-        // Sets the mapping to all possible indices
+        let entry = (self.entry_of)(symbol).with_width(code.width);
         let code_be = code.inverse_endian();
-        for padding in 0..(1 << (self.max_bitwidth - code.width)) {
-            let i = ((padding << code.width) | code_be.bits) as usize;
-            if self.table[i] != u16::from(MAX_BITWIDTH) + 1 {
-                let message = format!(
-                    "Bit region conflict: i={}, old_value={}, new_value={}, symbol={}, code={:?}",
-                    i, self.table[i], value, symbol, code
-                );
-                return Err(io::Error::new(io::ErrorKind::InvalidData, message));
-            }
-            unsafe {
-                *self.table.get_unchecked_mut(i) = value;
-            }
+        if code.width <= self.table_bits {
+            return self.set_entries(0, self.table_bits, &code_be, entry);
         }
-        Ok(())
+
+        // The longer codes are stored in the subtable shared by the codes with the same leading bits
+        let subtable_bits = self.max_bitwidth - self.table_bits;
+        let i = (code_be.bits & ((1 << self.table_bits) - 1)) as usize;
+        if self.table[i].kind() == Entry::INVALID {
+            let offset = self.table.len();
+            self.table[i] = Entry::subtable(self.table_bits, offset, subtable_bits);
+            let invalid = Entry::invalid(self.max_bitwidth);
+            self.table.resize(offset + (1 << subtable_bits), invalid);
+        }
+        let pointer = self.table[i];
+        if pointer.kind() != Entry::SUBTABLE {
+            let message = format!(
+                "Bit region conflict: i={}, old_entry={:?}, new_entry={:?}, code={:?}",
+                i, pointer, entry, code
+            );
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+        let offset = (pointer.value() >> 4) as usize;
+        let rest = Code::new(
+            code.width - self.table_bits,
+            code_be.bits >> self.table_bits,
+        );
+        self.set_entries(offset, subtable_bits, &rest, entry)
     }
     fn finish(self) -> Self::Instance {
         Decoder {
             table: self.table,
+            table_bits: self.table_bits,
             eob_bitwidth: self.eob_bitwidth,
             max_bitwidth: self.max_bitwidth,
         }
     }
 }
 
-#[derive(Debug)]
+/// Huffman decoder with multi-level lookup tables (in the style of libdeflate).
+///
+/// The main table is indexed by the next `table_bits` bits of the stream,
+/// and its entries for the longer codes refer to the subtables indexed by the following bits.
+#[derive(Debug, Clone)]
 pub struct Decoder {
-    table: Vec<u16>,
+    table: Vec<Entry>,
+    table_bits: u8,
     eob_bitwidth: u8,
     max_bitwidth: u8,
 }
@@ -124,7 +239,7 @@ impl Decoder {
     #[inline(always)]
     pub fn decode<R>(&self, reader: &mut bit::BitReader<R>) -> io::Result<u16>
     where
-        R: io::Read,
+        R: io::BufRead,
     {
         let v = self.decode_unchecked(reader);
         reader.check_last_error()?;
@@ -134,21 +249,108 @@ impl Decoder {
     #[inline(always)]
     pub fn decode_unchecked<R>(&self, reader: &mut bit::BitReader<R>) -> u16
     where
-        R: io::Read,
+        R: io::BufRead,
     {
-        let code = reader.peek_bits_unchecked(self.eob_bitwidth);
-        let mut value = unsafe { *self.table.get_unchecked(code as usize) };
-        let mut bitwidth = (value & 0b1_1111) as u8;
-        if bitwidth > self.eob_bitwidth {
-            let code = reader.peek_bits_unchecked(self.max_bitwidth);
-            value = unsafe { *self.table.get_unchecked(code as usize) };
-            bitwidth = (value & 0b1_1111) as u8;
-            if bitwidth > self.max_bitwidth {
-                reader.set_last_error(invalid_data_error!("Invalid huffman coded stream"));
+        let entry = self.peek_unchecked(reader, |_, _| None);
+        if entry.kind() == Entry::INVALID {
+            reader.set_last_error(invalid_data_error!("Invalid huffman coded stream"));
+        }
+        reader.skip_bits(entry.width());
+        entry.value() as u16
+    }
+
+    /// Returns the entry of the next code of `reader` without consuming its bits.
+    ///
+    /// Only the bits which are needed for the code are read across the internal buffers of the inner stream
+    /// (the end-of-block code, if any, always follows in the stream), so the bytes following the end of
+    /// the compressed data are left in it.
+    /// If the entry needs more bits than loaded, `shorten` may return the alternative entry
+    /// which fits in the loaded bits (e.g., the first half of an entry made by `combine`).
+    #[inline(always)]
+    pub fn peek_unchecked<R, F>(&self, reader: &mut bit::BitReader<R>, shorten: F) -> Entry
+    where
+        R: io::BufRead,
+        F: Fn(Entry, u8) -> Option<Entry>,
+    {
+        reader.preload(self.max_bitwidth);
+        if reader.buffered_bitwidth() < self.eob_bitwidth {
+            // The end-of-block code (or a longer one) follows, so the bits are in the stream certainly
+            reader.peek_bits_unchecked(self.eob_bitwidth);
+        }
+        let entry = self.lookup(reader.buffered_bits());
+        if entry.width() <= reader.buffered_bitwidth() {
+            entry
+        } else {
+            self.peek_slowly(reader, entry, shorten)
+        }
+    }
+
+    /// Looks up the entry indexed by the low bits of `bits`.
+    #[inline(always)]
+    pub fn lookup(&self, bits: u64) -> Entry {
+        let i = bits as usize & ((1 << self.table_bits) - 1);
+        let entry = unsafe { *self.table.get_unchecked(i) };
+        if entry.kind() != Entry::SUBTABLE {
+            return entry;
+        }
+        let offset = (entry.value() >> 4) as usize;
+        let subtable_bits = entry.value() & 0b1111;
+        let i = (bits >> self.table_bits) as usize & ((1 << subtable_bits) - 1);
+        unsafe { *self.table.get_unchecked(offset + i) }
+    }
+
+    /// Replaces the entries of the main table with the ones which decode two successive codes at once.
+    ///
+    /// `combine` is called with the entries of every pair of codes which fit in the index bits of the main table,
+    /// and its result (if any) is stored instead of the entry of the first code.
+    pub fn combine<F>(&mut self, combine: F)
+    where
+        F: Fn(Entry, Entry) -> Option<Entry>,
+    {
+        // In descending order, so that the entries of the second codes have not been replaced yet
+        for i in (0..1 << self.table_bits).rev() {
+            let first = self.table[i];
+            if first.width() >= self.table_bits {
+                continue;
+            }
+            let second = self.table[i >> first.width()];
+            if first.width() + second.width() > self.table_bits {
+                continue;
+            }
+            if let Some(entry) = combine(first, second) {
+                self.table[i] = entry;
+            }
+        }
+    }
+
+    #[inline]
+    fn peek_slowly<R, F>(
+        &self,
+        reader: &mut bit::BitReader<R>,
+        mut entry: Entry,
+        shorten: F,
+    ) -> Entry
+    where
+        R: io::BufRead,
+        F: Fn(Entry, u8) -> Option<Entry>,
+    {
+        loop {
+            let nbits = reader.buffered_bitwidth();
+            if let Some(entry) = shorten(entry, nbits) {
+                return entry;
+            }
+
+            // The code is longer than the loaded bits, so the next bit is still a part of the stream
+            reader.peek_bits_unchecked(nbits + 1);
+            if reader.buffered_bitwidth() <= nbits {
+                // Failed to read (the error has been set to `reader`)
+                return entry;
+            }
+            entry = self.lookup(reader.buffered_bits());
+            if entry.width() <= reader.buffered_bitwidth() {
+                return entry;
             }
         }
-        reader.skip_bits(bitwidth as u8);
-        value >> 5
     }
 }
 
@@ -173,11 +375,10 @@ impl EncoderBuilder {
         builder.restore_canonical_huffman_codes(bitwidthes)
     }
     pub fn from_frequencies(symbol_frequencies: &[usize], max_bitwidth: u8) -> io::Result<Encoder> {
-        let max_bitwidth = cmp::min(
-            max_bitwidth,
-            ordinary_huffman_codes::calc_optimal_max_bitwidth(symbol_frequencies),
-        );
-        let code_bitwidthes = length_limited_huffman_codes::calc(max_bitwidth, symbol_frequencies);
+        let mut code_bitwidthes = ordinary_huffman_codes::calc(symbol_frequencies);
+        if code_bitwidthes.iter().any(|&w| w > max_bitwidth) {
+            code_bitwidthes = length_limited_huffman_codes::calc(max_bitwidth, symbol_frequencies);
+        }
         Self::from_bitwidthes(&code_bitwidthes)
     }
 }
@@ -226,28 +427,57 @@ impl Encoder {
     }
 }
 
-#[allow(dead_code)]
 mod ordinary_huffman_codes {
-    use std::cmp;
-    use std::collections::BinaryHeap;
-
-    pub fn calc_optimal_max_bitwidth(frequencies: &[usize]) -> u8 {
-        let mut heap = BinaryHeap::new();
-        for &freq in frequencies.iter().filter(|&&f| f > 0) {
-            let weight = -(freq as isize);
-            heap.push((weight, 0 as u8));
+    use alloc::collections::BinaryHeap;
+    use alloc::vec::Vec;
+    use core::cmp::Reverse;
+
+    /// Returns the code bitwidthes of the (not length-limited) Huffman codes.
+    ///
+    /// If only one symbol occurs, its bitwidth is `1`.
+    pub fn calc(frequencies: &[usize]) -> Vec<u8> {
+        let mut code_bitwidthes = vec![0; frequencies.len()];
+        let leaves = frequencies
+            .iter()
+            .enumerate()
+            .filter(|&(_, &f)| f > 0)
+            .map(|(symbol, _)| symbol)
+            .collect::<Vec<_>>();
+        if leaves.len() == 1 {
+            code_bitwidthes[leaves[0]] = 1;
+            return code_bitwidthes;
         }
+
+        // The nodes are numbered in order of creation, so a parent always follows its children.
+        let mut parents = vec![0; leaves.len()];
+        let mut heap = leaves
+            .iter()
+            .enumerate()
+            .map(|(node, &symbol)| Reverse((frequencies[symbol], node)))
+            .collect::<BinaryHeap<_>>();
         while heap.len() > 1 {
-            let (weight1, width1) = heap.pop().unwrap();
-            let (weight2, width2) = heap.pop().unwrap();
-            heap.push((weight1 + weight2, 1 + cmp::max(width1, width2)));
+            let Reverse((weight1, node1)) = heap.pop().unwrap();
+            let Reverse((weight2, node2)) = heap.pop().unwrap();
+            let parent = parents.len();
+            parents[node1] = parent;
+            parents[node2] = parent;
+            parents.push(0);
+            heap.push(Reverse((weight1 + weight2, parent)));
         }
-        let max_bitwidth = heap.pop().map_or(0, |x| x.1);
-        cmp::max(1, max_bitwidth)
+
+        let mut depths = vec![0; parents.len()];
+        for node in (0..parents.len().saturating_sub(1)).rev() {
+            depths[node] = depths[parents[node]] + 1;
+        }
+        for (&symbol, &depth) in leaves.iter().zip(depths.iter()) {
+            code_bitwidthes[symbol] = depth;
+        }
+        code_bitwidthes
     }
 }
 mod length_limited_huffman_codes {
-    use std::mem;
+    use alloc::vec::Vec;
+    use core::mem;
 
     #[derive(Debug, Clone)]
     struct Node {
@@ -337,6 +567,48 @@ mod length_limited_huffman_codes {
 
 #[cfg(test)]
 mod test {
+    use super::*;
+
     #[test]
     fn it_works() {}
+
+    #[test]
+    fn ordinary_huffman_codes_works() {
+        let bitwidthes = ordinary_huffman_codes::calc(&[1, 1, 2, 4, 0]);
+        assert_eq!(bitwidthes, [3, 3, 2, 1, 0]);
+
+        let bitwidthes = ordinary_huffman_codes::calc(&[0, 5]);
+        assert_eq!(bitwidthes, [0, 1]);
+    }
+
+    #[test]
+    fn decoder_with_subtables_works() {
+        let frequencies = (0..16).map(|i| 1 << i).collect::<Vec<usize>>();
+        let encoder = EncoderBuilder::from_frequencies(&frequencies, 15).unwrap();
+        let bitwidthes = (0..16).map(|i| encoder.lookup(i).width).collect::<Vec<_>>();
+        assert_eq!(bitwidthes.iter().max(), Some(&15));
+
+        let symbols = [15, 0, 7, 1, 14, 2, 3, 15, 0];
+        let mut writer = bit::BitWriter::new(Vec::new());
+        for &s in &symbols {
+            encoder.encode(&mut writer, s).unwrap();
+        }
+        writer.flush().unwrap();
+        let encoded = writer.into_inner();
+
+        // The stream ends right after the last (longest) code, which is decoded via a subtable
+        let decoder = DecoderBuilder::from_bitwidthes(&bitwidthes, None, 4, symbol_entry).unwrap();
+        let mut reader = bit::BitReader::new(&encoded[..]);
+        for &s in &symbols {
+            assert_eq!(decoder.decode(&mut reader).unwrap(), s);
+        }
+    }
+
+    #[test]
+    fn from_frequencies_limits_bitwidthes() {
+        let frequencies = (0..20).map(|i| 1 << i).collect::<Vec<usize>>();
+        let encoder = EncoderBuilder::from_frequencies(&frequencies, 15).unwrap();
+        assert!((0..20).all(|i| encoder.lookup(i).width <= 15));
+        assert_eq!(encoder.lookup(19).width, 1);
+    }
 }
@@ -0,0 +1,266 @@
+//! Minimal replacement of `std::io` for `no_std` environments.
+//!
+//! This module is used if the `std` feature is disabled (otherwise `libflate::io` is a re-export of `std::io`).
+//! It provides the subset of `std::io` which is needed by the encoders and decoders of this crate,
+//! and the readers and writers of byte slices and vectors.
+use alloc::string::String;
+use alloc::vec::Vec;
+use byteorder::ByteOrder;
+use core::cmp;
+use core::fmt;
+use core::mem;
+use core::result;
+
+/// A specialized `Result` type for I/O operations.
+pub type Result<T> = result::Result<T, Error>;
+
+/// A list specifying general categories of I/O error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// Data not valid for the operation were encountered.
+    InvalidData,
+
+    /// A parameter was incorrect.
+    InvalidInput,
+
+    /// An operation could not be completed because an "end of file" was reached prematurely.
+    UnexpectedEof,
+
+    /// The operation needs to block to complete.
+    WouldBlock,
+
+    /// A call to `write` returned `Ok(0)`.
+    WriteZero,
+
+    /// A custom error that does not fall under any other I/O error kind.
+    Other,
+}
+
+/// The error type for I/O operations.
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    message: String,
+}
+impl Error {
+    /// Makes a new error instance from a kind and a message.
+    pub fn new<M>(kind: ErrorKind, message: M) -> Self
+    where
+        M: Into<String>,
+    {
+        Error {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// Makes a new error instance of `ErrorKind::Other`.
+    pub fn other<M>(message: M) -> Self
+    where
+        M: Into<String>,
+    {
+        Self::new(ErrorKind::Other, message)
+    }
+
+    /// Returns the kind of this error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+}
+impl From<ErrorKind> for Error {
+    fn from(f: ErrorKind) -> Self {
+        Self::new(f, "")
+    }
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.message.is_empty() {
+            write!(f, "{:?}", self.kind)
+        } else {
+            write!(f, "{}", self.message)
+        }
+    }
+}
+
+/// The `Read` trait allows for reading bytes from a source.
+pub trait Read {
+    /// Pulls some bytes from this source into `buf`, returning how many bytes were read.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+    /// Reads the exact number of bytes required to fill `buf`.
+    fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
+        while !buf.is_empty() {
+            let size = self.read(buf)?;
+            if size == 0 {
+                return Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "failed to fill whole buffer",
+                ));
+            }
+            buf = &mut buf[size..];
+        }
+        Ok(())
+    }
+
+    /// Reads all bytes until EOF in this source, placing them into `buf`.
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+        let start = buf.len();
+        let mut chunk = [0; 1024];
+        loop {
+            let size = self.read(&mut chunk)?;
+            if size == 0 {
+                return Ok(buf.len() - start);
+            }
+            buf.extend_from_slice(&chunk[..size]);
+        }
+    }
+}
+impl<'a, R: Read + ?Sized> Read for &'a mut R {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        (**self).read(buf)
+    }
+}
+impl<'a> Read for &'a [u8] {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let size = cmp::min(buf.len(), self.len());
+        buf[..size].copy_from_slice(&self[..size]);
+        *self = &self[size..];
+        Ok(size)
+    }
+}
+
+/// A `BufRead` is a type of `Read`er which has an internal buffer.
+pub trait BufRead: Read {
+    /// Returns the contents of the internal buffer, filling it with more data if it is empty.
+    ///
+    /// An empty buffer means that the end of the source has been reached.
+    fn fill_buf(&mut self) -> Result<&[u8]>;
+
+    /// Marks the first `amt` bytes of the buffer as consumed.
+    fn consume(&mut self, amt: usize);
+}
+impl<'a, B: BufRead + ?Sized> BufRead for &'a mut B {
+    fn fill_buf(&mut self) -> Result<&[u8]> {
+        (**self).fill_buf()
+    }
+    fn consume(&mut self, amt: usize) {
+        (**self).consume(amt)
+    }
+}
+impl<'a> BufRead for &'a [u8] {
+    fn fill_buf(&mut self) -> Result<&[u8]> {
+        Ok(*self)
+    }
+    fn consume(&mut self, amt: usize) {
+        *self = &self[amt..];
+    }
+}
+
+/// A trait for objects which are byte-oriented sinks.
+pub trait Write {
+    /// Writes a buffer into this writer, returning how many bytes were written.
+    fn write(&mut self, buf: &[u8]) -> Result<usize>;
+
+    /// Flushes this output stream.
+    fn flush(&mut self) -> Result<()>;
+
+    /// Attempts to write an entire buffer into this writer.
+    fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+        while !buf.is_empty() {
+            let size = self.write(buf)?;
+            if size == 0 {
+                return Err(Error::new(
+                    ErrorKind::WriteZero,
+                    "failed to write whole buffer",
+                ));
+            }
+            buf = &buf[size..];
+        }
+        Ok(())
+    }
+}
+impl<'a, W: Write + ?Sized> Write for &'a mut W {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        (**self).write(buf)
+    }
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
+    }
+}
+impl<'a> Write for &'a mut [u8] {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let size = cmp::min(buf.len(), self.len());
+        let (head, tail) = mem::take(self).split_at_mut(size);
+        head.copy_from_slice(&buf[..size]);
+        *self = tail;
+        Ok(size)
+    }
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+impl Write for Vec<u8> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Copies the entire contents of a reader into a writer, returning the number of the copied bytes.
+pub fn copy<R, W>(reader: &mut R, writer: &mut W) -> Result<u64>
+where
+    R: Read + ?Sized,
+    W: Write + ?Sized,
+{
+    let mut buf = [0; 1024];
+    let mut copied = 0;
+    loop {
+        let size = reader.read(&mut buf)?;
+        if size == 0 {
+            return Ok(copied);
+        }
+        writer.write_all(&buf[..size])?;
+        copied += size as u64;
+    }
+}
+
+/// Extends `Read` with methods for reading numbers (like `byteorder::ReadBytesExt`).
+pub(crate) trait ReadBytesExt: Read {
+    fn read_u8(&mut self) -> Result<u8> {
+        let mut buf = [0; 1];
+        self.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+    fn read_u16<T: ByteOrder>(&mut self) -> Result<u16> {
+        let mut buf = [0; 2];
+        self.read_exact(&mut buf)?;
+        Ok(T::read_u16(&buf))
+    }
+    fn read_u32<T: ByteOrder>(&mut self) -> Result<u32> {
+        let mut buf = [0; 4];
+        self.read_exact(&mut buf)?;
+        Ok(T::read_u32(&buf))
+    }
+}
+impl<R: Read + ?Sized> ReadBytesExt for R {}
+
+/// Extends `Write` with methods for writing numbers (like `byteorder::WriteBytesExt`).
+pub(crate) trait WriteBytesExt: Write {
+    fn write_u8(&mut self, n: u8) -> Result<()> {
+        self.write_all(&[n])
+    }
+    fn write_u16<T: ByteOrder>(&mut self, n: u16) -> Result<()> {
+        let mut buf = [0; 2];
+        T::write_u16(&mut buf, n);
+        self.write_all(&buf)
+    }
+    fn write_u32<T: ByteOrder>(&mut self, n: u32) -> Result<()> {
+        let mut buf = [0; 4];
+        T::write_u32(&mut buf, n);
+        self.write_all(&buf)
+    }
+}
+impl<W: Write + ?Sized> WriteBytesExt for W {}
@@ -1,16 +1,43 @@
 //! A Rust implementation of DEFLATE algorithm and related formats (ZLIB, GZIP).
+//!
+//! # Features
+//!
+//! - `std` (enabled by default): Uses `std::io` for the readers and writers, and enables
+//!   the items which require the standard library (e.g., `deflate::ParallelEncoder`).
+//! - `futures-io`: Enables the async encoders and decoders for the `futures-io` traits
+//!   (`deflate::futures`, `zlib::futures` and `gzip::futures`).
+//! - `tokio`: Enables the async encoders and decoders for the `tokio` traits
+//!   (`deflate::tokio`, `zlib::tokio` and `gzip::tokio`).
+//!
+//! Without the `std` feature, this crate depends only on `core` and `alloc`.
+//! In that case, `libflate::io` provides the minimal replacement of `std::io`, and
+//! the slice-based functions (e.g., `deflate::compress_into` and `deflate::decompress_into`)
+//! are available as well as the encoders and decoders.
+//! With the `std` feature, `libflate::io` is a re-export of `std::io`,
+//! so code which refers to the I/O traits through `libflate::io` works in both configurations.
 #![warn(missing_docs)]
 #![cfg_attr(feature = "cargo-clippy", allow(inline_always))]
+#![cfg_attr(not(feature = "std"), no_std)]
 extern crate adler32;
+#[cfg_attr(not(feature = "std"), macro_use)]
+extern crate alloc;
 extern crate byteorder;
+#[cfg(feature = "std")]
+extern crate core;
 extern crate crc;
+#[cfg(all(test, feature = "futures-io"))]
+extern crate futures;
+#[cfg(feature = "futures-io")]
+extern crate futures_io;
+#[cfg(feature = "tokio")]
+extern crate tokio;
 
 pub use finish::Finish;
 
 macro_rules! invalid_data_error {
     ($fmt:expr) => { invalid_data_error!("{}", $fmt) };
     ($fmt:expr, $($arg:tt)*) => {
-        ::std::io::Error::new(::std::io::ErrorKind::InvalidData, format!($fmt, $($arg)*))
+        ::io::Error::new(::io::ErrorKind::InvalidData, format!($fmt, $($arg)*))
     }
 }
 
@@ -30,6 +57,31 @@ pub mod lz77;
 pub mod non_blocking;
 pub mod zlib;
 
+#[cfg(not(feature = "std"))]
+pub mod io;
+#[cfg(feature = "std")]
+pub mod io {
+    //! The I/O traits and types used by the encoders and decoders.
+    //!
+    //! This is a re-export of `std::io` (a minimal replacement is provided instead without the `std` feature).
+    //!
+    //! # Examples
+    //! ```
+    //! use libflate::deflate::Decoder;
+    //! use libflate::io::Read;
+    //!
+    //! let encoded_data = [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0];
+    //! let mut decoded_data = Vec::new();
+    //! Decoder::new(&encoded_data[..]).read_to_end(&mut decoded_data).unwrap();
+    //! assert_eq!(decoded_data, b"Hello World!");
+    //! ```
+    pub(crate) use byteorder::{ReadBytesExt, WriteBytesExt};
+    pub use std::io::*;
+}
+
+mod adapter;
+#[cfg(any(feature = "futures-io", feature = "tokio"))]
+mod async_io;
 mod bit;
 mod checksum;
 mod huffman;
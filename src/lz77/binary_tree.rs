@@ -0,0 +1,341 @@
+use alloc::vec::Vec;
+use core::cmp;
+
+use super::keep_history;
+use super::match_length;
+use super::Code;
+use super::CompressionLevel;
+use super::Lz77Encode;
+use super::Sink;
+
+const MIN_LENGTH: usize = 3;
+const HASH_BITS: u32 = 16;
+const HASH_SIZE: usize = 1 << HASH_BITS;
+const CYCLIC_SIZE: usize = super::MAX_WINDOW_SIZE as usize + 1;
+const MAX_DEPTH: usize = 128;
+const NIL: u32 = u32::MAX;
+
+/// A `Lz77Encode` implementation which finds matches using binary trees (as the bt4 of LZMA).
+///
+/// The binary trees find the longest match at each position,
+/// and the parsing evaluates the match at the next position before emitting a pointer.
+///
+/// This is considerably slower than `DefaultLz77Encoder`.
+/// It is suitable for data which is compressed once and decompressed many times.
+#[derive(Debug, Clone)]
+pub struct BinaryTreeLz77Encoder {
+    // The first `history_len` bytes of `buf` have already been encoded,
+    // and are kept to be referred by the succeeding data.
+    buf: Vec<u8>,
+    history_len: usize,
+    tree: BinaryTree,
+    matches: Vec<Match>,
+}
+impl BinaryTreeLz77Encoder {
+    /// Makes a new encoder instance.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::deflate;
+    /// use libflate::lz77::{BinaryTreeLz77Encoder, CompressionLevel, Lz77Encode};
+    ///
+    /// let lz77 = BinaryTreeLz77Encoder::new();
+    /// assert_eq!(lz77.compression_level(), CompressionLevel::Best);
+    ///
+    /// let options = deflate::EncodeOptions::with_lz77(lz77);
+    /// let _deflate = deflate::Encoder::with_options(Vec::new(), options);
+    /// ```
+    pub fn new() -> Self {
+        Self::with_window_size(super::MAX_WINDOW_SIZE)
+    }
+
+    /// Makes a new encoder instance with specified window size.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::lz77::{BinaryTreeLz77Encoder, Lz77Encode};
+    ///
+    /// let lz77 = BinaryTreeLz77Encoder::with_window_size(1024);
+    /// assert_eq!(lz77.window_size(), 1024);
+    /// ```
+    pub fn with_window_size(size: u16) -> Self {
+        BinaryTreeLz77Encoder {
+            buf: Vec::new(),
+            history_len: 0,
+            tree: BinaryTree::new(cmp::min(size, super::MAX_WINDOW_SIZE)),
+            matches: Vec::new(),
+        }
+    }
+
+    fn longest_match(&mut self, position: usize) -> Option<Match> {
+        self.matches.clear();
+        self.tree
+            .find_matches(&self.buf, position, &mut self.matches);
+        self.matches.last().cloned()
+    }
+}
+impl Default for BinaryTreeLz77Encoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Lz77Encode for BinaryTreeLz77Encoder {
+    fn encode<S>(&mut self, buf: &[u8], sink: S)
+    where
+        S: Sink,
+    {
+        self.buf.extend_from_slice(buf);
+        if self.buf.len() >= self.tree.window_size * 8 {
+            self.flush(sink);
+        }
+    }
+    fn flush<S>(&mut self, mut sink: S)
+    where
+        S: Sink,
+    {
+        self.tree.clear();
+        let mut i = self.history_len;
+        let mut current = self.longest_match(i);
+        while i < self.buf.len() {
+            let m = match current.take() {
+                None => {
+                    sink.consume(Code::Literal(self.buf[i]));
+                    i += 1;
+                    current = self.longest_match(i);
+                    continue;
+                }
+                Some(m) => m,
+            };
+            if m.length < super::MAX_LENGTH {
+                let next = self.longest_match(i + 1);
+                if next.as_ref().map(|n| n.length).unwrap_or(0) > m.length {
+                    sink.consume(Code::Literal(self.buf[i]));
+                    i += 1;
+                    current = next;
+                    continue;
+                }
+            }
+            sink.consume(Code::Pointer {
+                length: m.length,
+                backward_distance: m.distance,
+            });
+            i += m.length as usize;
+            current = self.longest_match(i);
+        }
+        self.history_len = keep_history(&mut self.buf, self.tree.window_size);
+    }
+    fn reset(&mut self) {
+        self.buf.clear();
+        self.history_len = 0;
+    }
+    fn set_dictionary(&mut self, dictionary: &[u8]) {
+        self.buf.clear();
+        self.buf.extend_from_slice(dictionary);
+        self.history_len = keep_history(&mut self.buf, self.tree.window_size);
+    }
+    fn compression_level(&self) -> CompressionLevel {
+        CompressionLevel::Best
+    }
+    fn window_size(&self) -> u16 {
+        self.tree.window_size as u16
+    }
+}
+
+/// A match found by `BinaryTree`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Match {
+    pub length: u16,
+    pub distance: u16,
+}
+
+/// Binary search trees of the suffixes in the window.
+///
+/// Each hash bucket of the 3-byte prefixes has a tree which is ordered lexicographically by the suffixes.
+/// A new position becomes the root of the tree, and the old nodes are re-linked into
+/// its left and right subtrees while descending.
+/// All the nodes visited on the way are the candidates of matches.
+#[derive(Debug, Clone)]
+pub(super) struct BinaryTree {
+    head: Vec<u32>,
+    son: Vec<u32>,
+    window_size: usize,
+    next: usize,
+}
+impl BinaryTree {
+    pub fn new(window_size: u16) -> Self {
+        BinaryTree {
+            head: Vec::new(),
+            son: Vec::new(),
+            window_size: window_size as usize,
+            next: 0,
+        }
+    }
+
+    /// Removes all the registered positions.
+    pub fn clear(&mut self) {
+        self.head.clear();
+        self.head.resize(HASH_SIZE, NIL);
+        if self.son.is_empty() {
+            self.son.resize(CYCLIC_SIZE * 2, NIL);
+        }
+        self.next = 0;
+    }
+
+    /// Registers `position` and appends the matches at the position to `matches`.
+    ///
+    /// The matches are appended in increasing order of length,
+    /// and each of them has the shortest distance among the matches of the same length.
+    ///
+    /// The positions must be given in increasing order.
+    /// The positions skipped since the last call are registered without searching.
+    pub fn find_matches(&mut self, buf: &[u8], position: usize, matches: &mut Vec<Match>) {
+        if position < self.next {
+            return;
+        }
+        while self.next < position {
+            let i = self.next;
+            self.update(buf, i, None);
+        }
+        self.update(buf, position, Some(matches));
+    }
+
+    fn update(&mut self, buf: &[u8], position: usize, mut matches: Option<&mut Vec<Match>>) {
+        self.next = position + 1;
+        let len_limit = cmp::min(super::MAX_LENGTH as usize, buf.len() - position);
+        if len_limit < MIN_LENGTH {
+            return;
+        }
+
+        let hash = hash(&buf[position..]);
+        let mut current = self.head[hash];
+        self.head[hash] = position as u32;
+
+        let cyclic_pos = position % CYCLIC_SIZE;
+        let mut ptr0 = cyclic_pos * 2 + 1;
+        let mut ptr1 = cyclic_pos * 2;
+        let mut len0 = 0;
+        let mut len1 = 0;
+        let mut max_len = MIN_LENGTH - 1;
+        let mut depth = MAX_DEPTH;
+        while current != NIL && depth > 0 {
+            let j = current as usize;
+            let delta = position - j;
+            if delta > self.window_size {
+                break;
+            }
+            depth -= 1;
+
+            let pair = ((cyclic_pos + CYCLIC_SIZE - delta) % CYCLIC_SIZE) * 2;
+            let mut len = cmp::min(len0, len1);
+            if buf[j + len] == buf[position + len] {
+                len += match_length(buf, position + len, j + len, len_limit - len);
+                if len > max_len {
+                    max_len = len;
+                    if let Some(ref mut matches) = matches {
+                        matches.push(Match {
+                            length: len as u16,
+                            distance: delta as u16,
+                        });
+                    }
+                    if len == len_limit {
+                        self.son[ptr1] = self.son[pair];
+                        self.son[ptr0] = self.son[pair + 1];
+                        return;
+                    }
+                }
+            }
+            if buf[j + len] < buf[position + len] {
+                self.son[ptr1] = current;
+                ptr1 = pair + 1;
+                current = self.son[ptr1];
+                len1 = len;
+            } else {
+                self.son[ptr0] = current;
+                ptr0 = pair;
+                current = self.son[ptr0];
+                len0 = len;
+            }
+        }
+        self.son[ptr0] = NIL;
+        self.son[ptr1] = NIL;
+    }
+}
+
+#[inline]
+fn hash(buf: &[u8]) -> usize {
+    let key = u32::from(buf[0]) | u32::from(buf[1]) << 8 | u32::from(buf[2]) << 16;
+    (key.wrapping_mul(0x1E35_A7BD) >> (32 - HASH_BITS)) as usize
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::string::String;
+    use deflate;
+    use io::{Read, Write};
+    use lz77::DefaultLz77Encoder;
+
+    fn encode<E: Lz77Encode>(lz77: E, buf: &[u8]) -> Vec<u8> {
+        let options = deflate::EncodeOptions::with_lz77(lz77);
+        let mut encoder = deflate::Encoder::with_options(Vec::new(), options);
+        encoder.write_all(buf).unwrap();
+        encoder.finish().into_result().unwrap()
+    }
+
+    fn decode(buf: &[u8]) -> Vec<u8> {
+        let mut decoder = deflate::Decoder::new(buf);
+        let mut decoded = Vec::new();
+        decoder.read_to_end(&mut decoded).unwrap();
+        decoded
+    }
+
+    #[test]
+    fn find_matches_works() {
+        let buf = b"abcdefgh_abcd_abcdefghij";
+        let mut tree = BinaryTree::new(super::super::MAX_WINDOW_SIZE);
+        tree.clear();
+
+        let mut matches = Vec::new();
+        tree.find_matches(buf, 14, &mut matches);
+        assert_eq!(
+            matches,
+            [
+                Match {
+                    length: 4,
+                    distance: 5,
+                },
+                Match {
+                    length: 8,
+                    distance: 14,
+                },
+            ]
+        );
+
+        let mut tree = BinaryTree::new(8);
+        tree.clear();
+
+        matches.clear();
+        tree.find_matches(buf, 14, &mut matches);
+        assert_eq!(
+            matches,
+            [Match {
+                length: 4,
+                distance: 5,
+            }]
+        );
+    }
+
+    #[test]
+    fn encode_and_decode_works() {
+        let plain = (0..100_000)
+            .map(|i| format!("{} {} ", i % 907, i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let encoded = encode(BinaryTreeLz77Encoder::new(), &plain);
+        assert_eq!(decode(&encoded), plain);
+        assert!(encoded.len() <= encode(DefaultLz77Encoder::with_level(9), &plain).len());
+
+        let encoded = encode(BinaryTreeLz77Encoder::with_window_size(256), &plain);
+        assert_eq!(decode(&encoded), plain);
+    }
+}
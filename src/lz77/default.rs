@@ -1,15 +1,180 @@
-use std::cmp;
-use std::collections::HashMap;
+use alloc::vec::Vec;
+use core::cmp;
 
+use super::keep_history;
+use super::match_length;
 use super::Code;
+use super::CompressionLevel;
 use super::Lz77Encode;
 use super::Sink;
 
+const DEFAULT_LEVEL: u8 = 6;
+const MAX_LEVEL: u8 = 9;
+
+/// Tuning parameters of the match finder for each compression level (same as zlib).
+///
+/// `(good_length, max_lazy, nice_length, max_chain)`
+const LEVEL_PARAMS: [(u16, u16, u16, u16); 10] = [
+    (0, 0, 0, 0),
+    (4, 0, 8, 4),
+    (4, 0, 16, 8),
+    (4, 0, 32, 32),
+    (4, 4, 16, 16),
+    (8, 16, 32, 32),
+    (8, 16, 128, 128),
+    (8, 32, 128, 256),
+    (32, 128, 258, 1024),
+    (32, 258, 258, 4096),
+];
+
+/// Tuning parameters of the match finder of `DefaultLz77Encoder`.
+///
+/// The meanings of the parameters are the same as those of zlib.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MatchParams {
+    /// Maximum number of candidates examined for a match.
+    ///
+    /// `0` disables pointer emission.
+    pub max_chain: u16,
+
+    /// Match length at which the search for a longer match is stopped.
+    pub nice_length: u16,
+
+    /// Match length from which the number of candidates examined for a better match
+    /// is reduced to a quarter.
+    pub good_length: u16,
+
+    /// Match length below which a longer match starting at the next position is looked for.
+    ///
+    /// `0` disables the search.
+    pub max_lazy: u16,
+
+    /// If `true`, a match starting two positions ahead is also examined
+    /// when the next position does not give a longer match.
+    ///
+    /// This is disabled in all the presets.
+    pub two_step_lazy: bool,
+}
+impl MatchParams {
+    /// Returns the preset parameters of the compression level `level`.
+    ///
+    /// `0` means no compression (only literals are emitted), and `9` means the best compression.
+    /// Levels above `9` are treated as `9`.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::lz77::MatchParams;
+    ///
+    /// assert_eq!(MatchParams::from_level(0).max_chain, 0);
+    /// assert_eq!(MatchParams::from_level(9).nice_length, 258);
+    /// assert_eq!(MatchParams::from_level(10), MatchParams::from_level(9));
+    /// ```
+    pub fn from_level(level: u8) -> Self {
+        let (good_length, max_lazy, nice_length, max_chain) =
+            LEVEL_PARAMS[cmp::min(level, MAX_LEVEL) as usize];
+        MatchParams {
+            max_chain,
+            nice_length,
+            good_length,
+            max_lazy,
+            two_step_lazy: false,
+        }
+    }
+}
+impl Default for MatchParams {
+    fn default() -> Self {
+        Self::from_level(DEFAULT_LEVEL)
+    }
+}
+
+/// A builder of `DefaultLz77Encoder`.
+///
+/// # Examples
+/// ```
+/// use libflate::deflate;
+/// use libflate::lz77::{CompressionLevel, DefaultLz77EncoderBuilder, Lz77Encode, MatchParams};
+///
+/// let mut params = MatchParams::from_level(9);
+/// params.max_chain = 1024;
+/// let lz77 = DefaultLz77EncoderBuilder::new().level(9).params(params).finish();
+/// assert_eq!(lz77.compression_level(), CompressionLevel::Best);
+///
+/// let options = deflate::EncodeOptions::with_lz77(lz77);
+/// let _deflate = deflate::Encoder::with_options(Vec::new(), options);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DefaultLz77EncoderBuilder {
+    window_size: u16,
+    level: u8,
+    params: MatchParams,
+}
+impl DefaultLz77EncoderBuilder {
+    /// Makes a new builder instance.
+    ///
+    /// The default compression level is `6` and the default window size is `MAX_WINDOW_SIZE`.
+    pub fn new() -> Self {
+        DefaultLz77EncoderBuilder {
+            window_size: super::MAX_WINDOW_SIZE,
+            level: DEFAULT_LEVEL,
+            params: MatchParams::default(),
+        }
+    }
+
+    /// Sets the window size.
+    ///
+    /// If `size` exceeds `MAX_WINDOW_SIZE`, `MAX_WINDOW_SIZE` will be used instead.
+    pub fn window_size(&mut self, size: u16) -> &mut Self {
+        self.window_size = cmp::min(size, super::MAX_WINDOW_SIZE);
+        self
+    }
+
+    /// Sets the compression level (`0..=9`) and resets the match finder parameters to its preset.
+    ///
+    /// See `MatchParams::from_level` for more details.
+    pub fn level(&mut self, level: u8) -> &mut Self {
+        self.level = cmp::min(level, MAX_LEVEL);
+        self.params = MatchParams::from_level(self.level);
+        self
+    }
+
+    /// Overrides the match finder parameters.
+    ///
+    /// The compression level reported by the encoder is unchanged.
+    pub fn params(&mut self, params: MatchParams) -> &mut Self {
+        self.params = params;
+        self
+    }
+
+    /// Builds a `DefaultLz77Encoder` instance.
+    pub fn finish(&self) -> DefaultLz77Encoder {
+        DefaultLz77Encoder {
+            window_size: self.window_size,
+            level: self.level,
+            params: self.params.clone(),
+            buf: Vec::new(),
+            history_len: 0,
+            chain: HashChain::new(),
+        }
+    }
+}
+impl Default for DefaultLz77EncoderBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// A `Lz77Encode` implementation used by default.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DefaultLz77Encoder {
     window_size: u16,
+    level: u8,
+    params: MatchParams,
+
+    // The first `history_len` bytes of `buf` have already been encoded,
+    // and are kept to be referred by the succeeding data.
     buf: Vec<u8>,
+    history_len: usize,
+    chain: HashChain,
 }
 impl DefaultLz77Encoder {
     /// Makes a new encoder instance.
@@ -26,7 +191,7 @@ impl DefaultLz77Encoder {
     /// let _deflate = deflate::Encoder::with_options(Vec::new(), options);
     /// ```
     pub fn new() -> Self {
-        Self::with_window_size(super::MAX_WINDOW_SIZE)
+        DefaultLz77EncoderBuilder::new().finish()
     }
 
     /// Makes a new encoder instance with specified window size.
@@ -46,10 +211,32 @@ impl DefaultLz77Encoder {
     /// let _deflate = deflate::Encoder::with_options(Vec::new(), options);
     /// ```
     pub fn with_window_size(size: u16) -> Self {
-        DefaultLz77Encoder {
-            window_size: cmp::min(size, super::MAX_WINDOW_SIZE),
-            buf: Vec::new(),
-        }
+        DefaultLz77EncoderBuilder::new().window_size(size).finish()
+    }
+
+    /// Makes a new encoder instance with specified compression level.
+    ///
+    /// See `DefaultLz77EncoderBuilder::level` for more details.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::lz77::{CompressionLevel, DefaultLz77Encoder, Lz77Encode};
+    ///
+    /// let lz77 = DefaultLz77Encoder::with_level(1);
+    /// assert_eq!(lz77.compression_level(), CompressionLevel::Fast);
+    /// ```
+    pub fn with_level(level: u8) -> Self {
+        DefaultLz77EncoderBuilder::new().level(level).finish()
+    }
+
+    /// Returns the numeric compression level (`0..=9`) of the encoder.
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    /// Returns the match finder parameters of the encoder.
+    pub fn params(&self) -> &MatchParams {
+        &self.params
     }
 }
 impl Default for DefaultLz77Encoder {
@@ -71,111 +258,368 @@ impl Lz77Encode for DefaultLz77Encoder {
     where
         S: Sink,
     {
-        let mut prefix_table = PrefixTable::new(self.buf.len());
-        let mut i = 0;
-        let end = cmp::max(3, self.buf.len()) - 3;
+        let end = if self.params.max_chain == 0 {
+            0
+        } else {
+            cmp::max(3, self.buf.len()) - 3
+        };
+        let params = &self.params;
+        let mut finder = MatchFinder::new(
+            &self.buf,
+            end,
+            self.window_size,
+            params.nice_length,
+            &mut self.chain,
+        );
+        let lazy_chain = |m: &Match| {
+            if m.length >= params.good_length {
+                params.max_chain >> 2
+            } else {
+                params.max_chain
+            }
+        };
+
+        let mut i = self.history_len;
+        let mut current = finder.find(i, params.max_chain);
         while i < end {
-            let key = prefix(&self.buf[i..]);
-            let matched = prefix_table.insert(key, i as u32);
-            if let Some(j) = matched.map(|j| j as usize) {
-                let distance = i - j;
-                if distance <= self.window_size as usize {
-                    let length = 3 + longest_common_prefix(&self.buf, i + 3, j + 3);
-                    sink.consume(Code::Pointer {
-                        length,
-                        backward_distance: distance as u16,
-                    });
-                    for k in (i..).take(length as usize).skip(1) {
-                        prefix_table.insert(prefix(&self.buf[k..]), k as u32);
-                    }
-                    i += length as usize;
+            let m = match current.take() {
+                None => {
+                    sink.consume(Code::Literal(self.buf[i]));
+                    i += 1;
+                    current = finder.find(i, params.max_chain);
+                    continue;
+                }
+                Some(m) => m,
+            };
+            if m.length < params.max_lazy {
+                let next = finder.find(i + 1, lazy_chain(&m));
+                if next.as_ref().map(|n| n.length).unwrap_or(0) > m.length {
+                    sink.consume(Code::Literal(self.buf[i]));
+                    i += 1;
+                    current = next;
                     continue;
                 }
+                if params.two_step_lazy {
+                    let next = finder.find(i + 2, lazy_chain(&m));
+                    if next.as_ref().map(|n| n.length).unwrap_or(0) > m.length + 1 {
+                        sink.consume(Code::Literal(self.buf[i]));
+                        sink.consume(Code::Literal(self.buf[i + 1]));
+                        i += 2;
+                        current = next;
+                        continue;
+                    }
+                }
             }
-            sink.consume(Code::Literal(self.buf[i]));
-            i += 1;
+            sink.consume(Code::Pointer {
+                length: m.length,
+                backward_distance: m.distance,
+            });
+            i += m.length as usize;
+            current = finder.find(i, params.max_chain);
         }
         for b in &self.buf[i..] {
             sink.consume(Code::Literal(*b));
         }
+        self.history_len = keep_history(&mut self.buf, self.window_size as usize);
+    }
+    fn reset(&mut self) {
         self.buf.clear();
+        self.history_len = 0;
+    }
+    fn set_dictionary(&mut self, dictionary: &[u8]) {
+        self.buf.clear();
+        self.buf.extend_from_slice(dictionary);
+        self.history_len = keep_history(&mut self.buf, self.window_size as usize);
+    }
+    fn compression_level(&self) -> CompressionLevel {
+        match self.level {
+            0 => CompressionLevel::None,
+            1..=3 => CompressionLevel::Fast,
+            4..=6 => CompressionLevel::Balance,
+            _ => CompressionLevel::Best,
+        }
     }
     fn window_size(&self) -> u16 {
         self.window_size
     }
 }
 
-#[inline]
-fn prefix(buf: &[u8]) -> [u8; 3] {
-    unsafe {
-        [
-            *buf.get_unchecked(0),
-            *buf.get_unchecked(1),
-            *buf.get_unchecked(2),
-        ]
+const HASH_BITS: u32 = 15;
+const HASH_SIZE: usize = 1 << HASH_BITS;
+const CHAIN_MASK: usize = super::MAX_WINDOW_SIZE as usize - 1;
+const NIL: u32 = u32::MAX;
+
+/// Hash chains of the 3-byte prefixes in the window.
+///
+/// `head` holds the most recent position of each hash value,
+/// and `prev` links each position to the preceding position which has the same hash value.
+#[derive(Debug, Clone)]
+struct HashChain {
+    head: Vec<u32>,
+    prev: Vec<u32>,
+}
+impl HashChain {
+    fn new() -> Self {
+        HashChain {
+            head: Vec::new(),
+            prev: Vec::new(),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.head.clear();
+        self.head.resize(HASH_SIZE, NIL);
+        if self.prev.is_empty() {
+            self.prev.resize(CHAIN_MASK + 1, NIL);
+        }
+    }
+
+    #[inline]
+    fn insert(&mut self, hash: usize, position: u32) -> u32 {
+        let head = self.head[hash];
+        self.prev[position as usize & CHAIN_MASK] = head;
+        self.head[hash] = position;
+        head
+    }
+
+    #[inline]
+    fn prev(&self, position: u32) -> u32 {
+        self.prev[position as usize & CHAIN_MASK]
     }
 }
 
-#[inline]
-fn longest_common_prefix(buf: &[u8], i: usize, j: usize) -> u16 {
-    buf[i..]
-        .iter()
-        .take(super::MAX_LENGTH as usize - 3)
-        .zip(&buf[j..])
-        .take_while(|&(x, y)| x == y)
-        .count() as u16
+#[derive(Debug)]
+struct Match {
+    length: u16,
+    distance: u16,
 }
 
 #[derive(Debug)]
-enum PrefixTable {
-    Small(HashMap<[u8; 3], u32>),
-    Large(LargePrefixTable),
-}
-impl PrefixTable {
-    fn new(bytes: usize) -> Self {
-        if bytes < super::MAX_WINDOW_SIZE as usize {
-            PrefixTable::Small(HashMap::new())
+struct MatchFinder<'a> {
+    buf: &'a [u8],
+    end: usize,
+    window_size: usize,
+    nice_length: usize,
+    chain: &'a mut HashChain,
+    inserted: usize,
+}
+impl<'a> MatchFinder<'a> {
+    fn new(
+        buf: &'a [u8],
+        end: usize,
+        window_size: u16,
+        nice_length: u16,
+        chain: &'a mut HashChain,
+    ) -> Self {
+        chain.clear();
+        MatchFinder {
+            buf,
+            end,
+            window_size: window_size as usize,
+            nice_length: nice_length as usize,
+            chain,
+            inserted: 0,
+        }
+    }
+
+    /// Returns the longest match at `position`, examining up to `max_chain` candidates.
+    ///
+    /// The positions before `position` which have not been registered yet are also registered.
+    fn find(&mut self, position: usize, max_chain: u16) -> Option<Match> {
+        if position >= self.end {
+            return None;
+        }
+        self.skip(position);
+        if self.inserted > position {
+            // Already registered by a preceding lookahead
+            return None;
+        }
+        let mut candidate = self.insert(position);
+        let mut chain = max_chain;
+        let max_length = cmp::min(super::MAX_LENGTH as usize, self.buf.len() - position);
+        let mut best_length = 2;
+        let mut best_distance = 0;
+        while candidate != NIL && chain > 0 {
+            let j = candidate as usize;
+            let distance = position - j;
+            if distance > self.window_size {
+                break;
+            }
+            if self.buf[j + best_length] == self.buf[position + best_length] {
+                let length = match_length(self.buf, position, j, max_length);
+                if length > best_length {
+                    best_length = length;
+                    best_distance = distance;
+                    if length >= cmp::min(self.nice_length, max_length) {
+                        break;
+                    }
+                }
+            }
+            chain -= 1;
+
+            let next = self.chain.prev(candidate);
+            if next >= candidate {
+                // The link has been overwritten by a newer position
+                break;
+            }
+            candidate = next;
+        }
+        if best_distance == 0 {
+            None
         } else {
-            PrefixTable::Large(LargePrefixTable::new())
+            Some(Match {
+                length: best_length as u16,
+                distance: best_distance as u16,
+            })
         }
     }
 
-    #[inline]
-    fn insert(&mut self, prefix: [u8; 3], position: u32) -> Option<u32> {
-        match *self {
-            PrefixTable::Small(ref mut x) => x.insert(prefix, position),
-            PrefixTable::Large(ref mut x) => x.insert(prefix, position),
+    fn skip(&mut self, position: usize) {
+        while self.inserted < cmp::min(position, self.end) {
+            let i = self.inserted;
+            self.insert(i);
         }
     }
+
+    #[inline]
+    fn insert(&mut self, position: usize) -> u32 {
+        self.inserted = position + 1;
+        self.chain
+            .insert(hash(&self.buf[position..]), position as u32)
+    }
 }
 
-#[derive(Debug)]
-struct LargePrefixTable {
-    table: Vec<Vec<(u8, u32)>>,
+#[inline]
+fn hash(buf: &[u8]) -> usize {
+    let key = u32::from(buf[0]) | u32::from(buf[1]) << 8 | u32::from(buf[2]) << 16;
+    (key.wrapping_mul(0x1E35_A7BD) >> (32 - HASH_BITS)) as usize
 }
-impl LargePrefixTable {
-    fn new() -> Self {
-        LargePrefixTable {
-            table: (0..0xFFFF + 1).map(|_| Vec::new()).collect(),
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    impl Sink for Vec<Code> {
+        fn consume(&mut self, code: Code) {
+            self.push(code);
         }
     }
 
-    #[inline]
-    fn insert(&mut self, prefix: [u8; 3], position: u32) -> Option<u32> {
-        let p0 = prefix[0] as usize;
-        let p1 = prefix[1] as usize;
-        let p2 = prefix[2];
-
-        let i = (p0 << 8) + p1;
-        let positions = unsafe { self.table.get_unchecked_mut(i) };
-        for &mut (key, ref mut value) in positions.iter_mut() {
-            if key == p2 {
-                let old = *value;
-                *value = position;
-                return Some(old);
+    fn encode(lz77: &mut DefaultLz77Encoder, buf: &[u8]) -> Vec<Code> {
+        let mut codes = Vec::new();
+        lz77.encode(buf, &mut codes);
+        lz77.flush(&mut codes);
+        codes
+    }
+
+    #[test]
+    fn history_is_kept_across_flushes() {
+        let mut lz77 = DefaultLz77Encoder::new();
+        let codes = encode(&mut lz77, b"Hello World!");
+        assert_eq!(codes.len(), 12);
+
+        let codes = encode(&mut lz77, b"Hello World!");
+        assert_eq!(
+            codes,
+            [Code::Pointer {
+                length: 12,
+                backward_distance: 12
+            }]
+        );
+
+        lz77.reset();
+        let codes = encode(&mut lz77, b"Hello World!");
+        assert_eq!(codes.len(), 12);
+
+        let mut lz77 = DefaultLz77Encoder::with_window_size(8);
+        encode(&mut lz77, b"Hello World!");
+        let codes = encode(&mut lz77, b"Hello World!");
+        assert_eq!(codes.len(), 12);
+    }
+
+    #[test]
+    fn lazy_matching_works() {
+        let input = b"abcd_bcdefg_abcdefg";
+
+        let codes = encode(&mut DefaultLz77Encoder::with_level(1), input);
+        assert_eq!(
+            codes[10],
+            Code::Pointer {
+                length: 4,
+                backward_distance: 12
             }
-        }
-        positions.push((p2, position));
-        None
+        );
+
+        let codes = encode(&mut DefaultLz77Encoder::with_level(6), input);
+        assert_eq!(codes[10], Code::Literal(b'a'));
+        assert_eq!(
+            codes[11],
+            Code::Pointer {
+                length: 6,
+                backward_distance: 8
+            }
+        );
+        assert_eq!(codes.len(), 12);
+    }
+
+    #[test]
+    fn multiple_candidates_are_examined() {
+        let input = b"abcdefgh1abc2abcdefgh";
+
+        let mut params = MatchParams::from_level(1);
+        params.max_chain = 1;
+        let mut lz77 = DefaultLz77EncoderBuilder::new().params(params).finish();
+        let codes = encode(&mut lz77, input);
+        assert_eq!(
+            codes[11],
+            Code::Pointer {
+                length: 3,
+                backward_distance: 4
+            }
+        );
+
+        let codes = encode(&mut DefaultLz77Encoder::with_level(1), input);
+        assert_eq!(
+            codes[11],
+            Code::Pointer {
+                length: 8,
+                backward_distance: 13
+            }
+        );
+    }
+
+    #[test]
+    fn two_step_lazy_matching_works() {
+        let input = b"abcd_cdefgh_abcdefgh";
+        let mut params = MatchParams::from_level(6);
+
+        let codes = encode(
+            &mut DefaultLz77EncoderBuilder::new()
+                .params(params.clone())
+                .finish(),
+            input,
+        );
+        assert_eq!(
+            codes[12],
+            Code::Pointer {
+                length: 4,
+                backward_distance: 12
+            }
+        );
+
+        params.two_step_lazy = true;
+        let codes = encode(
+            &mut DefaultLz77EncoderBuilder::new().params(params).finish(),
+            input,
+        );
+        assert_eq!(codes[12], Code::Literal(b'a'));
+        assert_eq!(codes[13], Code::Literal(b'b'));
+        assert_eq!(
+            codes[14],
+            Code::Pointer {
+                length: 6,
+                backward_distance: 9
+            }
+        );
     }
 }
@@ -1,9 +1,17 @@
 //! The interface and implementations of LZ77 compression algorithm.
 //!
 //! LZ77 is a compression algorithm used in [DEFLATE](https://tools.ietf.org/html/rfc1951).
-pub use self::default::DefaultLz77Encoder;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, LittleEndian};
+use core::cmp;
 
+pub use self::binary_tree::BinaryTreeLz77Encoder;
+pub use self::default::{DefaultLz77Encoder, DefaultLz77EncoderBuilder, MatchParams};
+pub use self::optimal::OptimalLz77Encoder;
+
+mod binary_tree;
 mod default;
+mod optimal;
 
 /// Maximum length of sharable bytes in a pointer.
 pub const MAX_LENGTH: u16 = 258;
@@ -74,6 +82,24 @@ pub trait Lz77Encode {
     where
         S: Sink;
 
+    /// Discards the history held by the encoder.
+    ///
+    /// After this call, `encode` must not produce pointers that refer to the data given before it.
+    /// This is called by the DEFLATE encoder at a full flush, after `flush` has been invoked.
+    ///
+    /// If the implementation is omitted, nothing will be done.
+    fn reset(&mut self) {}
+
+    /// Sets the preset dictionary.
+    ///
+    /// The dictionary replaces the history, so that the succeeding data can refer to it.
+    /// This is called by the DEFLATE encoder only when the encoder has no pending data
+    /// (i.e., before `encode` is invoked or just after `flush` is invoked).
+    ///
+    /// If the implementation is omitted, the dictionary will be ignored.
+    #[allow(unused_variables)]
+    fn set_dictionary(&mut self, dictionary: &[u8]) {}
+
     /// Returns the compression level of the encoder.
     ///
     /// If the implementation is omitted, `CompressionLevel::Balance` will be returned.
@@ -90,7 +116,7 @@ pub trait Lz77Encode {
 }
 
 /// A no compression implementation of `LZ77Encode` trait.
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct NoCompressionLz77Encoder;
 impl NoCompressionLz77Encoder {
     /// Makes a new encoder instance.
@@ -129,3 +155,45 @@ impl Lz77Encode for NoCompressionLz77Encoder {
         CompressionLevel::None
     }
 }
+
+/// Drops the bytes of `buf` except the last `window_size` bytes, and returns the length of the rest.
+///
+/// The rest is kept as the history which the succeeding data can refer.
+fn keep_history(buf: &mut Vec<u8>, window_size: usize) -> usize {
+    let history_len = cmp::min(buf.len(), window_size);
+    let obsolete_len = buf.len() - history_len;
+    buf.drain(..obsolete_len);
+    history_len
+}
+
+/// Returns the length of the common prefix of `buf[i..]` and `buf[j..]` (up to `max_length`).
+///
+/// The bytes are compared in word-sized steps.
+#[inline]
+fn match_length(buf: &[u8], i: usize, j: usize, max_length: usize) -> usize {
+    let mut n = 0;
+    while n + 8 <= max_length {
+        let x = LittleEndian::read_u64(&buf[i + n..]) ^ LittleEndian::read_u64(&buf[j + n..]);
+        if x != 0 {
+            return n + (x.trailing_zeros() / 8) as usize;
+        }
+        n += 8;
+    }
+    while n < max_length && buf[i + n] == buf[j + n] {
+        n += 1;
+    }
+    n
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn match_length_works() {
+        let buf = b"0123456789abcdef_0123456789abcdeF_";
+        assert_eq!(match_length(buf, 17, 0, 17), 15);
+        assert_eq!(match_length(buf, 17, 0, 7), 7);
+        assert_eq!(match_length(buf, 17, 1, 17), 0);
+    }
+}
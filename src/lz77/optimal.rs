@@ -0,0 +1,403 @@
+use alloc::vec::Vec;
+use core::cmp;
+use core::f64;
+
+use super::binary_tree::{BinaryTree, Match};
+use super::keep_history;
+use super::Code;
+use super::CompressionLevel;
+use super::Lz77Encode;
+use super::Sink;
+use deflate::symbol::{Histogram, Symbol};
+use util;
+
+const DEFAULT_ITERATIONS: usize = 15;
+const MIN_LENGTH: usize = 3;
+const LITERAL_CODE_COUNT: usize = 286;
+const DISTANCE_CODE_COUNT: usize = 30;
+
+/// A `Lz77Encode` implementation which chooses the codes by iterative optimal parsing (as Zopfli).
+///
+/// Each iteration finds the cheapest sequence of codes under a bit-cost model,
+/// and the statistics of the result are used as the cost model of the next iteration.
+/// The first iteration uses the costs of the fixed Huffman codes.
+///
+/// This is much slower than `BinaryTreeLz77Encoder`.
+/// It is suitable for data which is compressed once and decompressed many times.
+#[derive(Debug, Clone)]
+pub struct OptimalLz77Encoder {
+    iterations: usize,
+
+    // The first `history_len` bytes of `buf` have already been encoded,
+    // and are kept to be referred by the succeeding data.
+    buf: Vec<u8>,
+    history_len: usize,
+    tree: BinaryTree,
+}
+impl OptimalLz77Encoder {
+    /// Makes a new encoder instance.
+    ///
+    /// The number of iterations is `15`.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::gzip;
+    /// use libflate::lz77::{CompressionLevel, Lz77Encode, OptimalLz77Encoder};
+    ///
+    /// let lz77 = OptimalLz77Encoder::new();
+    /// assert_eq!(lz77.compression_level(), CompressionLevel::Best);
+    ///
+    /// let options = gzip::EncodeOptions::with_lz77(lz77);
+    /// let _gzip = gzip::Encoder::with_options(Vec::new(), options);
+    /// ```
+    pub fn new() -> Self {
+        Self::with_iterations(DEFAULT_ITERATIONS)
+    }
+
+    /// Makes a new encoder instance with specified number of iterations.
+    ///
+    /// More iterations may yield smaller output at the cost of encoding time.
+    /// If `iterations` is `0`, it is treated as `1`.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::lz77::OptimalLz77Encoder;
+    ///
+    /// let lz77 = OptimalLz77Encoder::with_iterations(5);
+    /// assert_eq!(lz77.iterations(), 5);
+    /// ```
+    pub fn with_iterations(iterations: usize) -> Self {
+        OptimalLz77Encoder {
+            iterations: cmp::max(1, iterations),
+            buf: Vec::new(),
+            history_len: 0,
+            tree: BinaryTree::new(super::MAX_WINDOW_SIZE),
+        }
+    }
+
+    /// Returns the number of iterations.
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    fn find_all_matches(&mut self) -> MatchTable {
+        let mut table = MatchTable {
+            start: self.history_len,
+            offsets: Vec::with_capacity(self.buf.len() - self.history_len + 1),
+            matches: Vec::new(),
+        };
+        self.tree.clear();
+        table.offsets.push(0);
+        for i in self.history_len..self.buf.len() {
+            self.tree.find_matches(&self.buf, i, &mut table.matches);
+            table.offsets.push(table.matches.len());
+        }
+        table
+    }
+}
+impl Default for OptimalLz77Encoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Lz77Encode for OptimalLz77Encoder {
+    fn encode<S>(&mut self, buf: &[u8], sink: S)
+    where
+        S: Sink,
+    {
+        self.buf.extend_from_slice(buf);
+        if self.buf.len() >= super::MAX_WINDOW_SIZE as usize * 8 {
+            self.flush(sink);
+        }
+    }
+    fn flush<S>(&mut self, mut sink: S)
+    where
+        S: Sink,
+    {
+        let table = self.find_all_matches();
+        let mut model = CostModel::fixed();
+        let mut best: Option<(f64, Vec<Code>)> = None;
+        for _ in 0..self.iterations {
+            let codes = parse(&self.buf, &table, &model);
+            let mut stats = Histogram::new();
+            for code in &codes {
+                stats.add(&to_symbol(code));
+            }
+            stats.add(&Symbol::EndOfBlock);
+            model = CostModel::from_statistics(&stats);
+            let cost = model.total_cost(&stats);
+            if best.as_ref().map(|b| cost < b.0).unwrap_or(true) {
+                best = Some((cost, codes));
+            }
+        }
+        if let Some((_, codes)) = best {
+            for code in codes {
+                sink.consume(code);
+            }
+        }
+        self.history_len = keep_history(&mut self.buf, super::MAX_WINDOW_SIZE as usize);
+    }
+    fn reset(&mut self) {
+        self.buf.clear();
+        self.history_len = 0;
+    }
+    fn set_dictionary(&mut self, dictionary: &[u8]) {
+        self.buf.clear();
+        self.buf.extend_from_slice(dictionary);
+        self.history_len = keep_history(&mut self.buf, super::MAX_WINDOW_SIZE as usize);
+    }
+    fn compression_level(&self) -> CompressionLevel {
+        CompressionLevel::Best
+    }
+}
+
+/// The matches of all the positions to be encoded in a buffer.
+#[derive(Debug)]
+struct MatchTable {
+    start: usize,
+    offsets: Vec<usize>,
+    matches: Vec<Match>,
+}
+impl MatchTable {
+    /// Returns the matches at `position` in increasing order of length.
+    fn get(&self, position: usize) -> &[Match] {
+        let i = position - self.start;
+        &self.matches[self.offsets[i]..self.offsets[i + 1]]
+    }
+}
+
+/// Estimated bit-costs of the codes.
+#[derive(Debug)]
+struct CostModel {
+    literals: [f64; LITERAL_CODE_COUNT],
+    distances: [f64; DISTANCE_CODE_COUNT],
+
+    // The costs of the lengths (including the extra bits) indexed by length.
+    lengths: Vec<f64>,
+}
+impl CostModel {
+    fn fixed() -> Self {
+        let mut literals = [0.0; LITERAL_CODE_COUNT];
+        for (i, c) in literals.iter_mut().enumerate() {
+            *c = match i {
+                0..=143 => 8.0,
+                144..=255 => 9.0,
+                256..=279 => 7.0,
+                _ => 8.0,
+            };
+        }
+        Self::new(literals, [5.0; DISTANCE_CODE_COUNT])
+    }
+    fn from_statistics(stats: &Histogram) -> Self {
+        let mut literals = [0.0; LITERAL_CODE_COUNT];
+        entropy(&stats.literals, &mut literals);
+        let mut distances = [0.0; DISTANCE_CODE_COUNT];
+        entropy(&stats.distances, &mut distances);
+        Self::new(literals, distances)
+    }
+    fn new(literals: [f64; LITERAL_CODE_COUNT], distances: [f64; DISTANCE_CODE_COUNT]) -> Self {
+        let lengths = (0..super::MAX_LENGTH as usize + 1)
+            .map(|length| {
+                if length < MIN_LENGTH {
+                    return f64::INFINITY;
+                }
+                let symbol = Symbol::Share {
+                    length: length as u16,
+                    distance: 1,
+                };
+                let extra = symbol.extra_lengh().map(|(bits, _)| bits).unwrap_or(0);
+                literals[symbol.code() as usize] + f64::from(extra)
+            })
+            .collect();
+        CostModel {
+            literals,
+            distances,
+            lengths,
+        }
+    }
+    fn literal_cost(&self, b: u8) -> f64 {
+        self.literals[b as usize]
+    }
+    fn distance_cost(&self, distance: u16) -> f64 {
+        let symbol = Symbol::Share {
+            length: MIN_LENGTH as u16,
+            distance,
+        };
+        let (code, bits, _) = symbol.distance().expect("Never fails");
+        self.distances[code as usize] + f64::from(bits)
+    }
+    fn total_cost(&self, stats: &Histogram) -> f64 {
+        let literals = stats
+            .literals
+            .iter()
+            .zip(self.literals.iter())
+            .map(|(&n, &c)| n as f64 * c);
+        let distances = stats
+            .distances
+            .iter()
+            .zip(self.distances.iter())
+            .map(|(&n, &c)| n as f64 * c);
+        literals.chain(distances).sum::<f64>() + stats.extra_bits as f64
+    }
+}
+
+/// Sets the information content of each symbol to `costs`.
+///
+/// The symbols which never occur are regarded as occurring less than once.
+fn entropy(counts: &[usize], costs: &mut [f64]) {
+    let total = counts.iter().sum::<usize>() as f64;
+    let log_total = if total > 0.0 { util::log2(total) } else { 0.0 };
+    for (&n, c) in counts.iter().zip(costs.iter_mut()) {
+        *c = if n == 0 {
+            log_total
+        } else {
+            log_total - util::log2(n as f64)
+        };
+    }
+}
+
+/// Finds the cheapest sequence of the codes which represents `buf[table.start..]` under `model`.
+fn parse(buf: &[u8], table: &MatchTable, model: &CostModel) -> Vec<Code> {
+    let mut costs = vec![f64::INFINITY; buf.len() + 1];
+    let mut steps = vec![(0, 0); buf.len() + 1];
+    costs[table.start] = 0.0;
+    for i in table.start..buf.len() {
+        let base = costs[i];
+        let cost = base + model.literal_cost(buf[i]);
+        if cost < costs[i + 1] {
+            costs[i + 1] = cost;
+            steps[i + 1] = (1, 0);
+        }
+
+        let mut min_length = MIN_LENGTH;
+        for m in table.get(i) {
+            let base = base + model.distance_cost(m.distance);
+            let max_length = m.length as usize;
+            for length in min_length..=max_length {
+                let cost = base + model.lengths[length];
+                if cost < costs[i + length] {
+                    costs[i + length] = cost;
+                    steps[i + length] = (length as u16, m.distance);
+                }
+            }
+            min_length = max_length + 1;
+        }
+    }
+
+    let mut codes = Vec::new();
+    let mut i = buf.len();
+    while i > table.start {
+        let (length, distance) = steps[i];
+        i -= length as usize;
+        if length == 1 {
+            codes.push(Code::Literal(buf[i]));
+        } else {
+            codes.push(Code::Pointer {
+                length,
+                backward_distance: distance,
+            });
+        }
+    }
+    codes.reverse();
+    codes
+}
+
+fn to_symbol(code: &Code) -> Symbol {
+    match *code {
+        Code::Literal(b) => Symbol::Literal(b),
+        Code::Pointer {
+            length,
+            backward_distance,
+        } => Symbol::Share {
+            length,
+            distance: backward_distance,
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::string::String;
+    use deflate;
+    use io::{Read, Write};
+    use lz77::{BinaryTreeLz77Encoder, DefaultLz77Encoder};
+
+    fn encode<E: Lz77Encode>(lz77: E, buf: &[u8]) -> Vec<u8> {
+        let options = deflate::EncodeOptions::with_lz77(lz77);
+        let mut encoder = deflate::Encoder::with_options(Vec::new(), options);
+        encoder.write_all(buf).unwrap();
+        encoder.finish().into_result().unwrap()
+    }
+
+    fn decode(buf: &[u8]) -> Vec<u8> {
+        let mut decoder = deflate::Decoder::new(buf);
+        let mut decoded = Vec::new();
+        decoder.read_to_end(&mut decoded).unwrap();
+        decoded
+    }
+
+    #[test]
+    fn parse_works() {
+        let buf = b"abcabcabcabc";
+        let mut lz77 = OptimalLz77Encoder::with_iterations(1);
+        lz77.buf.extend_from_slice(buf);
+        let table = lz77.find_all_matches();
+        let codes = parse(buf, &table, &CostModel::fixed());
+        assert_eq!(
+            codes,
+            [
+                Code::Literal(b'a'),
+                Code::Literal(b'b'),
+                Code::Literal(b'c'),
+                Code::Pointer {
+                    length: 9,
+                    backward_distance: 3,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn encode_and_decode_works() {
+        let plain = (0..50_000)
+            .map(|i| format!("{} {} ", i % 907, i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let encoded = encode(OptimalLz77Encoder::new(), &plain);
+        assert_eq!(decode(&encoded), plain);
+        assert!(encoded.len() < encode(BinaryTreeLz77Encoder::new(), &plain).len());
+
+        let encoded = encode(OptimalLz77Encoder::with_iterations(1), &plain);
+        assert_eq!(decode(&encoded), plain);
+
+        assert_eq!(decode(&encode(OptimalLz77Encoder::new(), b"")), b"");
+    }
+
+    #[test]
+    fn compresses_better_than_default_encoder() {
+        // Pseudo-random sentences of English words
+        let words =
+            "the of and to in a is that for it as was with be by on not he this are or his from at"
+                .split(' ')
+                .collect::<Vec<_>>();
+        let mut x: u32 = 1;
+        let mut text = String::new();
+        for i in 0..20_000 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            text.push_str(words[(x >> 16) as usize % words.len()]);
+            text.push_str(if i % 13 == 12 { ".\n" } else { " " });
+        }
+        let plain = text.into_bytes();
+
+        let encoded = encode(OptimalLz77Encoder::new(), &plain);
+        assert_eq!(decode(&encoded), plain);
+        let default_size = encode(DefaultLz77Encoder::new(), &plain).len();
+        assert!(
+            encoded.len() < default_size * 95 / 100,
+            "optimal={}, default={}",
+            encoded.len(),
+            default_size
+        );
+    }
+}
@@ -1,11 +1,13 @@
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
 use byteorder::LittleEndian;
-use byteorder::ReadBytesExt;
-use std::cmp;
-use std::io;
-use std::io::Read;
-use std::ptr;
+use core::cmp;
+use core::ptr;
 
 use deflate::symbol::{self, HuffmanCodec};
+use io;
+use io::Read;
+use io::ReadBytesExt;
 use lz77;
 use non_blocking::transaction::TransactionalBitReader;
 use util;
@@ -44,6 +46,35 @@ impl<R: Read> Decoder<R> {
         }
     }
 
+    /// Sets the preset dictionary.
+    ///
+    /// The dictionary is regarded as the data preceding the stream,
+    /// so that the back-references in the stream can refer to it.
+    /// It must be the same one as given to the encoder (see `deflate::Encoder::set_dictionary`).
+    ///
+    /// This should be called before reading any data, otherwise unread decoded data is discarded.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Read, Write};
+    /// use libflate::deflate::Encoder;
+    /// use libflate::non_blocking::deflate::Decoder;
+    ///
+    /// let mut encoder = Encoder::new(Vec::new());
+    /// encoder.set_dictionary(b"Hello").unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    ///
+    /// let mut decoder = Decoder::new(&encoded_data[..]);
+    /// decoder.set_dictionary(b"Hello");
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    /// assert_eq!(buf, b"Hello World!");
+    /// ```
+    pub fn set_dictionary(&mut self, dictionary: &[u8]) {
+        self.block_decoder.set_dictionary(dictionary);
+    }
+
     /// Returns the immutable reference to the inner stream.
     pub fn as_inner_ref(&self) -> &R {
         self.bit_reader.as_inner_ref()
@@ -69,6 +100,30 @@ impl<R: Read> Decoder<R> {
         self.bit_reader.into_inner()
     }
 
+    /// Returns the bytes which have been read from the inner stream but not decoded yet.
+    ///
+    /// The inner stream is read in chunks, so these include the bytes following the DEFLATE stream
+    /// (they are not returned to the inner stream by `into_inner`).
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    /// use libflate::non_blocking::deflate::Decoder;
+    ///
+    /// let mut encoded_data = vec![243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0];
+    /// encoded_data.extend_from_slice(b"trailing data");
+    ///
+    /// let mut decoder = Decoder::new(&encoded_data[..]);
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    ///
+    /// assert_eq!(buf, b"Hello World!");
+    /// assert_eq!(decoder.unread_bytes(), b"trailing data");
+    /// ```
+    pub fn unread_bytes(&self) -> Vec<u8> {
+        self.bit_reader.unread_bytes()
+    }
+
     pub(crate) fn bit_reader_mut(&mut self) -> &mut TransactionalBitReader<R> {
         &mut self.bit_reader
     }
@@ -100,9 +155,9 @@ impl<R: Read> Read for Decoder<R> {
                 }
                 DecoderState::ReadNonCompressedBlockLen => {
                     let len = self.bit_reader.transaction(|r| {
-                        r.reset();
-                        let len = r.as_inner_mut().read_u16::<LittleEndian>()?;
-                        let nlen = r.as_inner_mut().read_u16::<LittleEndian>()?;
+                        r.align_to_byte();
+                        let len = r.read_u16::<LittleEndian>()?;
+                        let nlen = r.read_u16::<LittleEndian>()?;
                         if !len != nlen {
                             Err(invalid_data_error!(
                                 "LEN={} is not the one's complement of NLEN={}",
@@ -127,7 +182,7 @@ impl<R: Read> Read for Decoder<R> {
                 DecoderState::ReadNonCompressedBlock { ref mut len } => {
                     let buf_len = buf.len();
                     let buf = &mut buf[..cmp::min(buf_len, *len as usize)];
-                    read_size = self.bit_reader.as_inner_mut().read(buf)?;
+                    read_size = self.bit_reader.read(buf)?;
 
                     self.block_decoder.buffer.extend(&buf[..read_size]);
                     *len -= read_size as u16;
@@ -143,7 +198,7 @@ impl<R: Read> Read for Decoder<R> {
                         .transaction(|r| symbol::DynamicHuffmanCodec.load(r))?;
                     DecoderState::DecodeBlock(symbol_decoder)
                 }
-                DecoderState::DecodeBlock(ref mut symbol_decoder) => {
+                DecoderState::DecodeBlock(ref symbol_decoder) => {
                     self.block_decoder
                         .decode(&mut self.bit_reader, symbol_decoder)?;
                     read_size = self.block_decoder.read(buf)?;
@@ -167,7 +222,7 @@ enum DecoderState {
     ReadNonCompressedBlock { len: u16 },
     LoadFixedHuffmanCode,
     LoadDynamicHuffmanCode,
-    DecodeBlock(symbol::Decoder),
+    DecodeBlock(Cow<'static, symbol::Decoder>),
 }
 
 #[derive(Debug)]
@@ -184,6 +239,12 @@ impl BlockDecoder {
             eob: false,
         }
     }
+    pub fn set_dictionary(&mut self, dictionary: &[u8]) {
+        let start = dictionary.len().saturating_sub(lz77::MAX_DISTANCE as usize);
+        self.buffer.clear();
+        self.buffer.extend_from_slice(&dictionary[start..]);
+        self.offset = self.buffer.len();
+    }
     pub fn enter_new_block(&mut self) {
         self.eob = false;
         self.truncate_old_buffer();
@@ -191,17 +252,20 @@ impl BlockDecoder {
     pub fn decode<R: Read>(
         &mut self,
         bit_reader: &mut TransactionalBitReader<R>,
-        symbol_decoder: &mut symbol::Decoder,
+        symbol_decoder: &symbol::Decoder,
     ) -> io::Result<()> {
         if self.eob {
             return Ok(());
         }
         while let Some(s) = self.decode_symbol(bit_reader, symbol_decoder)? {
             match s {
-                symbol::Symbol::Literal(b) => {
+                symbol::DecodedSymbol::Literal(b) => {
                     self.buffer.push(b);
                 }
-                symbol::Symbol::Share { length, distance } => {
+                symbol::DecodedSymbol::LiteralPair(b0, b1) => {
+                    self.buffer.extend_from_slice(&[b0, b1]);
+                }
+                symbol::DecodedSymbol::Share { length, distance } => {
                     if self.buffer.len() < distance as usize {
                         return Err(invalid_data_error!(
                             "Too long backword reference: buffer.len={}, distance={}",
@@ -223,7 +287,7 @@ impl BlockDecoder {
                         );
                     }
                 }
-                symbol::Symbol::EndOfBlock => {
+                symbol::DecodedSymbol::EndOfBlock => {
                     self.eob = true;
                     break;
                 }
@@ -246,8 +310,8 @@ impl BlockDecoder {
     fn decode_symbol<R: Read>(
         &mut self,
         bit_reader: &mut TransactionalBitReader<R>,
-        symbol_decoder: &mut symbol::Decoder,
-    ) -> io::Result<Option<symbol::Symbol>> {
+        symbol_decoder: &symbol::Decoder,
+    ) -> io::Result<Option<symbol::DecodedSymbol>> {
         let result = bit_reader.transaction(|bit_reader| {
             let s = symbol_decoder.decode_unchecked(bit_reader);
             bit_reader.check_last_error().map(|()| s)
@@ -277,8 +341,9 @@ impl Read for BlockDecoder {
 #[cfg(test)]
 mod test {
     use super::*;
+    use alloc::string::String;
     use deflate::{EncodeOptions, Encoder};
-    use std::io::{self, Read};
+    use io::{self, Read};
     use util::{nb_read_to_end, WouldBlockReader};
 
     #[test]
@@ -322,6 +387,20 @@ mod test {
         assert_eq!(decoded_data, text.as_bytes());
     }
 
+    #[test]
+    fn dictionary_works() {
+        let dictionary = b"Hello World! Hello DEFLATE!";
+        let mut encoder = Encoder::new(Vec::new());
+        encoder.set_dictionary(dictionary).unwrap();
+        io::copy(&mut &b"Hello DEFLATE! Hello World!"[..], &mut encoder).unwrap();
+        let encoded_data = encoder.finish().into_result().unwrap();
+
+        let mut decoder = Decoder::new(WouldBlockReader::new(&encoded_data[..]));
+        decoder.set_dictionary(dictionary);
+        let decoded_data = nb_read_to_end(decoder).unwrap();
+        assert_eq!(decoded_data, b"Hello DEFLATE! Hello World!");
+    }
+
     #[test]
     fn non_compressed_non_blocking_io_works() {
         let mut encoder = Encoder::with_options(Vec::new(), EncodeOptions::new().no_compression());
@@ -333,4 +412,25 @@ mod test {
 
         assert_eq!(decoded_data, b"Hello World!");
     }
+
+    #[test]
+    fn unread_bytes_works() {
+        let text: String = (0..10000).map(|i| format!("test {}", i)).collect();
+        for options in &[EncodeOptions::new(), EncodeOptions::new().no_compression()] {
+            let mut encoder = Encoder::with_options(Vec::new(), options.clone());
+            io::copy(&mut text.as_bytes(), &mut encoder).unwrap();
+            let mut encoded_data = encoder.finish().into_result().unwrap();
+            encoded_data.extend_from_slice(b"trailing data");
+
+            let mut input = &encoded_data[..];
+            let mut decoder = Decoder::new(&mut input);
+            let mut decoded_data = Vec::new();
+            decoder.read_to_end(&mut decoded_data).unwrap();
+            assert_eq!(decoded_data, text.as_bytes());
+
+            let mut trailing_data = decoder.unread_bytes();
+            trailing_data.extend_from_slice(input);
+            assert_eq!(trailing_data, b"trailing data");
+        }
+    }
 }
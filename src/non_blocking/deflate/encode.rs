@@ -0,0 +1,153 @@
+use alloc::vec::Vec;
+
+use deflate::{self, EncodeOptions};
+use io::{self, Write};
+use lz77;
+use non_blocking::encode::BufferedEncoder;
+
+/// DEFLATE encoder which supports non-blocking I/O.
+///
+/// The encoded data is kept in the internal buffer until the inner writer accepts it.
+/// While the buffer has pending data, `write` returns `WouldBlock` without consuming the input.
+#[derive(Debug)]
+pub struct Encoder<W, E = lz77::DefaultLz77Encoder> {
+    inner: BufferedEncoder<deflate::Encoder<Vec<u8>, E>, W>,
+}
+impl<W> Encoder<W, lz77::DefaultLz77Encoder>
+where
+    W: Write,
+{
+    /// Makes a new encoder instance.
+    ///
+    /// Encoded DEFLATE stream is written to `inner`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::non_blocking::deflate::Encoder;
+    ///
+    /// let mut encoder = Encoder::new(Vec::new());
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// encoder.finish().unwrap();
+    ///
+    /// assert_eq!(encoder.into_inner(),
+    ///            [243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0]);
+    /// ```
+    pub fn new(inner: W) -> Self {
+        Self::with_options(inner, EncodeOptions::default())
+    }
+}
+impl<W, E> Encoder<W, E>
+where
+    W: Write,
+    E: lz77::Lz77Encode,
+{
+    /// Makes a new encoder instance with specified options.
+    ///
+    /// Encoded DEFLATE stream is written to `inner`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::EncodeOptions;
+    /// use libflate::non_blocking::deflate::Encoder;
+    ///
+    /// let options = EncodeOptions::new().no_compression();
+    /// let mut encoder = Encoder::with_options(Vec::new(), options);
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// encoder.finish().unwrap();
+    ///
+    /// assert_eq!(encoder.into_inner(),
+    ///            [1, 12, 0, 243, 255, 72, 101, 108, 108, 111, 32, 87, 111, 114, 108, 100, 33]);
+    /// ```
+    pub fn with_options(inner: W, options: EncodeOptions<E>) -> Self {
+        let encoder = deflate::Encoder::with_options(Vec::new(), options);
+        Encoder {
+            inner: BufferedEncoder::new(encoder, inner),
+        }
+    }
+
+    /// Writes the remaining data to the inner stream.
+    ///
+    /// If the inner stream returns `WouldBlock`, this returns it as is.
+    /// In that case, this should be called again after the inner stream becomes writable.
+    ///
+    /// After this method returns `Ok(())`, the encoded stream is complete
+    /// and `into_inner` can be used to take the inner stream.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::non_blocking::deflate::Encoder;
+    ///
+    /// let mut encoder = Encoder::new(Vec::new());
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// encoder.finish().unwrap();
+    /// assert!(encoder.write_all(b"Hello").is_err());
+    /// ```
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.inner.finish()
+    }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &W {
+        self.inner.as_inner_ref()
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut W {
+        self.inner.as_inner_mut()
+    }
+
+    /// Unwraps the `Encoder`, returning the inner stream.
+    ///
+    /// The data which is not written to the inner stream yet is discarded.
+    pub fn into_inner(self) -> W {
+        self.inner.into_inner()
+    }
+}
+impl<W, E> Write for Encoder<W, E>
+where
+    W: Write,
+    E: lz77::Lz77Encode,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::string::String;
+    use deflate::Decoder;
+    use io::Read;
+    use util::{nb_retry, WouldBlockWriter};
+
+    #[test]
+    fn would_block_writer_works() {
+        let plain = (0..50_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let options = EncodeOptions::new().block_size(4096);
+        let mut encoder = Encoder::with_options(WouldBlockWriter::new(Vec::new()), options);
+        for chunk in plain.chunks(1000) {
+            assert_eq!(nb_retry(|| encoder.write(chunk)).unwrap(), chunk.len());
+        }
+        nb_retry(|| encoder.flush()).unwrap();
+        nb_retry(|| encoder.write(b"Bye!")).unwrap();
+        nb_retry(|| encoder.finish()).unwrap();
+        let encoded = encoder.into_inner().into_inner();
+
+        let mut decoded = Vec::new();
+        Decoder::new(&encoded[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(&decoded[..plain.len()], &plain[..]);
+        assert_eq!(&decoded[plain.len()..], b"Bye!");
+    }
+}
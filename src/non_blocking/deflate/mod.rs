@@ -1,17 +1,17 @@
-//! The decoder of the DEFLATE format and algorithm.
+//! The encoder and decoder of the DEFLATE format and algorithm.
 //!
 //! The DEFLATE is defined in [RFC-1951](https://tools.ietf.org/html/rfc1951).
 //!
 //! # Examples
 //! ```
 //! use std::io::{self, Read};
-//! use libflate::deflate::Encoder;
-//! use libflate::non_blocking::deflate::Decoder;
+//! use libflate::non_blocking::deflate::{Encoder, Decoder};
 //!
 //! // Encoding
 //! let mut encoder = Encoder::new(Vec::new());
 //! io::copy(&mut &b"Hello World!"[..], &mut encoder).unwrap();
-//! let encoded_data = encoder.finish().into_result().unwrap();
+//! encoder.finish().unwrap();
+//! let encoded_data = encoder.into_inner();
 //!
 //! // Decoding
 //! let mut decoder = Decoder::new(&encoded_data[..]);
@@ -21,5 +21,7 @@
 //! assert_eq!(decoded_data, b"Hello World!");
 //! ```
 pub use self::decode::Decoder;
+pub use self::encode::Encoder;
 
 mod decode;
+mod encode;
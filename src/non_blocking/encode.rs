@@ -0,0 +1,116 @@
+use alloc::vec::Vec;
+use core::mem;
+
+use adapter::BufferEncode;
+use io::{self, Write};
+
+/// An encoder which keeps the encoded data until the inner writer accepts it.
+///
+/// The input data is encoded into the internal buffer at once,
+/// so that no input is lost or duplicated even if the inner writer would block.
+/// New input is refused with `WouldBlock` while the buffer has pending data which can not be written.
+#[derive(Debug)]
+pub struct BufferedEncoder<T, W> {
+    encoder: Option<T>,
+    inner: W,
+    buf: Vec<u8>,
+    offset: usize,
+    flushed: bool,
+}
+impl<T, W> BufferedEncoder<T, W>
+where
+    T: BufferEncode,
+    W: Write,
+{
+    pub fn new(encoder: T, inner: W) -> Self {
+        let mut this = BufferedEncoder {
+            encoder: Some(encoder),
+            inner,
+            buf: Vec::new(),
+            offset: 0,
+            flushed: false,
+        };
+        this.take_output();
+        this
+    }
+    pub fn finish(&mut self) -> io::Result<()> {
+        if let Some(encoder) = self.encoder.take() {
+            let output = encoder.finish_output()?;
+            self.buf.extend_from_slice(&output);
+        }
+        self.write_buf()?;
+        self.inner.flush()
+    }
+    pub fn as_inner_ref(&self) -> &W {
+        &self.inner
+    }
+    pub fn as_inner_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn encoder_mut(&mut self) -> io::Result<&mut T> {
+        self.encoder.as_mut().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The encoder has already been finished",
+            )
+        })
+    }
+    fn take_output(&mut self) {
+        if let Some(ref mut encoder) = self.encoder {
+            let output = encoder.output_mut();
+            if self.buf.is_empty() {
+                mem::swap(&mut self.buf, output);
+            } else {
+                self.buf.extend_from_slice(output);
+                output.clear();
+            }
+        }
+    }
+    fn write_buf(&mut self) -> io::Result<()> {
+        while self.offset < self.buf.len() {
+            let size = self.inner.write(&self.buf[self.offset..])?;
+            if size == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to write the buffered data",
+                ));
+            }
+            self.offset += size;
+        }
+        self.buf.clear();
+        self.offset = 0;
+        Ok(())
+    }
+}
+impl<T, W> Write for BufferedEncoder<T, W>
+where
+    T: BufferEncode,
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_buf()?;
+        self.encoder_mut()?.write_all(buf)?;
+        self.flushed = false;
+        self.take_output();
+        match self.write_buf() {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            result => result?,
+        }
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.flushed {
+            if let Some(ref mut encoder) = self.encoder {
+                encoder.sync_flush()?;
+            }
+            self.flushed = true;
+            self.take_output();
+        }
+        self.write_buf()?;
+        self.inner.flush()
+    }
+}
@@ -5,13 +5,13 @@
 //! # Examples
 //! ```
 //! use std::io::{self, Read};
-//! use libflate::gzip::Encoder;
-//! use libflate::non_blocking::gzip::Decoder;
+//! use libflate::non_blocking::gzip::{Encoder, Decoder};
 //!
 //! // Encoding
 //! let mut encoder = Encoder::new(Vec::new()).unwrap();
 //! io::copy(&mut &b"Hello World!"[..], &mut encoder).unwrap();
-//! let encoded_data = encoder.finish().into_result().unwrap();
+//! encoder.finish().unwrap();
+//! let encoded_data = encoder.into_inner();
 //!
 //! // Decoding
 //! let mut decoder = Decoder::new(&encoded_data[..]);
@@ -20,11 +20,14 @@
 //!
 //! assert_eq!(decoded_data, b"Hello World!");
 //! ```
-use std::io::{self, Read};
+use alloc::vec::Vec;
 
 use checksum;
-use gzip::{Header, Trailer};
+use gzip::{self, EncodeOptions, Header, Trailer};
+use io::{self, Read, Write};
+use lz77;
 use non_blocking::deflate;
+use non_blocking::encode::BufferedEncoder;
 
 /// GZIP decoder which supports non-blocking I/O.
 #[derive(Debug)]
@@ -116,6 +119,13 @@ impl<R: Read> Decoder<R> {
     pub fn into_inner(self) -> R {
         self.reader.into_inner()
     }
+
+    /// Returns the bytes which have been read from the inner stream but not decoded yet.
+    ///
+    /// See `non_blocking::deflate::Decoder::unread_bytes` for more details.
+    pub fn unread_bytes(&self) -> Vec<u8> {
+        self.reader.unread_bytes()
+    }
 }
 impl<R: Read> Read for Decoder<R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
@@ -148,12 +158,125 @@ impl<R: Read> Read for Decoder<R> {
     }
 }
 
+/// GZIP encoder which supports non-blocking I/O.
+///
+/// See `non_blocking::deflate::Encoder` for more details.
+pub struct Encoder<W, E = lz77::DefaultLz77Encoder> {
+    header: Header,
+    inner: BufferedEncoder<gzip::Encoder<Vec<u8>, E>, W>,
+}
+impl<W> Encoder<W, lz77::DefaultLz77Encoder>
+where
+    W: Write,
+{
+    /// Makes a new encoder instance.
+    ///
+    /// Encoded GZIP stream is written to `inner`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::non_blocking::gzip::Encoder;
+    ///
+    /// let mut encoder = Encoder::new(Vec::new()).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// encoder.finish().unwrap();
+    /// ```
+    pub fn new(inner: W) -> io::Result<Self> {
+        Self::with_options(inner, EncodeOptions::new())
+    }
+}
+impl<W, E> Encoder<W, E>
+where
+    W: Write,
+    E: lz77::Lz77Encode,
+{
+    /// Makes a new encoder instance with specified options.
+    ///
+    /// Encoded GZIP stream is written to `inner`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::gzip::{EncodeOptions, HeaderBuilder};
+    /// use libflate::non_blocking::gzip::Encoder;
+    ///
+    /// let header = HeaderBuilder::new().modification_time(123).finish();
+    /// let options = EncodeOptions::new().no_compression().header(header);
+    /// let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// encoder.finish().unwrap();
+    ///
+    /// assert_eq!(encoder.into_inner(),
+    ///            &[31, 139, 8, 0, 123, 0, 0, 0, 0, 3, 1, 12, 0, 243, 255, 72, 101, 108, 108,
+    ///              111, 32, 87, 111, 114, 108, 100, 33, 163, 28, 41, 28, 12, 0, 0, 0][..]);
+    /// ```
+    pub fn with_options(inner: W, options: EncodeOptions<E>) -> io::Result<Self> {
+        let encoder = gzip::Encoder::with_options(Vec::new(), options)?;
+        Ok(Encoder {
+            header: encoder.header().clone(),
+            inner: BufferedEncoder::new(encoder, inner),
+        })
+    }
+
+    /// Returns the header of the GZIP stream.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::gzip::Os;
+    /// use libflate::non_blocking::gzip::Encoder;
+    ///
+    /// let encoder = Encoder::new(Vec::new()).unwrap();
+    /// assert_eq!(encoder.header().os(), Os::Unix);
+    /// ```
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Writes the remaining data and the GZIP trailer to the inner stream.
+    ///
+    /// See `non_blocking::deflate::Encoder::finish` for more details.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.inner.finish()
+    }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &W {
+        self.inner.as_inner_ref()
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut W {
+        self.inner.as_inner_mut()
+    }
+
+    /// Unwraps the `Encoder`, returning the inner stream.
+    ///
+    /// The data which is not written to the inner stream yet is discarded.
+    pub fn into_inner(self) -> W {
+        self.inner.into_inner()
+    }
+}
+impl<W, E> Write for Encoder<W, E>
+where
+    W: Write,
+    E: lz77::Lz77Encode,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use alloc::string::String;
     use gzip::Encoder;
-    use std::io;
-    use util::{nb_read_to_end, WouldBlockReader};
+    use io;
+    use util::{nb_read_to_end, nb_retry, WouldBlockReader, WouldBlockWriter};
 
     fn decode_all(buf: &[u8]) -> io::Result<Vec<u8>> {
         let decoder = Decoder::new(WouldBlockReader::new(buf));
@@ -168,4 +291,22 @@ mod test {
         let encoded = encoder.finish().into_result().unwrap();
         assert_eq!(decode_all(&encoded).unwrap(), plain);
     }
+
+    #[test]
+    fn non_blocking_encode_works() {
+        let plain = (0..50_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let options = EncodeOptions::new().block_size(4096);
+        let mut encoder =
+            super::Encoder::with_options(WouldBlockWriter::new(Vec::new()), options).unwrap();
+        for chunk in plain.chunks(1000) {
+            assert_eq!(nb_retry(|| encoder.write(chunk)).unwrap(), chunk.len());
+        }
+        nb_retry(|| encoder.flush()).unwrap();
+        nb_retry(|| encoder.finish()).unwrap();
+        let encoded = encoder.into_inner().into_inner();
+        assert_eq!(decode_all(&encoded).unwrap(), plain);
+    }
 }
@@ -18,4 +18,5 @@ pub mod deflate;
 pub mod gzip;
 pub mod zlib;
 
+mod encode;
 mod transaction;
@@ -1,7 +1,10 @@
-use std::cmp;
-use std::io::{self, Read};
+use alloc::vec::Vec;
+use core::cmp;
 
 use bit;
+use io::{self, BufRead, Read};
+
+const READ_SIZE: usize = 8 * 1024;
 
 #[derive(Debug)]
 pub struct TransactionalBitReader<R> {
@@ -30,31 +33,48 @@ impl<R: Read> TransactionalBitReader<R> {
     }
     #[inline]
     pub fn start_transaction(&mut self) {
-        self.inner.as_inner_mut().start_transaction();
+        self.inner.as_inner_mut_unsynced().start_transaction();
         self.savepoint = self.inner.state();
     }
     #[inline]
     pub fn abort_transaction(&mut self) {
-        self.inner.as_inner_mut().abort_transaction();
+        self.inner.as_inner_mut_unsynced().abort_transaction();
         self.inner.restore_state(self.savepoint);
     }
     #[inline]
     pub fn commit_transaction(&mut self) {
-        self.inner.as_inner_mut().commit_transaction();
+        self.inner.as_inner_mut_unsynced().commit_transaction();
+    }
+    pub fn as_inner_mut(&mut self) -> &mut R {
+        &mut self.inner.as_inner_mut().inner
+    }
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner().inner
     }
 }
 impl<R> TransactionalBitReader<R> {
     pub fn as_inner_ref(&self) -> &R {
         &self.inner.as_inner_ref().inner
     }
-    pub fn as_inner_mut(&mut self) -> &mut R {
-        &mut self.inner.as_inner_mut().inner
+
+    /// Returns the bytes which have been read from the inner stream but not decoded yet.
+    pub fn unread_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.inner.unread_bytes();
+        let read = self.inner.read_unconsumed_bytes();
+        bytes.extend_from_slice(&self.inner.as_inner_ref().unconsumed()[read..]);
+        bytes
     }
-    pub fn into_inner(self) -> R {
-        self.inner.into_inner().inner
+}
+impl<R: Read> Read for TransactionalBitReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
     }
 }
 
+/// A reader which can restore the bytes consumed in an aborted transaction.
+///
+/// As a `BufRead`, this reads the inner stream in chunks of `READ_SIZE` bytes.
+/// The bytes beyond the end of a DEFLATE stream are kept in the buffer (see `TransactionalBitReader::unread_bytes`).
 #[derive(Debug)]
 pub struct TransactionalReader<R> {
     inner: R,
@@ -75,18 +95,30 @@ impl<R> TransactionalReader<R> {
     pub fn start_transaction(&mut self) {
         assert!(!self.in_transaction);
         self.in_transaction = true;
+        self.discard_consumed();
     }
     #[inline]
     pub fn commit_transaction(&mut self) {
         self.in_transaction = false;
-        self.offset = 0;
-        self.buffer.clear();
+        self.discard_consumed();
     }
     #[inline]
     pub fn abort_transaction(&mut self) {
         self.in_transaction = false;
         self.offset = 0;
     }
+    fn unconsumed(&self) -> &[u8] {
+        &self.buffer[self.offset..]
+    }
+    #[inline]
+    fn discard_consumed(&mut self) {
+        if self.offset == self.buffer.len() {
+            self.buffer.clear();
+        } else {
+            self.buffer.drain(..self.offset);
+        }
+        self.offset = 0;
+    }
 }
 impl<R: Read> Read for TransactionalReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
@@ -94,7 +126,7 @@ impl<R: Read> Read for TransactionalReader<R> {
             let unread_buf_size = self.buffer.len() - self.offset;
             let size = cmp::min(buf.len(), unread_buf_size);
             (&mut buf[0..size]).copy_from_slice(&self.buffer[self.offset..self.offset + size]);
-            self.offset += size;
+            self.consume(size);
             return Ok(size);
         }
 
@@ -106,3 +138,22 @@ impl<R: Read> Read for TransactionalReader<R> {
         Ok(size)
     }
 }
+impl<R: Read> BufRead for TransactionalReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.offset == self.buffer.len() {
+            let len = self.buffer.len();
+            self.buffer.resize(len + READ_SIZE, 0);
+            let result = self.inner.read(&mut self.buffer[len..]);
+            self.buffer.truncate(len + *result.as_ref().unwrap_or(&0));
+            result?;
+        }
+        Ok(&self.buffer[self.offset..])
+    }
+    fn consume(&mut self, amt: usize) {
+        self.offset += amt;
+        if !self.in_transaction && self.offset == self.buffer.len() {
+            self.buffer.clear();
+            self.offset = 0;
+        }
+    }
+}
@@ -5,13 +5,13 @@
 //! # Examples
 //! ```
 //! use std::io::{self, Read};
-//! use libflate::zlib::Encoder;
-//! use libflate::non_blocking::zlib::Decoder;
+//! use libflate::non_blocking::zlib::{Encoder, Decoder};
 //!
 //! // Encoding
 //! let mut encoder = Encoder::new(Vec::new()).unwrap();
 //! io::copy(&mut &b"Hello World!"[..], &mut encoder).unwrap();
-//! let encoded_data = encoder.finish().into_result().unwrap();
+//! encoder.finish().unwrap();
+//! let encoded_data = encoder.into_inner();
 //!
 //! // Decoding
 //! let mut decoder = Decoder::new(&encoded_data[..]);
@@ -20,13 +20,18 @@
 //!
 //! assert_eq!(decoded_data, b"Hello World!");
 //! ```
+use alloc::boxed::Box;
+use alloc::vec::Vec;
 use byteorder::BigEndian;
-use byteorder::ReadBytesExt;
-use std::io::{self, Read};
+use core::fmt;
 
 use checksum;
+use io::ReadBytesExt;
+use io::{self, Read, Write};
+use lz77;
 use non_blocking::deflate;
-use zlib::Header;
+use non_blocking::encode::BufferedEncoder;
+use zlib::{self, EncodeOptions, Header};
 
 /// ZLIB decoder which supports non-blocking I/O.
 #[derive(Debug)]
@@ -35,6 +40,7 @@ pub struct Decoder<R> {
     reader: deflate::Decoder<R>,
     adler32: checksum::Adler32,
     eos: bool,
+    lookup: Option<DictionaryLookup>,
 }
 impl<R: Read> Decoder<R> {
     /// Makes a new decoder instance.
@@ -55,15 +61,81 @@ impl<R: Read> Decoder<R> {
     ///
     /// assert_eq!(buf, b"Hello World!");
     /// ```
+    ///
+    /// If the stream requires a preset dictionary, reading it results in an `InvalidData` error.
+    /// Use `Decoder::with_dictionary` or `Decoder::with_dictionary_lookup` to decode such streams.
     pub fn new(inner: R) -> Self {
         Decoder {
             header: None,
             reader: deflate::Decoder::new(inner),
             adler32: checksum::Adler32::new(),
             eos: false,
+            lookup: None,
         }
     }
 
+    /// Makes a new decoder instance which uses `dictionary` as the preset dictionary.
+    ///
+    /// See `zlib::Decoder::with_dictionary` for more details.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Read, Write};
+    /// use libflate::zlib::{Encoder, EncodeOptions};
+    /// use libflate::non_blocking::zlib::Decoder;
+    ///
+    /// let options = EncodeOptions::new().dictionary(b"Hello");
+    /// let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    ///
+    /// let mut decoder = Decoder::with_dictionary(&encoded_data[..], b"Hello");
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    /// assert_eq!(buf, b"Hello World!");
+    /// ```
+    pub fn with_dictionary(inner: R, dictionary: &[u8]) -> Self {
+        let dictionary = dictionary.to_vec();
+        Self::with_dictionary_lookup(inner, move |_| Some(dictionary))
+    }
+
+    /// Makes a new decoder instance which looks up the preset dictionary by `lookup`.
+    ///
+    /// `lookup` is called once the header is read,
+    /// only if the stream requires a preset dictionary.
+    /// See `zlib::Decoder::with_dictionary_lookup` for more details.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::collections::HashMap;
+    /// use std::io::{Read, Write};
+    /// use libflate::zlib::{Encoder, EncodeOptions};
+    /// use libflate::non_blocking::zlib::Decoder;
+    ///
+    /// let options = EncodeOptions::new().dictionary(b"Hello");
+    /// let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// let dictionary_id = encoder.header().dictionary_id().unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    ///
+    /// let mut dictionaries = HashMap::new();
+    /// dictionaries.insert(dictionary_id, b"Hello".to_vec());
+    ///
+    /// let mut decoder =
+    ///     Decoder::with_dictionary_lookup(&encoded_data[..], move |id| dictionaries.remove(&id));
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    /// assert_eq!(buf, b"Hello World!");
+    /// ```
+    pub fn with_dictionary_lookup<F>(inner: R, lookup: F) -> Self
+    where
+        F: FnOnce(u32) -> Option<Vec<u8>> + Send + 'static,
+    {
+        let mut this = Self::new(inner);
+        this.lookup = Some(DictionaryLookup(Box::new(lookup)));
+        this
+    }
+
     /// Returns the header of the ZLIB stream.
     ///
     /// # Examples
@@ -85,6 +157,12 @@ impl<R: Read> Decoder<R> {
             let header = self.reader
                 .bit_reader_mut()
                 .transaction(|r| Header::read_from(r.as_inner_mut()))?;
+            let lookup = self.lookup.take();
+            let dictionary =
+                header.lookup_dictionary(|id| lookup.and_then(|DictionaryLookup(f)| f(id)))?;
+            if let Some(dictionary) = dictionary {
+                self.reader.set_dictionary(&dictionary);
+            }
             self.header = Some(header);
             self.header()
         }
@@ -116,6 +194,13 @@ impl<R: Read> Decoder<R> {
     pub fn into_inner(self) -> R {
         self.reader.into_inner()
     }
+
+    /// Returns the bytes which have been read from the inner stream but not decoded yet.
+    ///
+    /// See `non_blocking::deflate::Decoder::unread_bytes` for more details.
+    pub fn unread_bytes(&self) -> Vec<u8> {
+        self.reader.unread_bytes()
+    }
 }
 impl<R: Read> Read for Decoder<R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
@@ -148,11 +233,135 @@ impl<R: Read> Read for Decoder<R> {
     }
 }
 
+/// ZLIB encoder which supports non-blocking I/O.
+///
+/// See `non_blocking::deflate::Encoder` for more details.
+#[derive(Debug)]
+pub struct Encoder<W, E = lz77::DefaultLz77Encoder> {
+    header: Header,
+    inner: BufferedEncoder<zlib::Encoder<Vec<u8>, E>, W>,
+}
+impl<W> Encoder<W, lz77::DefaultLz77Encoder>
+where
+    W: Write,
+{
+    /// Makes a new encoder instance.
+    ///
+    /// Encoded ZLIB stream is written to `inner`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::non_blocking::zlib::Encoder;
+    ///
+    /// let mut encoder = Encoder::new(Vec::new()).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// encoder.finish().unwrap();
+    ///
+    /// assert_eq!(encoder.into_inner(),
+    ///            [120, 156, 243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0,
+    ///             28, 73, 4, 62]);
+    /// ```
+    pub fn new(inner: W) -> io::Result<Self> {
+        Self::with_options(inner, EncodeOptions::default())
+    }
+}
+impl<W, E> Encoder<W, E>
+where
+    W: Write,
+    E: lz77::Lz77Encode,
+{
+    /// Makes a new encoder instance with specified options.
+    ///
+    /// Encoded ZLIB stream is written to `inner`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::zlib::EncodeOptions;
+    /// use libflate::non_blocking::zlib::Encoder;
+    ///
+    /// let options = EncodeOptions::new().no_compression();
+    /// let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// encoder.finish().unwrap();
+    ///
+    /// assert_eq!(encoder.into_inner(),
+    ///            [120, 1, 1, 12, 0, 243, 255, 72, 101, 108, 108, 111, 32, 87, 111,
+    ///             114, 108, 100, 33, 28, 73, 4, 62]);
+    /// ```
+    pub fn with_options(inner: W, options: EncodeOptions<E>) -> io::Result<Self> {
+        let encoder = zlib::Encoder::with_options(Vec::new(), options)?;
+        Ok(Encoder {
+            header: encoder.header().clone(),
+            inner: BufferedEncoder::new(encoder, inner),
+        })
+    }
+
+    /// Returns the header of the ZLIB stream.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::zlib::Lz77WindowSize;
+    /// use libflate::non_blocking::zlib::Encoder;
+    ///
+    /// let encoder = Encoder::new(Vec::new()).unwrap();
+    /// assert_eq!(encoder.header().window_size(), Lz77WindowSize::KB32);
+    /// ```
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Writes the remaining data and the ZLIB trailer to the inner stream.
+    ///
+    /// See `non_blocking::deflate::Encoder::finish` for more details.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.inner.finish()
+    }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &W {
+        self.inner.as_inner_ref()
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut W {
+        self.inner.as_inner_mut()
+    }
+
+    /// Unwraps the `Encoder`, returning the inner stream.
+    ///
+    /// The data which is not written to the inner stream yet is discarded.
+    pub fn into_inner(self) -> W {
+        self.inner.into_inner()
+    }
+}
+impl<W, E> Write for Encoder<W, E>
+where
+    W: Write,
+    E: lz77::Lz77Encode,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+struct DictionaryLookup(Box<dyn FnOnce(u32) -> Option<Vec<u8>> + Send>);
+impl fmt::Debug for DictionaryLookup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DictionaryLookup(_)")
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use std::io;
-    use util::{nb_read_to_end, WouldBlockReader};
+    use alloc::string::String;
+    use io;
+    use util::{nb_read_to_end, nb_retry, WouldBlockReader, WouldBlockWriter};
     use zlib::{EncodeOptions, Encoder};
 
     fn decode_all(buf: &[u8]) -> io::Result<Vec<u8>> {
@@ -218,6 +427,46 @@ mod test {
         assert_eq!(decode_all(&encoded).unwrap(), plain);
     }
 
+    #[test]
+    fn dictionary_works() {
+        let dictionary = b"Hello World!";
+        let plain = b"Hello World! Hello ZLIB!";
+        let options = EncodeOptions::new().dictionary(dictionary);
+        let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+        io::copy(&mut &plain[..], &mut encoder).unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+
+        let decoder = Decoder::with_dictionary(WouldBlockReader::new(&encoded[..]), dictionary);
+        assert_eq!(nb_read_to_end(decoder).unwrap(), &plain[..]);
+
+        let decoder = Decoder::with_dictionary_lookup(WouldBlockReader::new(&encoded[..]), |id| {
+            assert_eq!(id, 0x1C49_043E);
+            Some(b"Hello World!".to_vec())
+        });
+        assert_eq!(nb_read_to_end(decoder).unwrap(), &plain[..]);
+
+        assert!(decode_all(&encoded).is_err());
+        let decoder = Decoder::with_dictionary(WouldBlockReader::new(&encoded[..]), b"Hello");
+        assert!(nb_read_to_end(decoder).is_err());
+    }
+
+    #[test]
+    fn non_blocking_encode_works() {
+        let plain = (0..50_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let options = EncodeOptions::new().block_size(4096);
+        let mut encoder =
+            super::Encoder::with_options(WouldBlockWriter::new(Vec::new()), options).unwrap();
+        for chunk in plain.chunks(1000) {
+            assert_eq!(nb_retry(|| encoder.write(chunk)).unwrap(), chunk.len());
+        }
+        nb_retry(|| encoder.finish()).unwrap();
+        let encoded = encoder.into_inner().into_inner();
+        assert_eq!(decode_all(&encoded).unwrap(), plain);
+    }
+
     #[test]
     fn test_issue_2() {
         // See: https://github.com/sile/libflate/issues/2
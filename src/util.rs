@@ -1,6 +1,20 @@
+#[cfg(target_has_atomic = "ptr")]
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cmp;
+#[cfg(not(feature = "std"))]
+use core::f64;
+#[cfg(target_has_atomic = "ptr")]
+use core::marker::PhantomData;
+use core::ptr;
+#[cfg(target_has_atomic = "ptr")]
+use core::sync::atomic::{AtomicPtr, Ordering};
+
 #[cfg(test)]
-use std::io::{self, Read};
-use std::ptr;
+use io::Write;
+use io::{self, BufRead, Read};
+
+const READ_SIZE: usize = 8 * 1024;
 
 #[inline]
 pub unsafe fn ptr_copy(src: *const u8, dst: *mut u8, count: usize, is_overlapping: bool) {
@@ -13,6 +27,145 @@ pub unsafe fn ptr_copy(src: *const u8, dst: *mut u8, count: usize, is_overlappin
     }
 }
 
+/// Returns the base 2 logarithm of `x` (which must be a positive normal number).
+#[cfg(feature = "std")]
+#[inline]
+pub fn log2(x: f64) -> f64 {
+    x.log2()
+}
+
+/// Returns the base 2 logarithm of `x` (which must be a positive normal number).
+///
+/// `f64::log2` is unavailable without `std`, so `x` is split into `m * 2^e` (`1/sqrt(2) <= m < sqrt(2)`)
+/// and `ln(m)` is calculated by the series of `2 * atanh((m - 1) / (m + 1))`.
+#[cfg(not(feature = "std"))]
+pub fn log2(x: f64) -> f64 {
+    let bits = x.to_bits();
+    let mut e = ((bits >> 52) & 0x7FF) as i64 - 1023;
+    let mut m = f64::from_bits((bits & 0x000F_FFFF_FFFF_FFFF) | 0x3FF0_0000_0000_0000);
+    if m > f64::consts::SQRT_2 {
+        m /= 2.0;
+        e += 1;
+    }
+    let s = (m - 1.0) / (m + 1.0);
+    let s2 = s * s;
+    let mut term = s;
+    let mut ln = 0.0;
+    let mut k = 1.0;
+    while term.abs() > 1e-17 {
+        ln += term / k;
+        term *= s2;
+        k += 2.0;
+    }
+    e as f64 + 2.0 * ln * f64::consts::LOG2_E
+}
+
+/// A value which is initialized on the first access, and shared afterwards.
+///
+/// This works without `std` (unlike `std::sync::OnceLock`).
+/// If several threads access it concurrently for the first time,
+/// each of them may call `init`, but only one of the results is kept.
+///
+/// This needs the atomic operations on pointers, which some targets (e.g., `thumbv6m`) lack.
+#[cfg(target_has_atomic = "ptr")]
+pub struct Lazy<T> {
+    value: AtomicPtr<T>,
+    init: fn() -> T,
+    _value: PhantomData<T>,
+}
+#[cfg(target_has_atomic = "ptr")]
+impl<T> Lazy<T> {
+    pub const fn new(init: fn() -> T) -> Self {
+        Lazy {
+            value: AtomicPtr::new(ptr::null_mut()),
+            init,
+            _value: PhantomData,
+        }
+    }
+    pub fn get(&'static self) -> &'static T {
+        let mut value = self.value.load(Ordering::Acquire);
+        if value.is_null() {
+            let new = Box::into_raw(Box::new((self.init)()));
+            value = match self.value.compare_exchange(
+                ptr::null_mut(),
+                new,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => new,
+                Err(current) => {
+                    drop(unsafe { Box::from_raw(new) });
+                    current
+                }
+            };
+        }
+        unsafe { &*value }
+    }
+}
+
+/// A `BufRead` which reads the inner stream in chunks of `READ_SIZE` bytes.
+///
+/// This works without `std` (unlike `std::io::BufReader`).
+#[derive(Debug)]
+pub struct BufferedReader<R> {
+    inner: R,
+    buffer: Vec<u8>,
+    offset: usize,
+}
+impl<R> BufferedReader<R> {
+    pub fn new(inner: R) -> Self {
+        BufferedReader {
+            inner,
+            buffer: Vec::new(),
+            offset: 0,
+        }
+    }
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Returns the bytes which have been read from the inner stream but not consumed yet.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buffer[self.offset..]
+    }
+}
+impl<R: Read> Read for BufferedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.offset == self.buffer.len() && buf.len() >= READ_SIZE {
+            return self.inner.read(buf);
+        }
+        let size = {
+            let data = self.fill_buf()?;
+            let size = cmp::min(buf.len(), data.len());
+            buf[..size].copy_from_slice(&data[..size]);
+            size
+        };
+        self.consume(size);
+        Ok(size)
+    }
+}
+impl<R: Read> BufRead for BufferedReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.offset == self.buffer.len() {
+            self.buffer.resize(READ_SIZE, 0);
+            let result = self.inner.read(&mut self.buffer);
+            self.buffer.truncate(*result.as_ref().unwrap_or(&0));
+            self.offset = 0;
+            result?;
+        }
+        Ok(&self.buffer[self.offset..])
+    }
+    fn consume(&mut self, amt: usize) {
+        self.offset = cmp::min(self.offset + amt, self.buffer.len());
+    }
+}
+
 #[cfg(test)]
 pub struct WouldBlockReader<R> {
     inner: R,
@@ -72,3 +225,55 @@ pub fn nb_read_to_end<R: Read>(mut reader: R) -> io::Result<Vec<u8>> {
     }
     Ok(buf)
 }
+
+#[cfg(test)]
+pub struct WouldBlockWriter<W> {
+    inner: W,
+    do_block: bool,
+}
+#[cfg(test)]
+impl<W: Write> WouldBlockWriter<W> {
+    pub fn new(inner: W) -> Self {
+        WouldBlockWriter {
+            inner,
+            do_block: false,
+        }
+    }
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+#[cfg(test)]
+impl<W: Write> Write for WouldBlockWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.do_block = !self.do_block;
+        if self.do_block {
+            Err(io::Error::new(io::ErrorKind::WouldBlock, "Would block"))
+        } else if buf.is_empty() {
+            Ok(0)
+        } else {
+            self.inner.write(&buf[..1])
+        }
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.do_block = !self.do_block;
+        if self.do_block {
+            Err(io::Error::new(io::ErrorKind::WouldBlock, "Would block"))
+        } else {
+            self.inner.flush()
+        }
+    }
+}
+
+#[cfg(test)]
+pub fn nb_retry<F, T>(mut f: F) -> io::Result<T>
+where
+    F: FnMut() -> io::Result<T>,
+{
+    loop {
+        match f() {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            result => return result,
+        }
+    }
+}
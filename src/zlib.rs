@@ -19,16 +19,26 @@
 //!
 //! assert_eq!(decoded_data, b"Hello World!");
 //! ```
+use alloc::vec::Vec;
 use byteorder::BigEndian;
-use byteorder::ReadBytesExt;
-use byteorder::WriteBytesExt;
-use std::io;
+use core::cmp;
 
 use checksum;
 use deflate;
 use finish::{Complete, Finish};
+use io;
+use io::BufRead;
+use io::ReadBytesExt;
+use io::WriteBytesExt;
 use lz77;
 
+#[cfg(feature = "futures-io")]
+pub mod futures;
+pub mod read;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+pub mod write;
+
 const COMPRESSION_METHOD_DEFLATE: u8 = 8;
 
 /// Compression levels defined by the ZLIB format.
@@ -59,6 +69,14 @@ impl CompressionLevel {
     fn as_u2(&self) -> u8 {
         self.clone() as u8
     }
+    fn from_level(level: u8) -> Self {
+        match level {
+            0 | 1 => CompressionLevel::Fastest,
+            2..=5 => CompressionLevel::Fast,
+            6 => CompressionLevel::Default,
+            _ => CompressionLevel::Slowest,
+        }
+    }
 }
 impl From<lz77::CompressionLevel> for CompressionLevel {
     fn from(f: lz77::CompressionLevel) -> Self {
@@ -180,6 +198,7 @@ impl Lz77WindowSize {
 pub struct Header {
     window_size: Lz77WindowSize,
     compression_level: CompressionLevel,
+    dictionary_id: Option<u32>,
 }
 impl Header {
     /// Returns the LZ77 window size stored in the header.
@@ -190,6 +209,13 @@ impl Header {
     pub fn compression_level(&self) -> CompressionLevel {
         self.compression_level.clone()
     }
+    /// Returns the identifier of the preset dictionary stored in the header.
+    ///
+    /// The identifier is the Adler-32 checksum of the dictionary.
+    /// `None` means that the stream does not use a preset dictionary.
+    pub fn dictionary_id(&self) -> Option<u32> {
+        self.dictionary_id
+    }
     fn from_lz77<E>(lz77: &E) -> Self
     where
         E: lz77::Lz77Encode,
@@ -197,6 +223,7 @@ impl Header {
         Header {
             compression_level: From::from(lz77.compression_level()),
             window_size: Lz77WindowSize::from_u16(lz77.window_size()),
+            dictionary_id: None,
         }
     }
     pub(crate) fn read_from<R>(mut reader: R) -> io::Result<Self>
@@ -229,37 +256,79 @@ impl Header {
         })?;
 
         let dict_flag = (flg & 0b100_000) != 0;
-        if dict_flag {
-            let dictionary_id = reader.read_u32::<BigEndian>()?;
-            return Err(invalid_data_error!(
-                "Preset dictionaries are not supported: \
-                 dictionary_id=0x{:X}",
-                dictionary_id
-            ));
-        }
+        let dictionary_id = if dict_flag {
+            Some(reader.read_u32::<BigEndian>()?)
+        } else {
+            None
+        };
         let compression_level = CompressionLevel::from_u2(flg >> 6);
         Ok(Header {
             window_size,
             compression_level,
+            dictionary_id,
         })
     }
+    /// Looks up the preset dictionary of the stream by `lookup`, and verifies it.
+    ///
+    /// Returns `None` if the stream does not use a preset dictionary.
+    pub(crate) fn lookup_dictionary<F>(&self, lookup: F) -> io::Result<Option<Vec<u8>>>
+    where
+        F: FnOnce(u32) -> Option<Vec<u8>>,
+    {
+        let dictionary_id = match self.dictionary_id {
+            None => return Ok(None),
+            Some(id) => id,
+        };
+        let dictionary = lookup(dictionary_id).ok_or_else(|| {
+            invalid_data_error!(
+                "A preset dictionary is required: dictionary_id=0x{:X}",
+                dictionary_id
+            )
+        })?;
+        let mut adler32 = checksum::Adler32::new();
+        adler32.update(&dictionary);
+        if adler32.value() != dictionary_id {
+            return Err(invalid_data_error!(
+                "Preset dictionary mismatched: value=0x{:X}, expected=0x{:X}",
+                adler32.value(),
+                dictionary_id
+            ));
+        }
+        Ok(Some(dictionary))
+    }
+    fn encoded_len(&self) -> usize {
+        // CMF, FLG and DICTID (if any)
+        if self.dictionary_id.is_some() {
+            6
+        } else {
+            2
+        }
+    }
     fn write_to<W>(&self, mut writer: W) -> io::Result<()>
     where
         W: io::Write,
     {
         let cmf = (self.window_size.as_u4() << 4) | COMPRESSION_METHOD_DEFLATE;
         let mut flg = self.compression_level.as_u2() << 6;
+        if self.dictionary_id.is_some() {
+            flg |= 0b100_000;
+        }
         let check = (u16::from(cmf) << 8) + u16::from(flg);
         if check % 31 != 0 {
             flg += (31 - check % 31) as u8;
         }
         writer.write_u8(cmf)?;
         writer.write_u8(flg)?;
+        if let Some(dictionary_id) = self.dictionary_id {
+            writer.write_u32::<BigEndian>(dictionary_id)?;
+        }
         Ok(())
     }
 }
 
 /// ZLIB decoder.
+///
+/// Like `deflate::Decoder`, this implements `BufRead` as well as `Read`.
 #[derive(Debug)]
 pub struct Decoder<R> {
     header: Header,
@@ -289,11 +358,83 @@ where
     ///
     /// assert_eq!(buf, b"Hello World!");
     /// ```
-    pub fn new(mut inner: R) -> io::Result<Self> {
+    ///
+    /// If the stream requires a preset dictionary, an `InvalidData` error is returned.
+    /// Use `Decoder::with_dictionary` or `Decoder::with_dictionary_lookup` to decode such streams.
+    pub fn new(inner: R) -> io::Result<Self> {
+        Self::with_dictionary_lookup(inner, |_| None)
+    }
+
+    /// Makes a new decoder instance which uses `dictionary` as the preset dictionary.
+    ///
+    /// If the stream requires a preset dictionary and it is not `dictionary`,
+    /// an `InvalidData` error is returned.
+    /// `dictionary` is ignored if the stream does not require a preset dictionary.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Read, Write};
+    /// use libflate::zlib::{Decoder, Encoder, EncodeOptions};
+    ///
+    /// let options = EncodeOptions::new().dictionary(b"Hello");
+    /// let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    ///
+    /// let mut decoder = Decoder::with_dictionary(&encoded_data[..], b"Hello").unwrap();
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    /// assert_eq!(buf, b"Hello World!");
+    ///
+    /// assert!(Decoder::new(&encoded_data[..]).is_err());
+    /// assert!(Decoder::with_dictionary(&encoded_data[..], b"Bye").is_err());
+    /// ```
+    pub fn with_dictionary(inner: R, dictionary: &[u8]) -> io::Result<Self> {
+        Self::with_dictionary_lookup(inner, |_| Some(dictionary.to_vec()))
+    }
+
+    /// Makes a new decoder instance which looks up the preset dictionary by `lookup`.
+    ///
+    /// `lookup` is called with the dictionary identifier (DICTID) of the stream,
+    /// only if the stream requires a preset dictionary.
+    /// If it returns `None` or a dictionary of which Adler-32 checksum is not DICTID,
+    /// an `InvalidData` error is returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::collections::HashMap;
+    /// use std::io::{Read, Write};
+    /// use libflate::zlib::{Decoder, Encoder, EncodeOptions};
+    ///
+    /// let options = EncodeOptions::new().dictionary(b"Hello");
+    /// let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// let dictionary_id = encoder.header().dictionary_id().unwrap();
+    /// let encoded_data = encoder.finish().into_result().unwrap();
+    ///
+    /// let mut dictionaries = HashMap::new();
+    /// dictionaries.insert(dictionary_id, b"Hello".to_vec());
+    ///
+    /// let mut decoder =
+    ///     Decoder::with_dictionary_lookup(&encoded_data[..], |id| dictionaries.get(&id).cloned())
+    ///         .unwrap();
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    /// assert_eq!(buf, b"Hello World!");
+    /// ```
+    pub fn with_dictionary_lookup<F>(mut inner: R, lookup: F) -> io::Result<Self>
+    where
+        F: FnOnce(u32) -> Option<Vec<u8>>,
+    {
         let header = Header::read_from(&mut inner)?;
+        let dictionary = header.lookup_dictionary(lookup)?;
+        let mut reader = deflate::Decoder::new(inner);
+        if let Some(dictionary) = dictionary {
+            reader.set_dictionary(&dictionary);
+        }
         Ok(Decoder {
             header,
-            reader: deflate::Decoder::new(inner),
+            reader,
             adler32: checksum::Adler32::new(),
             eos: false,
         })
@@ -342,33 +483,78 @@ where
     pub fn into_inner(self) -> R {
         self.reader.into_inner()
     }
+
+    /// Returns the number of the bytes of the ZLIB stream (including the header and the checksum)
+    /// which have been read so far.
+    ///
+    /// After the end of the stream is reached, this is the exact size of the stream,
+    /// and the following bytes can be taken by `unread_bytes`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    /// use libflate::zlib::Decoder;
+    ///
+    /// let mut encoded_data = vec![120, 156, 243, 72, 205, 201, 201, 87, 8, 207, 47,
+    ///                             202, 73, 81, 4, 0, 28, 73, 4, 62];
+    /// encoded_data.extend_from_slice(b"trailing data");
+    ///
+    /// let mut decoder = Decoder::new(&encoded_data[..]).unwrap();
+    /// let mut buf = Vec::new();
+    /// decoder.read_to_end(&mut buf).unwrap();
+    ///
+    /// assert_eq!(buf, b"Hello World!");
+    /// assert_eq!(decoder.total_in(), 20);
+    /// assert_eq!(decoder.unread_bytes(), b"trailing data");
+    /// ```
+    pub fn total_in(&self) -> u64 {
+        self.header.encoded_len() as u64 + self.reader.total_in()
+    }
+
+    /// Returns the bytes which have been read from the inner stream but not decoded yet.
+    ///
+    /// See `deflate::Decoder::unread_bytes` for more details.
+    pub fn unread_bytes(&self) -> Vec<u8> {
+        self.reader.unread_bytes()
+    }
 }
 impl<R> io::Read for Decoder<R>
 where
     R: io::Read,
 {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        if self.eos {
-            Ok(0)
-        } else {
-            let read_size = self.reader.read(buf)?;
-            if read_size == 0 {
-                self.eos = true;
-                let adler32 = self.reader.as_inner_mut().read_u32::<BigEndian>()?;
-                if adler32 != self.adler32.value() {
-                    Err(invalid_data_error!(
-                        "Adler32 checksum mismatched: value={}, expected={}",
-                        self.adler32.value(),
-                        adler32
-                    ))
-                } else {
-                    Ok(0)
-                }
-            } else {
-                self.adler32.update(&buf[..read_size]);
-                Ok(read_size)
+        let read_size = {
+            let data = self.fill_buf()?;
+            let read_size = cmp::min(buf.len(), data.len());
+            buf[..read_size].copy_from_slice(&data[..read_size]);
+            read_size
+        };
+        self.consume(read_size);
+        Ok(read_size)
+    }
+}
+impl<R> io::BufRead for Decoder<R>
+where
+    R: io::Read,
+{
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if !self.eos && self.reader.fill_buf()?.is_empty() {
+            self.eos = true;
+            let adler32 = self.reader.read_trailer(|r| r.read_u32::<BigEndian>())?;
+            if adler32 != self.adler32.value() {
+                return Err(invalid_data_error!(
+                    "Adler32 checksum mismatched: value={}, expected={}",
+                    self.adler32.value(),
+                    adler32
+                ));
             }
         }
+        Ok(self.reader.buffered_data())
+    }
+    fn consume(&mut self, amt: usize) {
+        let amt = cmp::min(amt, self.reader.buffered_data().len());
+        self.adler32.update(&self.reader.buffered_data()[..amt]);
+        self.reader.consume(amt);
     }
 }
 
@@ -380,12 +566,14 @@ where
 {
     header: Header,
     options: deflate::EncodeOptions<E>,
+    dictionary: Option<Vec<u8>>,
 }
 impl Default for EncodeOptions<lz77::DefaultLz77Encoder> {
     fn default() -> Self {
         EncodeOptions {
             header: Header::from_lz77(&lz77::DefaultLz77Encoder::new()),
             options: Default::default(),
+            dictionary: None,
         }
     }
 }
@@ -402,6 +590,25 @@ impl EncodeOptions<lz77::DefaultLz77Encoder> {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Specifies the compression level (`0..=9`).
+    ///
+    /// The FLEVEL field of the header is also derived from `level`.
+    /// See `deflate::EncodeOptions::level` for more details.
+    ///
+    /// # Example
+    /// ```
+    /// use libflate::zlib::{CompressionLevel, Encoder, EncodeOptions};
+    ///
+    /// let options = EncodeOptions::new().level(9);
+    /// let encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// assert_eq!(encoder.header().compression_level(), CompressionLevel::Slowest);
+    /// ```
+    pub fn level(mut self, level: u8) -> Self {
+        self.options = self.options.level(level);
+        self.header.compression_level = CompressionLevel::from_level(level);
+        self
+    }
 }
 impl<E> EncodeOptions<E>
 where
@@ -421,6 +628,7 @@ where
         EncodeOptions {
             header: Header::from_lz77(&lz77),
             options: deflate::EncodeOptions::with_lz77(lz77),
+            dictionary: None,
         }
     }
 
@@ -469,6 +677,28 @@ where
         self.options = self.options.fixed_huffman_codes();
         self
     }
+
+    /// Specifies the preset dictionary.
+    ///
+    /// The FDICT flag and the dictionary identifier (the Adler-32 checksum of `dictionary`)
+    /// are written to the header.
+    /// The decoder needs the same dictionary (see `Decoder::with_dictionary`).
+    ///
+    /// # Example
+    /// ```
+    /// use libflate::zlib::{Encoder, EncodeOptions};
+    ///
+    /// let options = EncodeOptions::new().dictionary(b"Hello");
+    /// let encoder = Encoder::with_options(Vec::new(), options).unwrap();
+    /// assert_eq!(encoder.header().dictionary_id(), Some(0x05_8C_01_F5));
+    /// ```
+    pub fn dictionary(mut self, dictionary: &[u8]) -> Self {
+        let mut adler32 = checksum::Adler32::new();
+        adler32.update(dictionary);
+        self.header.dictionary_id = Some(adler32.value());
+        self.dictionary = Some(dictionary.to_vec());
+        self
+    }
 }
 
 /// ZLIB encoder.
@@ -495,8 +725,8 @@ where
     /// encoder.write_all(b"Hello World!").unwrap();
     ///
     /// assert_eq!(encoder.finish().into_result().unwrap(),
-    ///            [120, 156, 5, 128, 65, 9, 0, 0, 8, 3, 171, 104, 27, 27, 88, 64, 127,
-    ///             7, 131, 245, 127, 140, 121, 80, 173, 204, 117, 0, 28, 73, 4, 62]);
+    ///            [120, 156, 243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0,
+    ///             28, 73, 4, 62]);
     /// ```
     pub fn new(inner: W) -> io::Result<Self> {
         Self::with_options(inner, EncodeOptions::default())
@@ -526,9 +756,13 @@ where
     /// ```
     pub fn with_options(mut inner: W, options: EncodeOptions<E>) -> io::Result<Self> {
         options.header.write_to(&mut inner)?;
+        let mut writer = deflate::Encoder::with_options(inner, options.options);
+        if let Some(dictionary) = options.dictionary {
+            writer.set_dictionary(&dictionary)?;
+        }
         Ok(Encoder {
             header: options.header,
-            writer: deflate::Encoder::with_options(inner, options.options),
+            writer,
             adler32: checksum::Adler32::new(),
         })
     }
@@ -557,8 +791,8 @@ where
     /// encoder.write_all(b"Hello World!").unwrap();
     ///
     /// assert_eq!(encoder.finish().into_result().unwrap(),
-    ///            [120, 156, 5, 128, 65, 9, 0, 0, 8, 3, 171, 104, 27, 27, 88, 64, 127,
-    ///             7, 131, 245, 127, 140, 121, 80, 173, 204, 117, 0, 28, 73, 4, 62]);
+    ///            [120, 156, 243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0,
+    ///             28, 73, 4, 62]);
     /// ```
     ///
     /// # Note
@@ -587,6 +821,42 @@ where
         }
     }
 
+    /// Performs a sync flush (equivalent to `Z_SYNC_FLUSH` of zlib).
+    ///
+    /// See `deflate::Encoder::sync_flush` for more details.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::zlib::Encoder;
+    ///
+    /// let mut encoder = Encoder::new(Vec::new()).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// encoder.sync_flush().unwrap();
+    /// assert!(encoder.as_inner_ref().ends_with(&[0, 0, 255, 255]));
+    /// ```
+    pub fn sync_flush(&mut self) -> io::Result<()> {
+        self.writer.sync_flush()
+    }
+
+    /// Performs a full flush (equivalent to `Z_FULL_FLUSH` of zlib).
+    ///
+    /// See `deflate::Encoder::full_flush` for more details.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::zlib::Encoder;
+    ///
+    /// let mut encoder = Encoder::new(Vec::new()).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// encoder.full_flush().unwrap();
+    /// assert!(encoder.as_inner_ref().ends_with(&[0, 0, 255, 255]));
+    /// ```
+    pub fn full_flush(&mut self) -> io::Result<()> {
+        self.writer.full_flush()
+    }
+
     /// Returns the immutable reference to the inner stream.
     pub fn as_inner_ref(&self) -> &W {
         self.writer.as_inner_ref()
@@ -626,11 +896,230 @@ where
     }
 }
 
+/// ZLIB encoder which compresses data on multiple threads.
+///
+/// See `deflate::ParallelEncoder` for more details.
+///
+/// # Examples
+/// ```
+/// use std::io::{Read, Write};
+/// use libflate::zlib::{Decoder, ParallelEncoder};
+///
+/// let plain = (0..100_000).map(|i| format!("{} ", i)).collect::<String>();
+///
+/// let mut encoder = ParallelEncoder::new(Vec::new()).unwrap();
+/// encoder.write_all(plain.as_bytes()).unwrap();
+/// let encoded_data = encoder.finish().into_result().unwrap();
+///
+/// let mut decoder = Decoder::new(&encoded_data[..]).unwrap();
+/// let mut decoded_data = Vec::new();
+/// decoder.read_to_end(&mut decoded_data).unwrap();
+/// assert_eq!(decoded_data, plain.as_bytes());
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct ParallelEncoder<W, E = lz77::DefaultLz77Encoder> {
+    header: Header,
+    writer: deflate::ParallelEncoder<W, E>,
+}
+#[cfg(feature = "std")]
+impl<W> ParallelEncoder<W, lz77::DefaultLz77Encoder>
+where
+    W: io::Write,
+{
+    /// Makes a new encoder instance.
+    ///
+    /// Encoded ZLIB stream is written to `inner`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::zlib::ParallelEncoder;
+    ///
+    /// let mut encoder = ParallelEncoder::new(Vec::new()).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    ///
+    /// assert_eq!(encoder.finish().into_result().unwrap(),
+    ///            [120, 156, 243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0,
+    ///             28, 73, 4, 62]);
+    /// ```
+    pub fn new(inner: W) -> io::Result<Self> {
+        Self::with_options(
+            inner,
+            EncodeOptions::default(),
+            deflate::ParallelOptions::new(),
+        )
+    }
+}
+#[cfg(feature = "std")]
+impl<W, E> ParallelEncoder<W, E>
+where
+    W: io::Write,
+    E: lz77::Lz77Encode + Clone + Send + 'static,
+{
+    /// Makes a new encoder instance with specified options.
+    ///
+    /// Encoded ZLIB stream is written to `inner`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use libflate::deflate::ParallelOptions;
+    /// use libflate::zlib::{EncodeOptions, ParallelEncoder};
+    ///
+    /// let options = EncodeOptions::new().level(9);
+    /// let parallel = ParallelOptions::new().threads(4);
+    /// let mut encoder = ParallelEncoder::with_options(Vec::new(), options, parallel).unwrap();
+    /// encoder.write_all(b"Hello World!").unwrap();
+    /// encoder.finish().into_result().unwrap();
+    /// ```
+    pub fn with_options(
+        mut inner: W,
+        options: EncodeOptions<E>,
+        parallel: deflate::ParallelOptions,
+    ) -> io::Result<Self> {
+        options.header.write_to(&mut inner)?;
+        let mut writer = deflate::ParallelEncoder::with_checksum(
+            inner,
+            options.options,
+            parallel,
+            deflate::parallel::Checksum::Adler32,
+        );
+        if let Some(dictionary) = options.dictionary {
+            writer.set_dictionary(&dictionary)?;
+        }
+        Ok(ParallelEncoder {
+            header: options.header,
+            writer,
+        })
+    }
+
+    /// Returns the header of the ZLIB stream.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Writes the ZLIB trailer and returns the inner stream.
+    ///
+    /// The Adler-32 checksum of the whole input is combined from the ones of the chunks.
+    pub fn finish(self) -> Finish<W, io::Error> {
+        let (finish, adler32) = self.writer.finish_with_checksum();
+        let mut inner = finish_try!(finish);
+        match inner
+            .write_u32::<BigEndian>(adler32)
+            .and_then(|_| inner.flush())
+        {
+            Ok(_) => Finish::new(inner, None),
+            Err(e) => Finish::new(inner, Some(e)),
+        }
+    }
+
+    /// Performs a sync flush (equivalent to `Z_SYNC_FLUSH` of zlib).
+    ///
+    /// See `deflate::ParallelEncoder::sync_flush` for more details.
+    pub fn sync_flush(&mut self) -> io::Result<()> {
+        self.writer.sync_flush()
+    }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &W {
+        self.writer.as_inner_ref()
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut W {
+        self.writer.as_inner_mut()
+    }
+
+    /// Unwraps the `ParallelEncoder`, returning the inner stream.
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner()
+    }
+}
+#[cfg(feature = "std")]
+impl<W, E> io::Write for ParallelEncoder<W, E>
+where
+    W: io::Write,
+    E: lz77::Lz77Encode + Clone + Send + 'static,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+#[cfg(feature = "std")]
+impl<W, E> Complete for ParallelEncoder<W, E>
+where
+    W: io::Write,
+    E: lz77::Lz77Encode + Clone + Send + 'static,
+{
+    fn complete(self) -> io::Result<()> {
+        self.finish().into_result().map(|_| ())
+    }
+}
+
+/// Encodes `input` into `output` as a ZLIB stream, and returns the size of the encoded data.
+///
+/// See `deflate::compress_into` for more details.
+///
+/// # Examples
+/// ```
+/// use libflate::deflate::SliceError;
+/// use libflate::zlib::{self, EncodeOptions};
+///
+/// let mut buf = [0; 64];
+/// let size = zlib::compress_into(b"Hello World!", &mut buf, EncodeOptions::new()).unwrap();
+/// assert_eq!(&buf[..size], [120, 156, 243, 72, 205, 201, 201, 87, 8, 207, 47,
+///                           202, 73, 81, 4, 0, 28, 73, 4, 62]);
+///
+/// let mut buf = [0; 19];
+/// match zlib::compress_into(b"Hello World!", &mut buf, EncodeOptions::new()) {
+///     Err(SliceError::BufferTooSmall) => {}
+///     _ => unreachable!(),
+/// }
+/// ```
+pub fn compress_into<E>(
+    input: &[u8],
+    output: &mut [u8],
+    options: EncodeOptions<E>,
+) -> Result<usize, deflate::SliceError>
+where
+    E: lz77::Lz77Encode,
+{
+    let output_len = output.len();
+    let mut encoder = Encoder::with_options(output, options)?;
+    io::Write::write_all(&mut encoder, input)?;
+    let rest = encoder.finish().into_result()?;
+    Ok(output_len - rest.len())
+}
+
+/// Returns the upper bound of the size of the ZLIB stream
+/// which `zlib::compress_into` produces from `input_len` bytes.
+///
+/// # Examples
+/// ```
+/// use libflate::zlib::{self, EncodeOptions};
+///
+/// let options = EncodeOptions::new();
+/// let mut buf = vec![0; zlib::max_compressed_len(12, &options)];
+/// let size = zlib::compress_into(b"Hello World!", &mut buf, options).unwrap();
+/// assert_eq!(size, 20);
+/// ```
+pub fn max_compressed_len<E>(input_len: usize, options: &EncodeOptions<E>) -> usize
+where
+    E: lz77::Lz77Encode,
+{
+    options.header.encoded_len() + deflate::max_compressed_len(input_len, &options.options) + 4
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use alloc::string::String;
     use finish::AutoFinish;
-    use std::io;
+    use io::{self, Write};
 
     fn decode_all(buf: &[u8]) -> io::Result<Vec<u8>> {
         let mut decoder = Decoder::new(buf).unwrap();
@@ -662,6 +1151,7 @@ mod test {
             Header {
                 window_size: Lz77WindowSize::KB32,
                 compression_level: CompressionLevel::Default,
+                dictionary_id: None,
             }
         );
 
@@ -708,6 +1198,49 @@ mod test {
         assert_eq!(decode_all(&encoded).unwrap(), plain);
     }
 
+    #[test]
+    fn buf_read_works() {
+        use io::BufRead;
+
+        let plain = (0..10_000)
+            .map(|i| format!("line {}\n", i % 1000))
+            .collect::<String>();
+        let mut encoder = Encoder::new(Vec::new()).unwrap();
+        io::copy(&mut plain.as_bytes(), &mut encoder).unwrap();
+        let mut encoded = encoder.finish().into_result().unwrap();
+
+        let mut decoder = Decoder::new(&encoded[..]).unwrap();
+        let mut decoded = Vec::new();
+        loop {
+            let size = {
+                let data = decoder.fill_buf().unwrap();
+                decoded.extend_from_slice(data);
+                data.len()
+            };
+            if size == 0 {
+                break;
+            }
+            decoder.consume(size);
+        }
+        assert_eq!(decoded, plain.as_bytes());
+
+        // The checksum covers the data consumed through `BufRead`
+        let len = encoded.len();
+        encoded[len - 1] ^= 1;
+        let mut decoder = Decoder::new(&encoded[..]).unwrap();
+        loop {
+            let size = match decoder.fill_buf() {
+                Ok(data) => data.len(),
+                Err(e) => {
+                    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+                    break;
+                }
+            };
+            assert_ne!(size, 0);
+            decoder.consume(size);
+        }
+    }
+
     #[test]
     fn encoder_auto_finish_works() {
         let plain = b"Hello World! Hello ZLIB!!";
@@ -719,6 +1252,115 @@ mod test {
         assert_eq!(decode_all(&buf).unwrap(), plain);
     }
 
+    #[test]
+    fn flush_works() {
+        let mut encoder = Encoder::new(Vec::new()).unwrap();
+        encoder.write_all(b"Hello World!").unwrap();
+        encoder.sync_flush().unwrap();
+        encoder.write_all(b" Hello ZLIB!").unwrap();
+        encoder.full_flush().unwrap();
+        encoder.write_all(b" Bye!").unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+        assert_eq!(
+            decode_all(&encoded).unwrap(),
+            &b"Hello World! Hello ZLIB! Bye!"[..]
+        );
+    }
+
+    #[test]
+    fn level_encode_works() {
+        let plain = b"Hello World! Hello ZLIB!!";
+        for &(level, flg) in &[(0, 0x01), (1, 0x01), (5, 0x5E), (6, 0x9C), (9, 0xDA)] {
+            let options = EncodeOptions::new().level(level);
+            let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+            io::copy(&mut &plain[..], &mut encoder).unwrap();
+            let encoded = encoder.finish().into_result().unwrap();
+            assert_eq!(&encoded[..2], &[0x78, flg][..], "level={}", level);
+            assert_eq!(decode_all(&encoded).unwrap(), plain);
+        }
+    }
+
+    #[test]
+    fn dictionary_works() {
+        // Encoded by `zlib.compressobj(zdict=b"Hello World!")` of Python.
+        let encoded = [
+            120, 187, 28, 73, 4, 62, 243, 64, 98, 43, 64, 56, 81, 62, 158, 78, 138, 0, 103, 34, 7,
+            196,
+        ];
+        let dictionary = b"Hello World!";
+        let plain = b"Hello World! Hello ZLIB!";
+
+        let mut decoder = Decoder::with_dictionary(&encoded[..], dictionary).unwrap();
+        assert_eq!(decoder.header().dictionary_id(), Some(0x1C49_043E));
+        let mut buf = Vec::new();
+        io::copy(&mut decoder, &mut buf).unwrap();
+        assert_eq!(buf, &plain[..]);
+
+        assert!(Decoder::new(&encoded[..]).is_err());
+        assert!(Decoder::with_dictionary(&encoded[..], b"Hello World?").is_err());
+
+        let options = EncodeOptions::new().dictionary(dictionary);
+        let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+        encoder.write_all(plain).unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+        assert_eq!(&encoded[..6], &[120, 187, 28, 73, 4, 62][..]);
+        let mut decoder = Decoder::with_dictionary_lookup(&encoded[..], |id| {
+            assert_eq!(id, 0x1C49_043E);
+            Some(dictionary.to_vec())
+        })
+        .unwrap();
+        let mut buf = Vec::new();
+        io::copy(&mut decoder, &mut buf).unwrap();
+        assert_eq!(buf, &plain[..]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn parallel_encode_works() {
+        let plain = (0..100_000)
+            .map(|i| format!("{} {} ", i % 907, i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let parallel = deflate::ParallelOptions::new()
+            .threads(3)
+            .chunk_size(10_000);
+        let options = EncodeOptions::new().dictionary(b"123 456 ");
+        let mut encoder = ParallelEncoder::with_options(Vec::new(), options, parallel).unwrap();
+        encoder.write_all(&plain).unwrap();
+        let encoded = encoder.finish().into_result().unwrap();
+
+        let mut decoder = Decoder::with_dictionary(&encoded[..], b"123 456 ").unwrap();
+        let mut buf = Vec::new();
+        io::copy(&mut decoder, &mut buf).unwrap();
+        assert_eq!(buf, plain);
+    }
+
+    #[test]
+    fn compress_into_works() {
+        let plain = (0..100_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let options = || {
+            EncodeOptions::new()
+                .dictionary(b"123 456 ")
+                .block_size(4096)
+        };
+
+        let mut buf = vec![0; max_compressed_len(plain.len(), &options())];
+        let size = compress_into(&plain, &mut buf, options()).unwrap();
+
+        let mut decoder = Decoder::with_dictionary(&buf[..size], b"123 456 ").unwrap();
+        let mut decoded = Vec::new();
+        io::copy(&mut decoder, &mut decoded).unwrap();
+        assert_eq!(decoded, plain);
+
+        match compress_into(&plain, &mut buf[..size - 1], options()) {
+            Err(deflate::SliceError::BufferTooSmall) => {}
+            r => panic!("Unexpected result: {:?}", r),
+        }
+    }
+
     #[test]
     fn test_issue_2() {
         // See: https://github.com/sile/libflate/issues/2
@@ -739,4 +1381,35 @@ mod test {
             77, 217, 100, 118, 49, 10, 64, 12, 125, 51, 202, 69, 67, 181, 146, 86,
         ]);
     }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn decoder_consumes_exact_stream() {
+        let plain = (0..10_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        for options in vec![EncodeOptions::new(), EncodeOptions::new().no_compression()] {
+            let mut encoder = Encoder::with_options(Vec::new(), options).unwrap();
+            encoder.write_all(&plain).unwrap();
+            let mut encoded = encoder.finish().into_result().unwrap();
+            let stream_len = encoded.len() as u64;
+            encoded.extend_from_slice(b"next");
+
+            for &capacity in &[1, 7, 4096] {
+                let mut input = io::BufReader::with_capacity(capacity, &encoded[..]);
+                let mut decoded = Vec::new();
+                let (total_in, mut rest) = {
+                    let mut decoder = Decoder::new(&mut input).unwrap();
+                    io::copy(&mut decoder, &mut decoded).unwrap();
+                    (decoder.total_in(), decoder.unread_bytes())
+                };
+                assert_eq!(decoded, plain);
+                assert_eq!(total_in, stream_len);
+
+                io::Read::read_to_end(&mut input, &mut rest).unwrap();
+                assert_eq!(rest, b"next");
+            }
+        }
+    }
 }
@@ -0,0 +1,225 @@
+//! The ZLIB encoder and decoder which work on the asynchronous streams of `futures-io`.
+//!
+//! This module is available only if the `futures-io` feature is enabled.
+//!
+//! # Examples
+//! ```edition2018
+//! # extern crate futures;
+//! use futures::executor::block_on;
+//! use futures::io::{AsyncReadExt, AsyncWriteExt};
+//! use libflate::zlib::futures::{Decoder, Encoder};
+//!
+//! block_on(async {
+//!     // Encoding
+//!     let mut encoder = Encoder::new(Vec::new()).unwrap();
+//!     encoder.write_all(b"Hello World!").await.unwrap();
+//!     encoder.close().await.unwrap();
+//!     let encoded_data = encoder.into_inner();
+//!
+//!     // Decoding
+//!     let mut decoder = Decoder::new(&encoded_data[..]);
+//!     let mut decoded_data = Vec::new();
+//!     decoder.read_to_end(&mut decoded_data).await.unwrap();
+//!
+//!     assert_eq!(decoded_data, b"Hello World!");
+//! });
+//! ```
+use futures_io::{AsyncRead, AsyncWrite};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use super::{EncodeOptions, Header};
+use async_io::{self, AsyncIo, FuturesIo};
+use io::{self, Read, Write};
+use lz77;
+use non_blocking;
+
+/// ZLIB decoder which implements `futures_io::AsyncRead`.
+///
+/// See `deflate::futures::Decoder` for more details.
+#[derive(Debug)]
+pub struct Decoder<R> {
+    inner: non_blocking::zlib::Decoder<AsyncIo<R, FuturesIo>>,
+}
+impl<R> Decoder<R>
+where
+    R: AsyncRead + Unpin,
+{
+    /// Makes a new decoder instance.
+    ///
+    /// `inner` is to be decoded ZLIB stream.
+    ///
+    /// If the stream requires a preset dictionary, reading it results in an `InvalidData` error.
+    pub fn new(inner: R) -> Self {
+        Decoder {
+            inner: non_blocking::zlib::Decoder::new(AsyncIo::new(inner)),
+        }
+    }
+
+    /// Makes a new decoder instance which uses `dictionary` as the preset dictionary.
+    ///
+    /// See `zlib::Decoder::with_dictionary` for more details.
+    pub fn with_dictionary(inner: R, dictionary: &[u8]) -> Self {
+        Decoder {
+            inner: non_blocking::zlib::Decoder::with_dictionary(AsyncIo::new(inner), dictionary),
+        }
+    }
+
+    /// Polls the header of the ZLIB stream.
+    ///
+    /// `Poll::Pending` is returned if the header has not been read from the inner stream yet.
+    pub fn poll_header(&mut self, cx: &mut Context) -> Poll<io::Result<&Header>> {
+        self.inner.as_inner_mut().register(cx);
+        async_io::into_poll(self.inner.header())
+    }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &R {
+        self.inner.as_inner_ref().as_inner_ref()
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut R {
+        self.inner.as_inner_mut().as_inner_mut()
+    }
+
+    /// Unwraps this `Decoder`, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner().into_inner()
+    }
+}
+impl<R> AsyncRead for Decoder<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        this.inner.as_inner_mut().register(cx);
+        async_io::into_poll(this.inner.read(buf))
+    }
+}
+
+/// ZLIB encoder which implements `futures_io::AsyncWrite`.
+///
+/// See `deflate::futures::Encoder` for more details.
+#[derive(Debug)]
+pub struct Encoder<W, E = lz77::DefaultLz77Encoder> {
+    inner: non_blocking::zlib::Encoder<AsyncIo<W, FuturesIo>, E>,
+    finished: bool,
+}
+impl<W> Encoder<W, lz77::DefaultLz77Encoder>
+where
+    W: AsyncWrite + Unpin,
+{
+    /// Makes a new encoder instance.
+    ///
+    /// Encoded ZLIB stream is written to `inner`.
+    pub fn new(inner: W) -> io::Result<Self> {
+        Self::with_options(inner, EncodeOptions::default())
+    }
+}
+impl<W, E> Encoder<W, E>
+where
+    W: AsyncWrite + Unpin,
+    E: lz77::Lz77Encode,
+{
+    /// Makes a new encoder instance with specified options.
+    ///
+    /// Encoded ZLIB stream is written to `inner`.
+    pub fn with_options(inner: W, options: EncodeOptions<E>) -> io::Result<Self> {
+        let inner = non_blocking::zlib::Encoder::with_options(AsyncIo::new(inner), options)?;
+        Ok(Encoder {
+            inner,
+            finished: false,
+        })
+    }
+
+    /// Returns the header of the ZLIB stream.
+    pub fn header(&self) -> &Header {
+        self.inner.header()
+    }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &W {
+        self.inner.as_inner_ref().as_inner_ref()
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut W {
+        self.inner.as_inner_mut().as_inner_mut()
+    }
+
+    /// Unwraps the `Encoder`, returning the inner stream.
+    ///
+    /// The data which is not written to the inner stream yet is discarded.
+    pub fn into_inner(self) -> W {
+        self.inner.into_inner().into_inner()
+    }
+}
+impl<W, E> AsyncWrite for Encoder<W, E>
+where
+    W: AsyncWrite + Unpin,
+    E: lz77::Lz77Encode + Unpin,
+{
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        this.inner.as_inner_mut().register(cx);
+        async_io::into_poll(this.inner.write(buf))
+    }
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.inner.as_inner_mut().register(cx);
+        async_io::into_poll(this.inner.flush())
+    }
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.inner.as_inner_mut().register(cx);
+        if !this.finished {
+            match async_io::into_poll(this.inner.finish()) {
+                Poll::Ready(Ok(())) => this.finished = true,
+                poll => return poll,
+            }
+        }
+        async_io::into_poll(this.inner.as_inner_mut().close())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::executor::block_on;
+    use futures::future::poll_fn;
+    use futures::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use async_io::Intermittent;
+    use zlib::CompressionLevel;
+
+    #[test]
+    fn async_encode_decode_works() {
+        let plain = (0..10_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+
+        let mut encoder = Encoder::new(Intermittent::new(Vec::new())).unwrap();
+        block_on(encoder.write_all(&plain)).unwrap();
+        block_on(encoder.close()).unwrap();
+        let encoded = encoder.into_inner().into_inner();
+
+        let mut decoder = Decoder::new(Intermittent::new(&encoded[..]));
+        let level = block_on(poll_fn(|cx| {
+            decoder
+                .poll_header(cx)
+                .map_ok(|header| header.compression_level())
+        }))
+        .unwrap();
+        assert_eq!(level, CompressionLevel::Default);
+        let mut decoded = Vec::new();
+        block_on(decoder.read_to_end(&mut decoded)).unwrap();
+        assert_eq!(decoded, plain);
+    }
+}
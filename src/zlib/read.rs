@@ -0,0 +1,152 @@
+//! The ZLIB encoder which compresses the data read from an inner reader.
+//!
+//! # Examples
+//! ```
+//! use std::io::Read;
+//! use libflate::zlib::Decoder;
+//! use libflate::zlib::read::Encoder;
+//!
+//! let mut encoder = Encoder::new(&b"Hello World!"[..]).unwrap();
+//! let mut encoded_data = Vec::new();
+//! encoder.read_to_end(&mut encoded_data).unwrap();
+//!
+//! let mut decoder = Decoder::new(&encoded_data[..]).unwrap();
+//! let mut decoded_data = Vec::new();
+//! decoder.read_to_end(&mut decoded_data).unwrap();
+//!
+//! assert_eq!(decoded_data, b"Hello World!");
+//! ```
+use alloc::vec::Vec;
+
+use super::{EncodeOptions, Header};
+use adapter::ReadEncoder;
+use io::{self, Read};
+use lz77;
+
+/// ZLIB encoder which implements `io::Read`.
+///
+/// See `deflate::read::Encoder` for more details.
+#[derive(Debug)]
+pub struct Encoder<R, E = lz77::DefaultLz77Encoder> {
+    header: Header,
+    inner: ReadEncoder<super::Encoder<Vec<u8>, E>, R>,
+}
+impl<R> Encoder<R, lz77::DefaultLz77Encoder>
+where
+    R: Read,
+{
+    /// Makes a new encoder instance.
+    ///
+    /// The data read from `inner` is to be encoded.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    /// use libflate::zlib::read::Encoder;
+    ///
+    /// let mut encoder = Encoder::new(&b"Hello World!"[..]).unwrap();
+    /// let mut buf = Vec::new();
+    /// encoder.read_to_end(&mut buf).unwrap();
+    ///
+    /// assert_eq!(buf, [120, 156, 243, 72, 205, 201, 201, 87, 8, 207, 47, 202, 73, 81, 4, 0,
+    ///                  28, 73, 4, 62]);
+    /// ```
+    pub fn new(inner: R) -> io::Result<Self> {
+        Self::with_options(inner, EncodeOptions::default())
+    }
+}
+impl<R, E> Encoder<R, E>
+where
+    R: Read,
+    E: lz77::Lz77Encode,
+{
+    /// Makes a new encoder instance with specified options.
+    ///
+    /// The data read from `inner` is to be encoded.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    /// use libflate::zlib::EncodeOptions;
+    /// use libflate::zlib::read::Encoder;
+    ///
+    /// let options = EncodeOptions::new().no_compression();
+    /// let mut encoder = Encoder::with_options(&b"Hello World!"[..], options).unwrap();
+    /// let mut buf = Vec::new();
+    /// encoder.read_to_end(&mut buf).unwrap();
+    ///
+    /// assert_eq!(buf, [120, 1, 1, 12, 0, 243, 255, 72, 101, 108, 108, 111, 32, 87, 111,
+    ///                  114, 108, 100, 33, 28, 73, 4, 62]);
+    /// ```
+    pub fn with_options(inner: R, options: EncodeOptions<E>) -> io::Result<Self> {
+        let encoder = super::Encoder::with_options(Vec::new(), options)?;
+        Ok(Encoder {
+            header: encoder.header().clone(),
+            inner: ReadEncoder::new(encoder, inner),
+        })
+    }
+
+    /// Returns the header of the ZLIB stream.
+    ///
+    /// # Examples
+    /// ```
+    /// use libflate::zlib::Lz77WindowSize;
+    /// use libflate::zlib::read::Encoder;
+    ///
+    /// let encoder = Encoder::new(&b""[..]).unwrap();
+    /// assert_eq!(encoder.header().window_size(), Lz77WindowSize::KB32);
+    /// ```
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Returns the immutable reference to the inner stream.
+    pub fn as_inner_ref(&self) -> &R {
+        self.inner.as_inner_ref()
+    }
+
+    /// Returns the mutable reference to the inner stream.
+    pub fn as_inner_mut(&mut self) -> &mut R {
+        self.inner.as_inner_mut()
+    }
+
+    /// Unwraps the `Encoder`, returning the inner stream.
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+}
+impl<R, E> Read for Encoder<R, E>
+where
+    R: Read,
+    E: lz77::Lz77Encode,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::string::String;
+    use zlib::Decoder;
+
+    #[test]
+    fn read_encode_works() {
+        let plain = (0..100_000)
+            .map(|i| format!("{} ", i % 1000))
+            .collect::<String>()
+            .into_bytes();
+        let options = EncodeOptions::new().block_size(4096);
+        let mut encoder = Encoder::with_options(&plain[..], options).unwrap();
+        let mut encoded = Vec::new();
+        encoder.read_to_end(&mut encoded).unwrap();
+
+        let mut decoded = Vec::new();
+        Decoder::new(&encoded[..])
+            .unwrap()
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, plain);
+    }
+}